@@ -0,0 +1,40 @@
+#version 300 es
+precision highp float;
+
+in vec2 TexCoords;  // x - along the curtain, y - from its bottom edge up
+flat in float Seed;
+
+uniform float time;
+// 0 during the day, when the aurora can't be seen
+uniform float visibility;
+
+out vec4 FragColor;
+
+// brighter than white at the bottom edge, so that it blooms a little
+const vec3 GREEN = vec3(0.1, 1.6, 0.5);
+const vec3 VIOLET = vec3(0.6, 0.1, 0.9);
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+}
+
+// value noise, smooth between random values at integer coordinates
+float noise(vec2 p) {
+    vec2 cell = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+    float bottom = mix(hash(cell), hash(cell + vec2(1.0, 0.0)), u.x);
+    float top = mix(hash(cell + vec2(0.0, 1.0)), hash(cell + vec2(1.0, 1.0)), u.x);
+    return mix(bottom, top, u.y);
+}
+
+void main() {
+    // vertical rays drifting along the curtain, coarse and fine ones
+    float rays = 0.65 * noise(vec2(TexCoords.x * 30.0 + Seed * 7.0, time * 0.25))
+        + 0.35 * noise(vec2(TexCoords.x * 90.0 - time * 0.5, Seed));
+    // sharp bottom edge, fading towards the top
+    float height = smoothstep(0.0, 0.06, TexCoords.y) * pow(1.0 - TexCoords.y, 1.5);
+    float ends = smoothstep(0.0, 0.15, TexCoords.x) * smoothstep(1.0, 0.85, TexCoords.x);
+    vec3 color = mix(GREEN, VIOLET, smoothstep(0.25, 0.9, TexCoords.y));
+    FragColor = vec4(color, clamp(rays * rays * height * ends * visibility, 0.0, 1.0));
+}
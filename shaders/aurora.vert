@@ -0,0 +1,24 @@
+#version 300 es
+precision highp float;
+
+layout (location = 0) in vec3 aPos;
+layout (location = 2) in vec2 aTexCoords;
+layout (location = 3) in mat4 instanceModel;
+layout (location = 10) in float instanceUser;
+
+#include "camera.glsl"
+
+uniform float time;
+
+out vec2 TexCoords;
+flat out float Seed;
+
+void main() {
+    vec4 pos = instanceModel * vec4(aPos, 1.0);
+    // curtains sway slowly towards and away from the tree, waves run along them
+    vec3 outwards = normalize(vec3(pos.x, 0.0, pos.z));
+    pos.xyz += outwards * 2.0 * sin(time * 0.4 + aTexCoords.x * 12.0 + instanceUser);
+    gl_Position = projection * view * pos;
+    TexCoords = aTexCoords;
+    Seed = instanceUser;
+}
@@ -0,0 +1,11 @@
+layout (std140) uniform Fog {
+    vec3 fogColor;
+    float fogDensity;
+};
+
+// exponential-squared fog, needs the camera block for the position of the camera
+vec3 applyFog(vec3 color, vec3 position) {
+    float depth = length(position - cameraPosition);
+    float visibility = exp(-pow(fogDensity * depth, 2.0));
+    return mix(fogColor, color, visibility);
+}
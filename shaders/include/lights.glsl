@@ -0,0 +1,12 @@
+struct Light {
+    vec4 position;  // w - 1 for point lights, 0 for directional ones, xyz is the direction towards the light then
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+layout (std140) uniform Lights {
+    int lightsNo;
+    Light light[MAX_LIGHTS];
+};
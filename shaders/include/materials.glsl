@@ -0,0 +1,29 @@
+struct Material {
+    vec3 ambient;
+    vec4 diffuse;  // w - opacity
+    vec4 specular;
+    vec3 emissive;
+    vec4 maps;  // x - diffuse map, y - normal map, z - emissive map; 1.0 if the map is bound; w - reflectivity
+};
+
+layout (std140) uniform Materials {
+    uint materialsInUbo;
+    Material material[MAX_MATERIALS];
+};
+
+// materials that don't fit into the UBO, one row per material, one texel per vec4
+uniform highp sampler2D materialsOverflow;
+
+Material getMaterial(uint id) {
+    if (id < materialsInUbo) {
+        return material[id];
+    }
+    int row = int(id - materialsInUbo);
+    Material m;
+    m.ambient = texelFetch(materialsOverflow, ivec2(0, row), 0).rgb;
+    m.diffuse = texelFetch(materialsOverflow, ivec2(1, row), 0);
+    m.specular = texelFetch(materialsOverflow, ivec2(2, row), 0);
+    m.emissive = texelFetch(materialsOverflow, ivec2(3, row), 0).rgb;
+    m.maps = texelFetch(materialsOverflow, ivec2(4, row), 0);
+    return m;
+}
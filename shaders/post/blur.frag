@@ -0,0 +1,20 @@
+#version 300 es
+precision highp float;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+// one texel in the direction of the blur, horizontal and vertical passes alternate
+uniform vec2 direction;
+
+out vec4 FragColor;
+
+// 9-tap Gaussian blur, taking 5 samples thanks to linear filtering between texels
+void main() {
+    vec3 color = texture(image, TexCoords).rgb * 0.2270270270;
+    color += texture(image, TexCoords + direction * 1.3846153846).rgb * 0.3162162162;
+    color += texture(image, TexCoords - direction * 1.3846153846).rgb * 0.3162162162;
+    color += texture(image, TexCoords + direction * 3.2307692308).rgb * 0.0702702703;
+    color += texture(image, TexCoords - direction * 3.2307692308).rgb * 0.0702702703;
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 300 es
+precision highp float;
+
+in vec2 TexCoords;
+
+uniform sampler2D sceneColor;
+uniform float threshold;
+
+out vec4 FragColor;
+
+// keeps only what is brighter than the threshold, the scene is downsampled on the way
+void main() {
+    vec3 color = texture(sceneColor, TexCoords).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float contribution = max(brightness - threshold, 0.0) / max(brightness, 0.0001);
+    FragColor = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,48 @@
+#version 300 es
+precision highp float;
+
+in vec2 TexCoords;
+
+uniform sampler2D sceneColor;
+uniform sampler2D bloomColor;
+uniform float bloomIntensity;
+// seconds, animates the grain
+uniform float time;
+
+out vec4 FragColor;
+
+// fitted ACES curve by Krzysztof Narkowicz, maps HDR colors to [0, 1] keeping highlights from burning out
+vec3 aces(vec3 color) {
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// the scene is lit in linear space, displays expect sRGB, the same curve as `color::encode` on the Rust side
+vec3 linearToSrgb(vec3 color) {
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, vec3(lessThanEqual(color, vec3(0.0031308))));
+}
+
+float random(vec2 point) {
+    return fract(sin(dot(point, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    vec3 color = texture(sceneColor, TexCoords).rgb;
+#ifdef BLOOM
+    color += texture(bloomColor, TexCoords).rgb * bloomIntensity;
+#endif
+#ifdef TONE_MAPPING
+    color = aces(color);
+#endif
+#ifdef VIGNETTE
+    vec2 fromCenter = TexCoords - 0.5;
+    color *= 1.0 - smoothstep(0.2, 0.8, length(fromCenter));
+#endif
+    color = linearToSrgb(clamp(color, 0.0, 1.0));
+    // grain is added to what is shown, so that it's equally visible in dark and bright areas
+#ifdef FILM_GRAIN
+    color += (random(TexCoords + fract(time)) - 0.5) * 0.05;
+#endif
+    FragColor = vec4(clamp(color, 0.0, 1.0), 1.0);
+}
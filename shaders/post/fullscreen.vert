@@ -0,0 +1,10 @@
+#version 300 es
+precision highp float;
+
+out vec2 TexCoords;
+
+// a single triangle covering the whole screen, no vertex buffers needed
+void main() {
+    TexCoords = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    gl_Position = vec4(TexCoords * 2.0 - 1.0, 0.0, 1.0);
+}
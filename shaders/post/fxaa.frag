@@ -0,0 +1,38 @@
+#version 300 es
+precision highp float;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+uniform vec2 texelSize;
+
+out vec4 FragColor;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+// FXAA by Timothy Lottes in its compact form, blurs along edges found by differences in luma
+void main() {
+    float lumaNW = dot(texture(image, TexCoords + vec2(-1.0, -1.0) * texelSize).rgb, LUMA);
+    float lumaNE = dot(texture(image, TexCoords + vec2(1.0, -1.0) * texelSize).rgb, LUMA);
+    float lumaSW = dot(texture(image, TexCoords + vec2(-1.0, 1.0) * texelSize).rgb, LUMA);
+    float lumaSE = dot(texture(image, TexCoords + vec2(1.0, 1.0) * texelSize).rgb, LUMA);
+    vec3 colorM = texture(image, TexCoords).rgb;
+    float lumaM = dot(colorM, LUMA);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 direction = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float directionReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float inverseDirectionMin = 1.0 / (min(abs(direction.x), abs(direction.y)) + directionReduce);
+    direction = clamp(direction * inverseDirectionMin, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texelSize;
+
+    vec3 colorA = 0.5 * (texture(image, TexCoords + direction * (1.0 / 3.0 - 0.5)).rgb
+        + texture(image, TexCoords + direction * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 colorB = colorA * 0.5 + 0.25 * (texture(image, TexCoords - direction * 0.5).rgb
+        + texture(image, TexCoords + direction * 0.5).rgb);
+    float lumaB = dot(colorB, LUMA);
+    FragColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? colorA : colorB, 1.0);
+}
@@ -0,0 +1,74 @@
+#version 300 es
+precision highp float;
+
+in vec3 Direction;
+
+// gradient of the sky, the same one the environment cubemap is generated from
+uniform vec3 zenith;
+uniform vec3 horizon;
+uniform vec3 ground;
+uniform vec3 sunDirection;
+uniform vec3 moonDirection;
+uniform float starVisibility;
+uniform float time;
+
+out vec4 FragColor;
+
+// stars are placed in cells of a grid on the faces of a cube around the camera, at most one in a cell
+const float STAR_CELLS = 40.0;
+const float STAR_DENSITY = 0.12;
+// brighter than white, so that the moon and the sun bloom
+const vec3 MOON_COLOR = vec3(2.0, 2.0, 1.8);
+const vec3 SUN_COLOR = vec3(30.0, 26.0, 20.0);
+
+float hash(vec3 p) {
+    return fract(sin(dot(p, vec3(12.9898, 78.233, 37.719))) * 43758.5453);
+}
+
+float stars(vec3 dir) {
+    vec3 a = abs(dir);
+    vec2 uv;
+    float face;
+    if (a.x >= a.y && a.x >= a.z) {
+        uv = dir.yz / a.x;
+        face = sign(dir.x);
+    } else if (a.y >= a.z) {
+        uv = dir.xz / a.y;
+        face = 2.0 + sign(dir.y);
+    } else {
+        uv = dir.xy / a.z;
+        face = 4.0 + sign(dir.z);
+    }
+    vec2 cell = floor(uv * STAR_CELLS);
+    vec3 seed = vec3(cell, face);
+    if (hash(seed) > STAR_DENSITY) {
+        return 0.0;
+    }
+    vec2 center = cell + 0.2 + 0.6 * vec2(hash(seed + 1.0), hash(seed + 2.0));
+    float offset = length(uv * STAR_CELLS - center);
+    float brightness = 0.3 + 0.7 * hash(seed + 3.0);
+    float twinkle = 0.7 + 0.3 * sin(time * (1.5 + 3.0 * hash(seed + 4.0)) + 6.283 * hash(seed + 5.0));
+    return smoothstep(0.12, 0.0, offset) * brightness * twinkle;
+}
+
+// disc of the body with a faint halo around it
+float disc(vec3 dir, vec3 body, float size) {
+    float closeness = dot(dir, body);
+    return smoothstep(cos(size), cos(size * 0.8), closeness) + 0.05 * pow(max(closeness, 0.0), 400.0);
+}
+
+void main() {
+    vec3 dir = normalize(Direction);
+    float height = dir.y;
+    vec3 color = height >= 0.0
+        ? mix(horizon, zenith, sqrt(height))
+        // the ground gets bright quickly, snow reflects whatever light there is
+        : mix(horizon, ground, min(-height * 4.0, 1.0));
+    if (height > 0.0) {
+        float aboveHorizon = smoothstep(0.0, 0.15, height);
+        color += vec3(stars(dir)) * starVisibility * aboveHorizon;
+        color += MOON_COLOR * disc(dir, moonDirection, 0.03);
+        color += SUN_COLOR * disc(dir, sunDirection, 0.025);
+    }
+    FragColor = vec4(color, 1.0);
+}
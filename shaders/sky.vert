@@ -0,0 +1,14 @@
+#version 300 es
+precision highp float;
+
+layout (location = 0) in vec3 aPos;
+
+#include "camera.glsl"
+
+out vec3 Direction;
+
+void main() {
+    // the dome moves with the camera, so it looks infinitely far away
+    gl_Position = projection * mat4(mat3(view)) * vec4(aPos, 1.0);
+    Direction = aPos;
+}
@@ -2,63 +2,109 @@
 precision highp float;
 precision highp int;
 
-struct Light {
-    vec3 position;
-
-    vec3 ambient;
-    vec3 diffuse;
-    vec3 specular;
-};
-
-struct Material {
-    vec3 ambient;
-    vec3 diffuse;
-    vec4 specular;
-};
-
 in vec3 FragPosition;
 in vec3 Normal;
+in vec2 TexCoords;
 flat in uint MaterialId;
+flat in vec3 Tint;
+flat in float Emissive;
 
-layout (std140) uniform Camera {
-    vec3 cameraPosition;
-    mat4 view;
-    mat4 projection;
-};
+#include "camera.glsl"
+#include "lights.glsl"
+#include "materials.glsl"
+#include "fog.glsl"
 
-layout (std140) uniform Lights {
-    int lightsNo;
-    Light light[4];
-};
-
-layout (std140) uniform Materials {
-    Material material[100];
-};
+uniform sampler2D diffuseMap;
+uniform sampler2D normalMap;
+uniform sampler2D emissiveMap;
+#ifdef REFLECTIONS
+uniform samplerCube environment;
+#endif
 
 out vec4 FragColor;
 
-vec3 calcLight(Light light);
+vec3 perturbNormal(vec3 normal);
+vec3 calcLight(Light light, Material mat, vec3 norm, vec3 diffuseColor);
+vec4 reflectEnvironment(vec4 color, Material mat, vec3 norm);
 
 void main() {
-    vec3 result = vec3(0.0);
+    Material mat = getMaterial(MaterialId);
+    mat.ambient *= Tint;
+    vec3 norm = normalize(Normal);
+#ifdef DOUBLE_SIDED
+    // back faces aren't culled, they are lit as if they were facing the camera
+    if (!gl_FrontFacing) {
+        norm = -norm;
+    }
+#endif
+#ifdef NORMAL_MAPPING
+    if (mat.maps.y > 0.5) {
+        norm = perturbNormal(norm);
+    }
+#endif
+    vec4 baseColor = vec4(mat.diffuse.rgb * Tint, mat.diffuse.a);
+    if (mat.maps.x > 0.5) {
+        baseColor *= texture(diffuseMap, TexCoords);
+    }
+    vec3 diffuseColor = baseColor.rgb;
+    vec3 emissive = mat.emissive;
+    if (mat.maps.z > 0.5) {
+        emissive *= texture(emissiveMap, TexCoords).rgb;
+    }
+    emissive += Emissive * diffuseColor;
+
+    vec4 result = vec4(emissive, baseColor.a);
     for (int i = 0; i < lightsNo; i++) {
-        result += calcLight(light[i]);
+        result.rgb += calcLight(light[i], mat, norm, diffuseColor);
+    }
+#ifdef REFLECTIONS
+    if (mat.maps.w > 0.0) {
+        result = reflectEnvironment(result, mat, norm);
     }
-    FragColor = vec4(result, 1.0);
+#endif
+    result.rgb = applyFog(result.rgb, FragPosition);
+    // opaque pipelines don't blend, alpha matters only for translucent materials
+    FragColor = result;
 }
 
-vec3 calcLight(Light light) {
-    vec3 ambient = light.ambient * material[MaterialId].ambient;
+// there are no tangents in the vertex data, so the tangent frame is derived from screen space derivatives
+vec3 perturbNormal(vec3 normal) {
+    vec3 tangentNormal = texture(normalMap, TexCoords).xyz * 2.0 - 1.0;
+    vec3 dp1 = dFdx(FragPosition);
+    vec3 dp2 = dFdy(FragPosition);
+    vec2 duv1 = dFdx(TexCoords);
+    vec2 duv2 = dFdy(TexCoords);
+    vec3 dp2perp = cross(dp2, normal);
+    vec3 dp1perp = cross(normal, dp1);
+    vec3 tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+    float invmax = inversesqrt(max(dot(tangent, tangent), dot(bitangent, bitangent)));
+    return normalize(mat3(tangent * invmax, bitangent * invmax, normal) * tangentNormal);
+}
 
-    vec3 norm = normalize(Normal);
-    vec3 lightDir = normalize(light.position - FragPosition);
+#ifdef REFLECTIONS
+// mirrors the environment more at grazing angles (Schlick's Fresnel approximation), reflectivity is the amount head on.
+// Reflected light doesn't pass through, so translucent surfaces get more opaque where they mirror more.
+vec4 reflectEnvironment(vec4 color, Material mat, vec3 norm) {
+    vec3 viewDir = normalize(cameraPosition - FragPosition);
+    vec3 reflected = texture(environment, reflect(-viewDir, norm)).rgb * mat.specular.rgb;
+    float reflectivity = mat.maps.w;
+    float fresnel = reflectivity + (1.0 - reflectivity) * pow(1.0 - max(dot(norm, viewDir), 0.0), 5.0);
+    return vec4(mix(color.rgb, reflected, fresnel), mix(color.a, 1.0, fresnel));
+}
+#endif
+
+vec3 calcLight(Light light, Material mat, vec3 norm, vec3 diffuseColor) {
+    vec3 ambient = light.ambient * mat.ambient;
+
+    vec3 lightDir = normalize(light.position.xyz - FragPosition * light.position.w);
     float diff = max(dot(norm, lightDir), 0.0);
-    vec3 diffuse = diff * light.diffuse * material[MaterialId].diffuse;
+    vec3 diffuse = diff * light.diffuse * diffuseColor;
 
     vec3 viewDir = normalize(cameraPosition - FragPosition);
     vec3 halfwayDir = normalize(lightDir + viewDir);
-    float spec = pow(max(dot(norm, halfwayDir), 0.0), material[MaterialId].specular.w);
-    vec3 specular = spec * light.specular * vec3(material[MaterialId].specular);
+    float spec = pow(max(dot(norm, halfwayDir), 0.0), mat.specular.w);
+    vec3 specular = spec * light.specular * vec3(mat.specular);
 
     return ambient + diffuse + specular;
 }
@@ -4,23 +4,28 @@ precision highp int;
 
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec3 aNormal;
-layout (location = 2) in mat4 instanceModel;
-layout (location = 6) in float instanceMaterialId;
+layout (location = 2) in vec2 aTexCoords;
+layout (location = 3) in mat4 instanceModel;
+layout (location = 7) in uint instanceMaterialId;
+layout (location = 8) in vec3 instanceTint;
+layout (location = 9) in float instanceEmissive;
 
-layout (std140) uniform Camera {
-    vec3 cameraPosition;
-    mat4 view;
-    mat4 projection;
-};
+#include "camera.glsl"
 
 out vec3 FragPosition;
 out vec3 Normal;
+out vec2 TexCoords;
 flat out uint MaterialId;
+flat out vec3 Tint;
+flat out float Emissive;
 
 void main() {
     vec4 pos = instanceModel * vec4(aPos, 1.0);
     gl_Position = projection * view * pos;
     FragPosition = vec3(pos);
     Normal = mat3(transpose(inverse(instanceModel))) * aNormal;
-    MaterialId = uint(instanceMaterialId);
+    TexCoords = aTexCoords;
+    MaterialId = instanceMaterialId;
+    Tint = instanceTint;
+    Emissive = instanceEmissive;
 }
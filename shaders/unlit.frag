@@ -0,0 +1,24 @@
+#version 300 es
+precision highp float;
+precision highp int;
+
+in vec3 FragPosition;
+in vec3 Normal;
+flat in uint MaterialId;
+flat in vec3 Tint;
+flat in float Emissive;
+
+#include "camera.glsl"
+#include "materials.glsl"
+#include "fog.glsl"
+
+out vec4 FragColor;
+
+void main() {
+    Material mat = getMaterial(MaterialId);
+    vec3 diffuseColor = mat.diffuse.rgb * Tint;
+    // no lights, surfaces facing up are a bit brighter so that shapes stay readable
+    float shade = 0.7 + 0.3 * max(normalize(Normal).y, 0.0);
+    vec3 color = diffuseColor * shade + mat.emissive + Emissive * diffuseColor;
+    FragColor = vec4(applyFog(color, FragPosition), mat.diffuse.a);
+}
@@ -0,0 +1,28 @@
+#version 300 es
+precision highp float;
+precision highp int;
+
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 3) in mat4 instanceModel;
+layout (location = 7) in uint instanceMaterialId;
+layout (location = 8) in vec3 instanceTint;
+layout (location = 9) in float instanceEmissive;
+
+#include "camera.glsl"
+
+out vec3 FragPosition;
+out vec3 Normal;
+flat out uint MaterialId;
+flat out vec3 Tint;
+flat out float Emissive;
+
+void main() {
+    vec4 pos = instanceModel * vec4(aPos, 1.0);
+    gl_Position = projection * view * pos;
+    FragPosition = vec3(pos);
+    Normal = mat3(instanceModel) * aNormal;
+    MaterialId = instanceMaterialId;
+    Tint = instanceTint;
+    Emissive = instanceEmissive;
+}
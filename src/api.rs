@@ -0,0 +1,208 @@
+//! Functions exported to JavaScript, they operate on the scene started by `start()`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use cgmath::InnerSpace;
+use wasm_bindgen::prelude::*;
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::color::Srgb;
+use crate::exporter;
+use crate::gpu;
+use crate::loader::NormalMode;
+use crate::render::post;
+use crate::shader;
+use crate::xmas_tree::scene::Scene;
+
+struct App {
+    gl: GL,
+    scene: Rc<RefCell<Scene>>,
+}
+
+thread_local! {
+    static APP: RefCell<Option<App>> = const { RefCell::new(None) };
+}
+
+pub fn register(gl: GL, scene: Rc<RefCell<Scene>>) {
+    APP.with(|app| *app.borrow_mut() = Some(App { gl, scene }));
+}
+
+fn with_scene<R>(f: impl FnOnce(&GL, &mut Scene) -> R) -> Result<R, JsValue> {
+    APP.with(|app| match app.borrow().as_ref() {
+        Some(app) => Ok(f(&app.gl, &mut app.scene.borrow_mut())),
+        None => Err("The scene is not started yet".into()),
+    })
+}
+
+/// Names of all materials that can be recolored
+#[wasm_bindgen]
+pub fn material_names() -> Result<js_sys::Array, JsValue> {
+    with_scene(|_gl, scene| scene.materials.names().into_iter().map(JsValue::from).collect())
+}
+
+/// Diffuse color of the material as sRGB `#rrggbb`, ready for `<input type="color">`
+#[wasm_bindgen]
+pub fn material_color(name: &str) -> Result<String, JsValue> {
+    with_scene(|_gl, scene| {
+        let material_id = scene.materials.get_by_name(name).ok_or_else(|| unknown_material(name))?;
+        Ok(scene.materials.get(material_id).unwrap().diffuse.to_srgb().to_hex())
+    })?
+}
+
+/// Changes diffuse color of the material to the sRGB `color`, ambient color follows keeping its original brightness
+#[wasm_bindgen]
+pub fn set_material_color(name: &str, color: &str) -> Result<(), JsValue> {
+    let diffuse = Srgb::from_hex(color).ok_or_else(|| JsValue::from(format!("Invalid color '{}', expected #rrggbb", color)))?.to_linear();
+    with_scene(|gl, scene| {
+        let material_id = scene.materials.get_by_name(name).ok_or_else(|| unknown_material(name))?;
+        let mut material = *scene.materials.get(material_id).unwrap();
+        let brightness = if material.diffuse.0.magnitude() > 0. { material.ambient.0.magnitude() / material.diffuse.0.magnitude() } else { 1. };
+        material.ambient = diffuse * brightness;
+        material.diffuse = diffuse;
+        scene.materials.update(gl, material_id, material);
+        Ok(())
+    })?
+}
+
+/// Turns an optional shader feature on or off, e.g. `"NORMAL_MAPPING"`
+#[wasm_bindgen]
+pub fn set_shader_feature(feature: &str, enabled: bool) -> Result<(), JsValue> {
+    if !shader::FEATURES.contains(&feature) {
+        return Err(format!("Unknown shader feature '{}', expected one of {:?}", feature, shader::FEATURES).into());
+    }
+    with_scene(|gl, scene| scene.set_shader_feature(gl, feature, enabled).map_err(|e| JsValue::from(e.to_string())))?
+}
+
+/// Turns a post-processing effect on or off, e.g. `"BLOOM"`
+#[wasm_bindgen]
+pub fn set_post_effect(effect: &str, enabled: bool) -> Result<(), JsValue> {
+    if !post::EFFECTS.contains(&effect) {
+        return Err(format!("Unknown post-processing effect '{}', expected one of {:?}", effect, post::EFFECTS).into());
+    }
+    with_scene(|gl, scene| scene.set_post_effect(gl, effect, enabled).map_err(|e| JsValue::from(e.to_string())))?
+}
+
+/// Turns MSAA on with the number of samples per pixel, or off with 0, returns the number the device can actually do
+#[wasm_bindgen]
+pub fn set_msaa_samples(samples: u32) -> Result<u32, JsValue> {
+    with_scene(|gl, scene| scene.set_msaa_samples(gl, samples.min(i32::MAX as u32) as i32) as u32)
+}
+
+/// Shows or hides the aurora, the page can turn it on from the start with `<canvas data-aurora>`
+#[wasm_bindgen]
+pub fn set_aurora(enabled: bool) -> Result<(), JsValue> {
+    with_scene(|gl, scene| {
+        let mut config = scene.config();
+        config.aurora = enabled;
+        scene.configure(gl, config)
+    })
+}
+
+/// Sets the fog density in clear air and how much a full snowfall adds to it, 0 for both clears the air
+#[wasm_bindgen]
+pub fn set_fog(density: f32, snow_haze: f32) -> Result<(), JsValue> {
+    if !(density.is_finite() && density >= 0. && snow_haze.is_finite() && snow_haze >= 0.) {
+        return Err(format!("Invalid fog density {} or snow haze {}", density, snow_haze).into());
+    }
+    with_scene(|gl, scene| {
+        let mut config = scene.config();
+        config.atmosphere.fog_density = density;
+        config.atmosphere.snow_haze = snow_haze;
+        scene.configure(gl, config)
+    })
+}
+
+/// Sets how hard it snows, from 0 for no snow to 1, the fog thickens with it
+#[wasm_bindgen]
+pub fn set_snow_intensity(intensity: f32) -> Result<(), JsValue> {
+    if !intensity.is_finite() {
+        return Err(format!("Invalid snow intensity {}", intensity).into());
+    }
+    with_scene(|gl, scene| {
+        let mut config = scene.config();
+        config.atmosphere.snow_intensity = intensity.clamp(0., 1.);
+        scene.configure(gl, config)
+    })
+}
+
+/// Starts the day cycle, a whole day takes `seconds`, 0 stops the time where it is
+#[wasm_bindgen]
+pub fn set_day_length(seconds: f32) -> Result<(), JsValue> {
+    with_scene(|_gl, scene| scene.set_day_length(seconds))
+}
+
+/// Moves the sun and the moon to the time of day, in hours since midnight
+#[wasm_bindgen]
+pub fn set_time_of_day(hours: f32) -> Result<(), JsValue> {
+    if !hours.is_finite() {
+        return Err(format!("Invalid time of day {}", hours).into());
+    }
+    with_scene(|gl, scene| scene.set_time_of_day(gl, hours / 24.))
+}
+
+/// Adds an OBJ model to the scene, `mtl` is the material library it uses, e.g. from a dropped file or `fetch`.
+/// `images` maps texture file names to their contents as `Uint8Array`s, textures missing there are fetched from the site.
+/// Normals missing in the model are generated, smooth or flat.
+#[wasm_bindgen]
+pub fn load_obj(obj: &[u8], mtl: Option<Vec<u8>>, images: &js_sys::Map, flat_normals: bool) -> Result<(), JsValue> {
+    let normals = if flat_normals { NormalMode::Flat } else { NormalMode::Smooth };
+    let images = files(images)?;
+    with_scene(|gl, scene| scene.import_obj(gl, obj, mtl.as_deref(), &images, normals).map_err(|e| JsValue::from(e.to_string())))?
+}
+
+/// Adds a glTF model (`.gltf` or `.glb`) to the scene, or puts it in place of the tree when `replace_tree` is set.
+/// `resources` maps URIs used in the model, like `scene.bin`, to their contents as `Uint8Array`s.
+#[wasm_bindgen]
+pub fn load_gltf(name: &str, bytes: &[u8], resources: &js_sys::Map, replace_tree: bool) -> Result<(), JsValue> {
+    let files = files(resources)?;
+    with_scene(|gl, scene| {
+        let result = if replace_tree {
+            scene.replace_tree_with_gltf(gl, name, bytes, &files)
+        } else {
+            scene.import_gltf(gl, name, bytes, &files)
+        };
+        result.map_err(|e| JsValue::from(e.to_string()))
+    })?
+}
+
+/// The decorated scene as a binary glTF file, ready to be downloaded as `.glb`
+#[wasm_bindgen]
+pub fn export_glb() -> Result<Vec<u8>, JsValue> {
+    with_scene(|_gl, scene| exporter::gltf::write_glb(&scene.export()))
+}
+
+/// The decorated scene as `[obj, mtl]` sources, the OBJ references the MTL as `mtl_file`
+#[wasm_bindgen]
+pub fn export_obj(mtl_file: &str) -> Result<js_sys::Array, JsValue> {
+    with_scene(|_gl, scene| {
+        let (obj, mtl) = exporter::obj::write(&scene.export(), mtl_file);
+        [obj, mtl].iter().map(|source| JsValue::from(source.as_str())).collect()
+    })
+}
+
+/// Numbers of WebGL objects currently alive, by kind, e.g. `{ Buffer: 21, VertexArray: 7, ... }`
+#[wasm_bindgen]
+pub fn gpu_resource_counts() -> Result<js_sys::Object, JsValue> {
+    let counts = js_sys::Object::new();
+    for (kind, count) in gpu::live_counts() {
+        js_sys::Reflect::set(&counts, &format!("{:?}", kind).into(), &(count as u32).into())?;
+    }
+    Ok(counts)
+}
+
+/// Contents of a `Map` from file names to `Uint8Array`s
+fn files(map: &js_sys::Map) -> Result<HashMap<String, Vec<u8>>, JsValue> {
+    let mut files = HashMap::new();
+    for entry in map.entries() {
+        let entry: js_sys::Array = entry?.into();
+        let name = entry.get(0).as_string().ok_or_else(|| JsValue::from("File names have to be strings"))?;
+        files.insert(name, js_sys::Uint8Array::new(&entry.get(1)).to_vec());
+    }
+    Ok(files)
+}
+
+fn unknown_material(name: &str) -> JsValue {
+    format!("Unknown material '{}'", name).into()
+}
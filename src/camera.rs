@@ -1,60 +1,98 @@
-use cgmath::{Deg, Matrix4, perspective, Point3, vec3, Vector4};
-use wasm_bindgen::__rt::core::mem;
-use web_sys::{WebGl2RenderingContext as GL, WebGlBuffer};
+use core::f32::consts::PI;
+use std::cell::Cell;
+use std::rc::Rc;
+
+use cgmath::{Deg, Matrix4, perspective, Point3, vec3};
+use web_sys::WebGl2RenderingContext as GL;
 
 use crate::coords::SphericalPoint3;
+use crate::culling::Frustum;
+use crate::gpu::Buffer;
 use crate::shader::CAMERA_UBO_BINDING_POINT;
+#[cfg(test)]
+use crate::shader::validation::UniformBlock;
+
+// std140 offsets in the Camera block, vec3 takes as much space as vec4
+const POSITION_OFFSET: i32 = 0;
+const VIEW_OFFSET: i32 = 16;
+const PROJECTION_OFFSET: i32 = VIEW_OFFSET + 64;
+const UBO_SIZE: i32 = PROJECTION_OFFSET + 64;
+/// Zooming keeps the camera outside of the tree and well inside the sky dome
+const MIN_DISTANCE: f32 = 8.;
+pub const MAX_DISTANCE: f32 = 40.;
+/// Far enough to see the aurora on the other side of the tree from `MAX_DISTANCE`
+pub const FAR_PLANE: f32 = 130.;
+/// Polar angle the camera keeps from the poles, right above or below the tree the up vector would flip the view
+const POLE_MARGIN: f32 = 0.01;
+
+/// What the camera sees in the current frame, models use it to skip invisible instances
+pub struct View {
+    pub position: Point3<f32>,
+    pub frustum: Frustum,
+}
 
+/// Clones share the position, so rotating any of them moves the camera used for drawing
 #[derive(Clone)]
 pub struct Camera {
-    position: SphericalPoint3<f32>,
+    position: Rc<Cell<SphericalPoint3<f32>>>,
+    /// Where the camera starts, resetting the view goes back there
+    home: SphericalPoint3<f32>,
     look_at: Point3<f32>,
-    ubo: WebGlBuffer,
+    ubo: Rc<Buffer>,
 }
 
 impl Camera {
     pub fn new(gl: &GL, position: SphericalPoint3<f32>, look_at: Point3<f32>) -> Self {
-        let ubo = Camera::setup_camera_ubo(&gl);
-        let camera = Camera { position, look_at, ubo };
+        let ubo = Camera::setup_camera_ubo(gl);
+        let camera = Camera { position: Rc::new(Cell::new(position)), home: position, look_at, ubo: Rc::new(ubo) };
         camera.update_uniforms(gl);
         camera
     }
 
-    fn setup_camera_ubo(gl: &GL) -> WebGlBuffer {
-        let camera_ubo = gl.create_buffer().unwrap();
-        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&camera_ubo));
-        let matrix_size = mem::size_of::<Matrix4<f32>>() as i32;
-        let vector3_size = mem::size_of::<Vector4<f32>>() as i32; // there's no mistake, Vector3 takes the same amount of memory as Vector4
-        gl.buffer_data_with_i32(GL::UNIFORM_BUFFER, vector3_size + 2 * matrix_size, GL::STATIC_DRAW);
-        gl.bind_buffer_base(GL::UNIFORM_BUFFER, CAMERA_UBO_BINDING_POINT, Some(&camera_ubo));
+    fn setup_camera_ubo(gl: &GL) -> Buffer {
+        let camera_ubo = Buffer::new(gl);
+        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&*camera_ubo));
+        gl.buffer_data_with_i32(GL::UNIFORM_BUFFER, UBO_SIZE, GL::STATIC_DRAW);
+        gl.bind_buffer_base(GL::UNIFORM_BUFFER, CAMERA_UBO_BINDING_POINT, Some(&*camera_ubo));
         gl.bind_buffer(GL::UNIFORM_BUFFER, None);
         camera_ubo
     }
 
-    fn update_uniforms(&self, gl: &GL) {
+    fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at(self.position.get().into(), self.look_at, vec3(0.0, 1.0, 0.0))
+    }
+
+    fn projection_matrix(gl: &GL) -> Matrix4<f32> {
         let (window_width, window_height) = (gl.drawing_buffer_width() as f32, gl.drawing_buffer_height() as f32);
-        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&self.ubo));
-        let matrix_size = mem::size_of::<Matrix4<f32>>() as i32;
-        let vector3_size = mem::size_of::<Vector4<f32>>() as i32; // there's no mistake, Vector3 takes the same amount of memory as Vector4
+        perspective(Deg(45.0), window_width / window_height, 0.1, FAR_PLANE)
+    }
+
+    pub fn view(&self, gl: &GL) -> View {
+        let view_projection = Self::projection_matrix(gl) * self.view_matrix();
+        View { position: self.position.get().into(), frustum: Frustum::from_matrix(&view_projection) }
+    }
+
+    fn update_uniforms(&self, gl: &GL) {
+        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&**self.ubo));
         unsafe {
-            let pos: Point3<f32> = self.position.into();
+            let pos: Point3<f32> = self.position.get().into();
             let pos_array: [f32; 3] = pos.into();
             let pos_js_array = js_sys::Float32Array::view(&pos_array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, 0, &pos_js_array);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, POSITION_OFFSET, &pos_js_array);
         }
 
         unsafe {
-            let view: Matrix4<f32> = Matrix4::look_at(self.position.into(), self.look_at, vec3(0.0, 1.0, 0.0));
+            let view = self.view_matrix();
             let view_array: &[f32; 16] = view.as_ref();
             let view_js_array = js_sys::Float32Array::view(view_array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, vector3_size, &view_js_array);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, VIEW_OFFSET, &view_js_array);
         }
 
         unsafe {
-            let projection = perspective(Deg(45.0), window_width / window_height, 0.1, 100.0);
+            let projection = Self::projection_matrix(gl);
             let projection_array: &[f32; 16] = projection.as_ref();
             let projection_js_array = js_sys::Float32Array::view(projection_array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, vector3_size + matrix_size, &projection_js_array);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, PROJECTION_OFFSET, &projection_js_array);
         }
         gl.bind_buffer(GL::UNIFORM_BUFFER, None);
     }
@@ -64,12 +102,68 @@ impl Camera {
     }
 
     pub fn rotate_horizontally(&mut self, gl: &GL, angle: f32) {
-        self.position.phi += angle;
+        let mut position = self.position.get();
+        position.phi += angle;
+        self.position.set(position);
         self.update_uniforms(gl);
     }
 
     pub fn rotate_vertically(&mut self, gl: &GL, angle: f32) {
-        self.position.theta += angle;
+        let mut position = self.position.get();
+        position.theta = rotated_vertically(position.theta, angle);
+        self.position.set(position);
         self.update_uniforms(gl);
     }
+
+    /// Moves the camera closer with `factor` below 1 and further away above it, within limits
+    pub fn zoom(&mut self, gl: &GL, factor: f32) {
+        let mut position = self.position.get();
+        position.r = zoomed(position.r, factor);
+        self.position.set(position);
+        self.update_uniforms(gl);
+    }
+
+    /// Goes back to the starting position
+    pub fn reset(&mut self, gl: &GL) {
+        self.position.set(self.home);
+        self.update_uniforms(gl);
+    }
+}
+
+fn zoomed(distance: f32, factor: f32) -> f32 {
+    (distance * factor).clamp(MIN_DISTANCE, MAX_DISTANCE)
+}
+
+fn rotated_vertically(theta: f32, angle: f32) -> f32 {
+    (theta + angle).clamp(POLE_MARGIN, PI - POLE_MARGIN)
+}
+
+/// Layout the Camera block is written with, shaders are checked against it in tests
+#[cfg(test)]
+pub fn ubo_layout() -> UniformBlock {
+    UniformBlock::new("Camera", CAMERA_UBO_BINDING_POINT, UBO_SIZE)
+        .member("cameraPosition", POSITION_OFFSET)
+        .member("view", VIEW_OFFSET)
+        .member("projection", PROJECTION_OFFSET)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::f32::consts::PI;
+
+    use crate::camera::{MAX_DISTANCE, MIN_DISTANCE, POLE_MARGIN, rotated_vertically, zoomed};
+
+    #[test]
+    fn zoom_stays_within_limits() {
+        assert_eq!(zoomed(20., 0.5), 10.);
+        assert_eq!(zoomed(10., 0.1), MIN_DISTANCE);
+        assert_eq!(zoomed(30., 10.), MAX_DISTANCE);
+    }
+
+    #[test]
+    fn vertical_rotation_stops_before_the_poles() {
+        assert_eq!(rotated_vertically(1., 0.5), 1.5);
+        assert_eq!(rotated_vertically(0.1, -1.), POLE_MARGIN);
+        assert_eq!(rotated_vertically(3., 1.), PI - POLE_MARGIN);
+    }
 }
@@ -0,0 +1,137 @@
+//! Colors are authored in sRGB, like in color pickers, MTL files and most images, but light only adds up correctly
+//! in linear space. Shaders work with linear colors only, the final post-processing pass encodes them for the display.
+use std::ops::Mul;
+
+use cgmath::Vector3;
+
+/// How the values of a color or a texture are meant, so that it can be converted for lighting
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Colors as they are shown, e.g. color and emission maps
+    Srgb,
+    /// Plain numbers that shouldn't be converted, e.g. normal maps
+    Linear,
+}
+
+/// Gamma encoded color, as picked by users and stored in files
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Srgb(pub Vector3<f32>);
+
+/// Color proportional to the amount of light, channels can go above 1 for emission and HDR
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LinearRgb(pub Vector3<f32>);
+
+impl Srgb {
+    pub const fn new(r: f32, g: f32, b: f32) -> Self {
+        Srgb(Vector3 { x: r, y: g, z: b })
+    }
+
+    pub fn to_linear(self) -> LinearRgb {
+        LinearRgb(self.0.map(decode))
+    }
+
+    /// Parses `#rrggbb`, the format of `<input type="color">`
+    pub fn from_hex(color: &str) -> Option<Self> {
+        let hex = color.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok().map(|c| c as f32 / 255.);
+        Some(Srgb::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// Formats the color as `#rrggbb`, channels out of range are clamped
+    pub fn to_hex(self) -> String {
+        let channel = |c: f32| (c.clamp(0., 1.) * 255.).round() as u8;
+        format!("#{:02x}{:02x}{:02x}", channel(self.0.x), channel(self.0.y), channel(self.0.z))
+    }
+}
+
+impl LinearRgb {
+    pub const BLACK: LinearRgb = LinearRgb::new(0., 0., 0.);
+
+    pub const fn new(r: f32, g: f32, b: f32) -> Self {
+        LinearRgb(Vector3 { x: r, y: g, z: b })
+    }
+
+    pub fn to_srgb(self) -> Srgb {
+        Srgb(self.0.map(encode))
+    }
+
+    pub fn is_black(self) -> bool {
+        self == LinearRgb::BLACK
+    }
+
+    /// Blends linearly towards `other`, 0 keeps this color, 1 gives the other one
+    pub fn mix(self, other: LinearRgb, amount: f32) -> LinearRgb {
+        LinearRgb(self.0 + (other.0 - self.0) * amount)
+    }
+}
+
+impl Mul<f32> for LinearRgb {
+    type Output = LinearRgb;
+
+    fn mul(self, factor: f32) -> LinearRgb {
+        LinearRgb(self.0 * factor)
+    }
+}
+
+impl From<LinearRgb> for [f32; 3] {
+    fn from(color: LinearRgb) -> Self {
+        color.0.into()
+    }
+}
+
+/// sRGB transfer function, the same as GPUs use for sRGB textures
+fn decode(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Inverse of `decode`, `linearToSrgb` in the shaders
+fn encode(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1. / 2.4) - 0.055 }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::vec3;
+
+    use crate::color::{LinearRgb, Srgb};
+
+    #[test]
+    fn middle_gray_is_darker_in_linear_space() {
+        let linear = Srgb::new(0.5, 0., 1.).to_linear();
+
+        assert!((linear.0.x - 0.214).abs() < 0.001, "{:?}", linear);
+        assert_eq!(linear.0.y, 0.);
+        assert_eq!(linear.0.z, 1.);
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        for &c in &[0., 0.002, 0.04, 0.3, 0.73, 1.] {
+            let color = LinearRgb::new(c, c, c);
+
+            assert!((color.to_srgb().to_linear().0 - color.0).x.abs() < 1e-6, "{}", c);
+        }
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(Srgb::from_hex("#ff0080"), Some(Srgb::new(1., 0., 128. / 255.)));
+        assert_eq!(Srgb::from_hex("ff0080"), None);
+        assert_eq!(Srgb::from_hex("#ff00"), None);
+        assert_eq!(Srgb::from_hex("#gg0000"), None);
+    }
+
+    #[test]
+    fn formats_hex_colors() {
+        assert_eq!(Srgb::new(1., 0., 0.5).to_hex(), "#ff0080");
+        assert_eq!(Srgb(vec3(2., -1., 0.)).to_hex(), "#ff0000");
+    }
+
+    #[test]
+    fn hex_colors_round_trip_through_linear_space() {
+        assert_eq!(Srgb::from_hex("#1a2b3c").unwrap().to_linear().to_srgb().to_hex(), "#1a2b3c");
+    }
+}
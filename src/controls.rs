@@ -0,0 +1,67 @@
+//! Keyboard controls of the canvas, an alternative to dragging the scene around with the mouse or a finger.
+use core::f32::consts::PI;
+
+/// Angle a single key press orbits the camera by, holding the key repeats it
+const ORBIT_STEP: f32 = PI / 36.;
+/// Distance to the tree changes by this factor with every key press
+const ZOOM_STEP: f32 = 0.9;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum KeyCommand {
+    /// Angles to rotate the camera by horizontally and vertically, the same way dragging does
+    Orbit(f32, f32),
+    /// Factor to multiply the distance of the camera by
+    Zoom(f32),
+    ToggleSnow,
+    ResetView,
+}
+
+/// Command of the key, as in `KeyboardEvent.key`, `None` leaves the key to the browser
+pub fn key_command(key: &str) -> Option<KeyCommand> {
+    match key {
+        "ArrowLeft" | "a" | "A" => Some(KeyCommand::Orbit(ORBIT_STEP, 0.)),
+        "ArrowRight" | "d" | "D" => Some(KeyCommand::Orbit(-ORBIT_STEP, 0.)),
+        "ArrowUp" | "w" | "W" => Some(KeyCommand::Orbit(0., ORBIT_STEP)),
+        "ArrowDown" | "s" | "S" => Some(KeyCommand::Orbit(0., -ORBIT_STEP)),
+        // "=" is "+" without Shift on most layouts
+        "+" | "=" => Some(KeyCommand::Zoom(ZOOM_STEP)),
+        "-" | "_" => Some(KeyCommand::Zoom(1. / ZOOM_STEP)),
+        // older browsers call it "Spacebar"
+        " " | "Spacebar" => Some(KeyCommand::ToggleSnow),
+        "r" | "R" => Some(KeyCommand::ResetView),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::controls::{key_command, KeyCommand};
+
+    #[test]
+    fn arrows_and_wasd_orbit_the_same_way() {
+        for (arrow, letter) in [("ArrowLeft", "a"), ("ArrowRight", "d"), ("ArrowUp", "w"), ("ArrowDown", "s")] {
+            assert!(matches!(key_command(arrow), Some(KeyCommand::Orbit(..))), "{}", arrow);
+            assert_eq!(key_command(arrow), key_command(letter));
+            assert_eq!(key_command(letter), key_command(&letter.to_uppercase()));
+        }
+    }
+
+    #[test]
+    fn plus_zooms_in_and_minus_out() {
+        match (key_command("+"), key_command("-")) {
+            (Some(KeyCommand::Zoom(zoom_in)), Some(KeyCommand::Zoom(zoom_out))) => {
+                assert!(zoom_in < 1. && zoom_out > 1.);
+                assert!((zoom_in * zoom_out - 1.).abs() < 1e-6, "zooming in and out again comes back");
+            }
+            commands => panic!("{:?}", commands),
+        }
+    }
+
+    #[test]
+    fn other_keys_are_left_to_the_browser() {
+        assert_eq!(key_command(" "), Some(KeyCommand::ToggleSnow));
+        assert_eq!(key_command("r"), Some(KeyCommand::ResetView));
+        assert_eq!(key_command("Tab"), None);
+        assert_eq!(key_command("Enter"), None);
+    }
+}
@@ -21,11 +21,11 @@ impl<T> SphericalPoint3<T> {
     }
 }
 
-impl<T: Float> Into<Point3<T>> for SphericalPoint3<T> {
-    fn into(self) -> Point3<T> {
-        let x = self.r * self.theta.sin() * self.phi.sin();
-        let y = self.r * self.theta.cos();
-        let z = self.r * self.theta.sin() * self.phi.cos();
+impl<T: Float> From<SphericalPoint3<T>> for Point3<T> {
+    fn from(sp: SphericalPoint3<T>) -> Self {
+        let x = sp.r * sp.theta.sin() * sp.phi.sin();
+        let y = sp.r * sp.theta.cos();
+        let z = sp.r * sp.theta.sin() * sp.phi.cos();
         Point3::new(x, y, z)
     }
 }
@@ -58,11 +58,11 @@ impl<T> CylindricalPoint3<T> {
     }
 }
 
-impl<T: Float> Into<Point3<T>> for CylindricalPoint3<T> {
-    fn into(self) -> Point3<T> {
-        let x = self.r * self.phi.cos();
-        let y = self.h;
-        let z = self.r * self.phi.sin();
+impl<T: Float> From<CylindricalPoint3<T>> for Point3<T> {
+    fn from(cp: CylindricalPoint3<T>) -> Self {
+        let x = cp.r * cp.phi.cos();
+        let y = cp.h;
+        let z = cp.r * cp.phi.sin();
         Point3::new(x, y, z)
     }
 }
@@ -93,10 +93,10 @@ mod tests {
     case(SphericalPoint3::new(2., 0., 0.), Point3::new(0., 2., 0.)),
     case(SphericalPoint3::new(1., FRAC_PI_2, 0.), Point3::new(0., 0., 1.)),
     case(SphericalPoint3::new(3., FRAC_PI_2, FRAC_PI_2), Point3::new(3., 0., 0.)),
-    case(SphericalPoint3::new(3., FRAC_PI_4, FRAC_PI_2), Point3::new((4.5 as f32).sqrt(), (4.5 as f32).sqrt(), 0.)),
-    case(SphericalPoint3::new(3., FRAC_PI_2, FRAC_PI_4), Point3::new((4.5 as f32).sqrt(), 0., (4.5 as f32).sqrt())),
-    case(SphericalPoint3::new(3., FRAC_PI_4, 0.), Point3::new(0., (4.5 as f32).sqrt(), (4.5 as f32).sqrt())),
-    case(SphericalPoint3::new(5., FRAC_PI_4, FRAC_PI_4), Point3::new(2.5, (12.5 as f32).sqrt(), 2.5)),
+    case(SphericalPoint3::new(3., FRAC_PI_4, FRAC_PI_2), Point3::new(4.5_f32.sqrt(), 4.5_f32.sqrt(), 0.)),
+    case(SphericalPoint3::new(3., FRAC_PI_2, FRAC_PI_4), Point3::new(4.5_f32.sqrt(), 0., 4.5_f32.sqrt())),
+    case(SphericalPoint3::new(3., FRAC_PI_4, 0.), Point3::new(0., 4.5_f32.sqrt(), 4.5_f32.sqrt())),
+    case(SphericalPoint3::new(5., FRAC_PI_4, FRAC_PI_4), Point3::new(2.5, 12.5_f32.sqrt(), 2.5)),
     )]
     fn spherical_point3_into_point3(sp: SphericalPoint3<f32>, expected: Point3<f32>) {
         let result: Point3<f32> = sp.into();
@@ -115,10 +115,10 @@ mod tests {
     case(Point3::new(0., 2., 0.), SphericalPoint3::new(2., 0., 0.)),
     case(Point3::new(0., 0., 1.), SphericalPoint3::new(1., FRAC_PI_2, 0.)),
     case(Point3::new(3., 0., 0.), SphericalPoint3::new(3., FRAC_PI_2, FRAC_PI_2)),
-    case(Point3::new(3., 3., 0.), SphericalPoint3::new((18 as f32).sqrt(), FRAC_PI_4, FRAC_PI_2)),
-    case(Point3::new(3., 0., 3.), SphericalPoint3::new((18 as f32).sqrt(), FRAC_PI_2, FRAC_PI_4)),
-    case(Point3::new(0., 3., 3.), SphericalPoint3::new((18 as f32).sqrt(), FRAC_PI_4, 0.)),
-    case(Point3::new(4., 4., 4.), SphericalPoint3::new((48 as f32).sqrt(), (4. / (48 as f32).sqrt()).acos(), FRAC_PI_4)),
+    case(Point3::new(3., 3., 0.), SphericalPoint3::new(18_f32.sqrt(), FRAC_PI_4, FRAC_PI_2)),
+    case(Point3::new(3., 0., 3.), SphericalPoint3::new(18_f32.sqrt(), FRAC_PI_2, FRAC_PI_4)),
+    case(Point3::new(0., 3., 3.), SphericalPoint3::new(18_f32.sqrt(), FRAC_PI_4, 0.)),
+    case(Point3::new(4., 4., 4.), SphericalPoint3::new(48_f32.sqrt(), (4. / 48_f32.sqrt()).acos(), FRAC_PI_4)),
     )]
     fn spherical_point3_from_point3(p: Point3<f32>, expected: SphericalPoint3<f32>) {
         let result: SphericalPoint3<f32> = SphericalPoint3::from(p);
@@ -137,8 +137,8 @@ mod tests {
     case(CylindricalPoint3::new(2., 0., 0.), Point3::new(2., 0., 0.)),
     case(CylindricalPoint3::new(1., FRAC_PI_2, 0.), Point3::new(0., 0., 1.)),
     case(CylindricalPoint3::new(3., FRAC_PI_2, 1.), Point3::new(0., 1., 3.)),
-    case(CylindricalPoint3::new(3., FRAC_PI_4, 1.), Point3::new((4.5 as f32).sqrt(), 1., (4.5 as f32).sqrt())),
-    case(CylindricalPoint3::new(3., 3. * FRAC_PI_4, 0.), Point3::new(- (4.5 as f32).sqrt(), 0., (4.5 as f32).sqrt())),
+    case(CylindricalPoint3::new(3., FRAC_PI_4, 1.), Point3::new(4.5_f32.sqrt(), 1., 4.5_f32.sqrt())),
+    case(CylindricalPoint3::new(3., 3. * FRAC_PI_4, 0.), Point3::new(- 4.5_f32.sqrt(), 0., 4.5_f32.sqrt())),
     )]
 
     fn cylindrical_spoint3_into_point(cp: CylindricalPoint3<f32>, expected: Point3<f32>) {
@@ -161,8 +161,8 @@ mod tests {
     case(Point3::new(2., 0., 0.), CylindricalPoint3::new(2., 0., 0.)),
     case(Point3::new(0., 0., 1.), CylindricalPoint3::new(1., FRAC_PI_2, 0.)),
     case(Point3::new(0., 1., 3.), CylindricalPoint3::new(3., FRAC_PI_2, 1.)),
-    case(Point3::new((4.5 as f32).sqrt(), 1., (4.5 as f32).sqrt()), CylindricalPoint3::new(3., FRAC_PI_4, 1.)),
-    case(Point3::new(- (4.5 as f32).sqrt(), 0., (4.5 as f32).sqrt()), CylindricalPoint3::new(3., 3. * FRAC_PI_4, 0.)),
+    case(Point3::new(4.5_f32.sqrt(), 1., 4.5_f32.sqrt()), CylindricalPoint3::new(3., FRAC_PI_4, 1.)),
+    case(Point3::new(- 4.5_f32.sqrt(), 0., 4.5_f32.sqrt()), CylindricalPoint3::new(3., 3. * FRAC_PI_4, 0.)),
     )]
     fn cylindrical_point3_from_point3(p: Point3<f32>, expected: CylindricalPoint3<f32>) {
         let result: CylindricalPoint3<f32> = CylindricalPoint3::from(p);
@@ -0,0 +1,192 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, Transform, Vector3, Vector4};
+
+/// Axis aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn from_points<'a>(points: impl IntoIterator<Item=&'a Point3<f32>>) -> Self {
+        let mut points = points.into_iter();
+        let first = *points.next().expect("Cannot compute bounds of nothing");
+        points.fold(Aabb { min: first, max: first }, |aabb, p| Aabb {
+            min: Point3::new(aabb.min.x.min(p.x), aabb.min.y.min(p.y), aabb.min.z.min(p.z)),
+            max: Point3::new(aabb.max.x.max(p.x), aabb.max.y.max(p.y), aabb.max.z.max(p.z)),
+        })
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    /// Box containing this one after applying the transformation
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let corners: Vec<Point3<f32>> = (0..8)
+            .map(|i| Point3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            ))
+            .map(|corner| matrix.transform_point(corner))
+            .collect();
+        Aabb::from_points(&corners)
+    }
+
+    /// Smallest sphere around the box, centered in the middle of it
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere { center: self.center(), radius: (self.max - self.min).magnitude() / 2. }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Sphere containing this one after applying the transformation, non-uniform scaling makes it grow along the longest axis
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let scale = [matrix.x, matrix.y, matrix.z].iter()
+            .map(|column| column.truncate().magnitude())
+            .fold(0., f32::max);
+        BoundingSphere { center: matrix.transform_point(self.center), radius: self.radius * scale }
+    }
+}
+
+/// Points for which `normal · point + distance` is not negative are on the inner side of the plane
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// Plane with coefficients `a·x + b·y + c·z + d = 0` taken from the vector
+    fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let length = coefficients.truncate().magnitude();
+        Plane { normal: coefficients.truncate() / length, distance: coefficients.w / length }
+    }
+
+    pub fn signed_distance(&self, point: Point3<f32>) -> f32 {
+        self.normal.dot(point.to_vec()) + self.distance
+    }
+}
+
+/// Part of the space the camera sees, bounded by 6 planes facing inside
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts planes from the combined `projection * view` matrix, points inside are the ones that end up in the clip space
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Self {
+        let row = |i: usize| view_projection.row(i);
+        Frustum {
+            planes: [
+                Plane::from_coefficients(row(3) + row(0)), // left
+                Plane::from_coefficients(row(3) - row(0)), // right
+                Plane::from_coefficients(row(3) + row(1)), // bottom
+                Plane::from_coefficients(row(3) - row(1)), // top
+                Plane::from_coefficients(row(3) + row(2)), // near
+                Plane::from_coefficients(row(3) - row(2)), // far
+            ]
+        }
+    }
+
+    /// Conservative test, a sphere close to the corners may be reported as visible even if it's not
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(sphere.center) >= -sphere.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Matrix4, perspective, Point3, vec3};
+
+    use crate::culling::{Aabb, BoundingSphere, Frustum};
+
+    /// Camera at (0, 0, 10) looking towards the origin
+    fn frustum() -> Frustum {
+        let view = Matrix4::look_at(Point3::new(0., 0., 10.), Point3::new(0., 0., 0.), vec3(0., 1., 0.));
+        let projection = perspective(Deg(90.), 1., 0.1, 100.);
+        Frustum::from_matrix(&(projection * view))
+    }
+
+    fn sphere(x: f32, y: f32, z: f32, radius: f32) -> BoundingSphere {
+        BoundingSphere { center: Point3::new(x, y, z), radius }
+    }
+
+    #[test]
+    fn box_contains_all_points() {
+        let points = [Point3::new(1., -2., 0.), Point3::new(-1., 3., 0.5), Point3::new(0., 0., -4.)];
+
+        let aabb = Aabb::from_points(&points);
+
+        assert_eq!(aabb, Aabb { min: Point3::new(-1., -2., -4.), max: Point3::new(1., 3., 0.5) });
+        assert_eq!(aabb.center(), Point3::new(0., 0.5, -1.75));
+    }
+
+    #[test]
+    fn transformed_box_contains_rotated_corners() {
+        let aabb = Aabb { min: Point3::new(0., 0., 0.), max: Point3::new(2., 1., 1.) };
+        let matrix = Matrix4::from_translation(vec3(0., 5., 0.)) * Matrix4::from_angle_y(Deg(90.));
+
+        let transformed = aabb.transform(&matrix);
+
+        assert!((transformed.min - Point3::new(0., 5., -2.)).magnitude() < 1e-6);
+        assert!((transformed.max - Point3::new(1., 6., 0.)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn bounding_sphere_contains_box_corners() {
+        let aabb = Aabb { min: Point3::new(-1., -1., -1.), max: Point3::new(1., 1., 1.) };
+
+        let sphere = aabb.bounding_sphere();
+
+        assert_eq!(sphere.center, Point3::new(0., 0., 0.));
+        assert!((sphere.radius - 3_f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn transformed_sphere_follows_translation_and_largest_scale() {
+        let matrix = Matrix4::from_translation(vec3(1., 2., 3.)) * Matrix4::from_nonuniform_scale(1.8, 1., 0.5);
+
+        let transformed = sphere(0., 1., 0., 2.).transform(&matrix);
+
+        assert_eq!(transformed.center, Point3::new(1., 3., 3.));
+        assert!((transformed.radius - 3.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sees_what_is_in_front_of_the_camera() {
+        assert!(frustum().intersects_sphere(&sphere(0., 0., 0., 0.1)));
+        assert!(frustum().intersects_sphere(&sphere(5., -5., -5., 0.1)));
+    }
+
+    #[test]
+    fn does_not_see_what_is_behind_the_camera() {
+        assert!(!frustum().intersects_sphere(&sphere(0., 0., 11., 0.5)));
+    }
+
+    #[test]
+    fn does_not_see_what_is_too_far() {
+        assert!(!frustum().intersects_sphere(&sphere(0., 0., -95., 1.)));
+    }
+
+    #[test]
+    fn does_not_see_what_is_to_the_side() {
+        // 90 degrees field of view, at the origin it's 10 units wide on each side
+        assert!(!frustum().intersects_sphere(&sphere(12., 0., 0., 1.)));
+        assert!(!frustum().intersects_sphere(&sphere(0., -12., 0., 1.)));
+    }
+
+    #[test]
+    fn sees_spheres_crossing_the_edge() {
+        assert!(frustum().intersects_sphere(&sphere(10.5, 0., 0., 1.)));
+        assert!(frustum().intersects_sphere(&sphere(0., 0., 10.5, 1.)));
+    }
+}
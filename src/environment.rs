@@ -0,0 +1,150 @@
+//! Surroundings of the scene as a cubemap, reflective materials mirror it.
+//! It's generated from a sky gradient, there's nothing around the tree worth rendering into it.
+use cgmath::{InnerSpace, vec3, Vector3};
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::color::LinearRgb;
+use crate::gpu::Texture;
+use crate::shader::ENVIRONMENT_TEXTURE_UNIT;
+
+/// Width and height of every face, the gradient is smooth, so it doesn't need many pixels
+const FACE_SIZE: usize = 32;
+
+/// Colors of the sky gradient, from straight up, through the horizon, to the ground below
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sky {
+    pub zenith: LinearRgb,
+    pub horizon: LinearRgb,
+    pub ground: LinearRgb,
+}
+
+impl Sky {
+    /// Color seen in the direction, which doesn't have to be normalized
+    pub fn color(&self, direction: Vector3<f32>) -> LinearRgb {
+        let height = direction.normalize().y;
+        let (towards, amount) = if height >= 0. {
+            (self.zenith, height.sqrt())
+        } else {
+            // the ground gets bright quickly, snow reflects whatever light there is
+            (self.ground, (-height * 4.).min(1.))
+        };
+        LinearRgb(self.horizon.0 + (towards.0 - self.horizon.0) * amount)
+    }
+}
+
+/// Direction pointed to by the middle of the pixel of the face, faces are in the order of
+/// `TEXTURE_CUBE_MAP_POSITIVE_X + face` and their rows go top to bottom, as cubemaps expect
+fn direction(face: usize, x: usize, y: usize) -> Vector3<f32> {
+    let s = 2. * (x as f32 + 0.5) / FACE_SIZE as f32 - 1.;
+    let t = 2. * (y as f32 + 0.5) / FACE_SIZE as f32 - 1.;
+    match face {
+        0 => vec3(1., -t, -s),
+        1 => vec3(-1., -t, s),
+        2 => vec3(s, 1., t),
+        3 => vec3(s, -1., -t),
+        4 => vec3(s, -t, 1.),
+        _ => vec3(-s, -t, -1.),
+    }
+}
+
+/// RGBA pixels of the face
+fn face_pixels(sky: &Sky, face: usize) -> Vec<f32> {
+    (0..FACE_SIZE)
+        .flat_map(|y| (0..FACE_SIZE).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            let color = sky.color(direction(face, x, y)).0;
+            vec![color.x, color.y, color.z, 1.]
+        })
+        .collect()
+}
+
+pub struct Environment {
+    cubemap: Texture,
+}
+
+impl Environment {
+    pub fn new(gl: &GL, sky: &Sky) -> Self {
+        let cubemap = Texture::new(gl);
+        gl.bind_texture(GL::TEXTURE_CUBE_MAP, Some(&*cubemap));
+        gl.tex_parameteri(GL::TEXTURE_CUBE_MAP, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
+        gl.tex_parameteri(GL::TEXTURE_CUBE_MAP, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+        gl.tex_parameteri(GL::TEXTURE_CUBE_MAP, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
+        gl.tex_parameteri(GL::TEXTURE_CUBE_MAP, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
+        gl.bind_texture(GL::TEXTURE_CUBE_MAP, None);
+        let environment = Environment { cubemap };
+        environment.update(gl, sky);
+        environment
+    }
+
+    /// Regenerates all faces, e.g. when the sky changes
+    pub fn update(&self, gl: &GL, sky: &Sky) {
+        gl.bind_texture(GL::TEXTURE_CUBE_MAP, Some(&*self.cubemap));
+        // half floats keep colors of bright skies above 1.0, they can be uploaded from 32-bit floats
+        for face in 0..6 {
+            let pixels = face_pixels(sky, face);
+            unsafe {
+                let js_array = js_sys::Float32Array::view(&pixels);
+                gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+                    GL::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32, 0, GL::RGBA16F as i32, FACE_SIZE as i32, FACE_SIZE as i32, 0,
+                    GL::RGBA, GL::FLOAT, Some(&js_array))
+                    .expect("Cannot upload environment cubemap");
+            }
+        }
+        gl.bind_texture(GL::TEXTURE_CUBE_MAP, None);
+    }
+
+    pub fn bind(&self, gl: &GL) {
+        gl.active_texture(GL::TEXTURE0 + ENVIRONMENT_TEXTURE_UNIT);
+        gl.bind_texture(GL::TEXTURE_CUBE_MAP, Some(&*self.cubemap));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, vec3};
+
+    use crate::color::LinearRgb;
+    use crate::environment::{direction, face_pixels, FACE_SIZE, Sky};
+
+    const SKY: Sky = Sky {
+        zenith: LinearRgb::new(0., 0., 1.),
+        horizon: LinearRgb::new(1., 1., 1.),
+        ground: LinearRgb::new(0., 1., 0.),
+    };
+
+    #[test]
+    fn faces_look_along_the_axes() {
+        let middle = FACE_SIZE / 2;
+        let axes = [vec3(1., 0., 0.), vec3(-1., 0., 0.), vec3(0., 1., 0.), vec3(0., -1., 0.), vec3(0., 0., 1.), vec3(0., 0., -1.)];
+
+        for (face, axis) in axes.iter().enumerate() {
+            // the middle falls between pixels, the one next to it is close enough
+            assert!(direction(face, middle, middle).normalize().dot(*axis) > 0.99, "face {}", face);
+        }
+    }
+
+    #[test]
+    fn first_rows_of_side_faces_are_at_the_top() {
+        for face in [0, 1, 4, 5] {
+            assert!(direction(face, 0, 0).y > 0.);
+            assert!(direction(face, 0, FACE_SIZE - 1).y < 0.);
+        }
+    }
+
+    #[test]
+    fn sky_blends_from_the_horizon() {
+        assert_eq!(SKY.color(vec3(0., 2., 0.)), SKY.zenith);
+        assert_eq!(SKY.color(vec3(1., 0., 0.)), SKY.horizon);
+        assert_eq!(SKY.color(vec3(0., -1., 0.)), SKY.ground);
+        let above = SKY.color(vec3(1., 0.1, 0.)).0;
+        assert!(above.x < 1. && above.z == 1., "{:?}", above);
+    }
+
+    #[test]
+    fn faces_are_rgba() {
+        let pixels = face_pixels(&SKY, 2);
+
+        assert_eq!(pixels.len(), FACE_SIZE * FACE_SIZE * 4);
+        assert!(pixels.chunks(4).all(|pixel| pixel[3] == 1.));
+    }
+}
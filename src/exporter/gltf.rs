@@ -0,0 +1,320 @@
+//! Binary glTF 2.0, the whole scene with embedded images in a single `.glb` file.
+//! Phong materials are approximated with non-metallic metallic-roughness ones, ambient and specular colors are lost.
+use std::collections::HashMap;
+
+use cgmath::{Matrix4, SquareMatrix, Vector3};
+use serde_json::{json, Value};
+
+use crate::loader::{MaterialData, MeshData, ModelData, NodeData};
+
+const GLB_MAGIC: u32 = 0x4654_6c67; // "glTF"
+const GLB_VERSION: u32 = 2;
+const JSON_CHUNK: u32 = 0x4e4f_534a; // "JSON"
+const BIN_CHUNK: u32 = 0x004e_4942; // "BIN\0"
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Writes the model as a binary glTF file, nodes keep meshes instanced instead of copying them
+pub fn write_glb(data: &ModelData) -> Vec<u8> {
+    let mut writer = Writer::default();
+    let meshes: Vec<Value> = data.meshes.iter().map(|mesh| writer.mesh(mesh)).collect();
+    let images: HashMap<&str, usize> = image_names(data).into_iter()
+        .map(|name| (name, writer.image(name, data)))
+        .collect();
+    let materials: Vec<Value> = data.materials.iter().map(|material| material_json(material, &images)).collect();
+    let textures: Vec<Value> = (0..images.len()).map(|i| json!({ "source": i })).collect();
+    let roots: Vec<usize> = data.nodes.iter().map(|node| writer.node(node)).collect();
+
+    let mut root = json!({
+        "asset": { "version": "2.0", "generator": "wasm-christmas-tree" },
+        "scene": 0,
+        "scenes": [{ "nodes": roots }],
+        "nodes": writer.nodes,
+        "meshes": meshes,
+        "materials": materials,
+        "accessors": writer.accessors,
+        "bufferViews": writer.views,
+        "buffers": [{ "byteLength": writer.binary.len() }],
+    });
+    if !images.is_empty() {
+        root["images"] = Value::Array(writer.images);
+        root["textures"] = Value::Array(textures);
+    }
+    glb(&serde_json::to_vec(&root).expect("Cannot serialize glTF"), writer.binary)
+}
+
+/// Names of all textures used by materials, in the order of first use
+fn image_names(data: &ModelData) -> Vec<&str> {
+    let mut names: Vec<&str> = vec![];
+    for material in &data.materials {
+        for name in [&material.diffuse_texture, &material.normal_texture, &material.emissive_texture].iter().copied().flatten() {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+fn material_json(material: &MaterialData, images: &HashMap<&str, usize>) -> Value {
+    let texture = |name: &Option<String>| name.as_ref().map(|name| json!({ "index": images[name.as_str()] }));
+    // glTF colors are linear like ours
+    let color = |c: Vector3<f32>| vec![c.x, c.y, c.z];
+    // inverse of the conversion done when importing
+    let roughness = (2. / (material.shininess.max(0.) + 2.)).powf(0.25);
+    let mut pbr = json!({
+        "baseColorFactor": [material.diffuse.0.x, material.diffuse.0.y, material.diffuse.0.z, material.alpha],
+        "metallicFactor": 0.,
+        "roughnessFactor": roughness,
+    });
+    let mut json = json!({
+        "name": material.name,
+        // glTF limits emission to 1, brighter materials would need an extension
+        "emissiveFactor": color(material.emissive.0.map(|c| c.clamp(0., 1.))),
+    });
+    if material.alpha < 1. {
+        // the default mode ignores alpha
+        json["alphaMode"] = json!("BLEND");
+    }
+    if let Some(texture) = texture(&material.diffuse_texture) {
+        pbr["baseColorTexture"] = texture;
+    }
+    if let Some(texture) = texture(&material.normal_texture) {
+        json["normalTexture"] = texture;
+    }
+    if let Some(texture) = texture(&material.emissive_texture) {
+        json["emissiveTexture"] = texture;
+    }
+    json["pbrMetallicRoughness"] = pbr;
+    json
+}
+
+/// Binary buffer and the JSON describing what's inside
+#[derive(Default)]
+struct Writer {
+    binary: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+    images: Vec<Value>,
+    nodes: Vec<Value>,
+}
+
+impl Writer {
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        // accessors of 4 byte components have to be aligned to 4 bytes
+        while self.binary.len() % 4 != 0 {
+            self.binary.push(0);
+        }
+        let mut view = json!({ "buffer": 0, "byteOffset": self.binary.len(), "byteLength": bytes.len() });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.binary.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn floats(&mut self, values: &[[f32; 3]]) -> usize {
+        let bytes: Vec<u8> = values.iter().flatten().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+        let view = self.view(&bytes, Some(ARRAY_BUFFER));
+        self.accessor(json!({ "bufferView": view, "componentType": FLOAT, "count": values.len(), "type": "VEC3" }))
+    }
+
+    fn mesh(&mut self, mesh: &MeshData) -> Value {
+        let positions: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.position.into()).collect();
+        let normals: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.normal.into()).collect();
+        let position = self.floats(&positions);
+        // positions are the only attribute that has to have bounds
+        let min = (0..3).map(|i| positions.iter().map(|p| p[i]).fold(f32::INFINITY, f32::min)).collect::<Vec<_>>();
+        let max = (0..3).map(|i| positions.iter().map(|p| p[i]).fold(f32::NEG_INFINITY, f32::max)).collect::<Vec<_>>();
+        self.accessors[position]["min"] = json!(min);
+        self.accessors[position]["max"] = json!(max);
+        let normal = self.floats(&normals);
+
+        // texture coordinates go top to bottom in glTF
+        let uvs: Vec<u8> = mesh.vertices.iter().flat_map(|v| [v.uv.x, 1. - v.uv.y].iter().flat_map(|c| c.to_le_bytes().to_vec()).collect::<Vec<_>>()).collect();
+        let view = self.view(&uvs, Some(ARRAY_BUFFER));
+        let uv = self.accessor(json!({ "bufferView": view, "componentType": FLOAT, "count": mesh.vertices.len(), "type": "VEC2" }));
+
+        let indices: Vec<u8> = mesh.indices.iter().flat_map(|i| i.to_le_bytes().to_vec()).collect();
+        let view = self.view(&indices, Some(ELEMENT_ARRAY_BUFFER));
+        let indices = self.accessor(json!({ "bufferView": view, "componentType": UNSIGNED_INT, "count": mesh.indices.len(), "type": "SCALAR" }));
+
+        let mut primitive = json!({ "attributes": { "POSITION": position, "NORMAL": normal, "TEXCOORD_0": uv }, "indices": indices });
+        if let Some(material) = mesh.material {
+            primitive["material"] = json!(material);
+        }
+        json!({ "primitives": [primitive] })
+    }
+
+    /// Embeds the image if the model carries it, otherwise references it by its name
+    fn image(&mut self, name: &str, data: &ModelData) -> usize {
+        let image = match data.images.iter().find(|(image_name, _)| image_name == name) {
+            Some((_, bytes)) => {
+                let view = self.view(bytes, None);
+                let mime_type = if bytes.starts_with(b"\x89PNG") { "image/png" } else { "image/jpeg" };
+                json!({ "name": name, "bufferView": view, "mimeType": mime_type })
+            }
+            None => json!({ "name": name, "uri": name }),
+        };
+        self.images.push(image);
+        self.images.len() - 1
+    }
+
+    /// glTF nodes have at most one mesh, the other ones are put in children without their own transformation
+    fn node(&mut self, node: &NodeData) -> usize {
+        let mut children: Vec<usize> = node.children.iter().map(|child| self.node(child)).collect();
+        for &mesh in node.meshes.iter().skip(1) {
+            self.nodes.push(json!({ "mesh": mesh }));
+            children.push(self.nodes.len() - 1);
+        }
+        let mut json = json!({});
+        if node.transform != Matrix4::identity() {
+            let matrix: &[f32; 16] = node.transform.as_ref();
+            json["matrix"] = json!(matrix.to_vec());
+        }
+        if let Some(&mesh) = node.meshes.first() {
+            json["mesh"] = json!(mesh);
+        }
+        if !children.is_empty() {
+            json["children"] = json!(children);
+        }
+        self.nodes.push(json);
+        self.nodes.len() - 1
+    }
+}
+
+/// Header, JSON chunk padded with spaces and binary chunk padded with zeros
+fn glb(json: &[u8], mut binary: Vec<u8>) -> Vec<u8> {
+    let mut json = json.to_vec();
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+    while binary.len() % 4 != 0 {
+        binary.push(0);
+    }
+    let length = 12 + 8 + json.len() + 8 + binary.len();
+    let mut glb = Vec::with_capacity(length);
+    for word in &[GLB_MAGIC, GLB_VERSION, length as u32, json.len() as u32, JSON_CHUNK] {
+        glb.extend_from_slice(&word.to_le_bytes());
+    }
+    glb.extend_from_slice(&json);
+    for word in &[binary.len() as u32, BIN_CHUNK] {
+        glb.extend_from_slice(&word.to_le_bytes());
+    }
+    glb.extend_from_slice(&binary);
+    glb
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cgmath::{Matrix4, Point3, SquareMatrix, Transform, vec2, vec3};
+
+    use crate::color::LinearRgb;
+    use crate::exporter::gltf::write_glb;
+    use crate::loader::{gltf, MaterialData, MeshData, ModelData, NodeData};
+    use crate::texture;
+
+    static SNOW: &[u8] = include_bytes!("../../models/snow.png");
+
+    fn material(name: &str, texture: Option<&str>) -> MaterialData {
+        MaterialData {
+            ambient: LinearRgb::new(0.1, 0.1, 0.1),
+            diffuse: LinearRgb::new(0.8, 0.1, 0.1),
+            specular: LinearRgb::new(0.5, 0.5, 0.5),
+            shininess: 32.,
+            emissive: LinearRgb::new(2., 0.5, 0.),
+            diffuse_texture: texture.map(String::from),
+            ..MaterialData::named(name)
+        }
+    }
+
+    fn scene() -> ModelData {
+        let node = |transform, meshes| NodeData { transform, meshes, children: vec![] };
+        ModelData {
+            meshes: vec![MeshData::triangle(0), MeshData::triangle(1)],
+            materials: vec![material("red", Some("snow.png")), MaterialData { alpha: 0.5, ..material("plain", Some("bark.png")) }],
+            images: vec![("snow.png".to_string(), SNOW.to_vec())],
+            nodes: vec![
+                node(Matrix4::identity(), vec![0]),
+                node(Matrix4::from_translation(vec3(3., 0., 0.)), vec![0, 1]),
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trips_geometry_and_instances() {
+        let glb = write_glb(&scene());
+
+        let model = gltf::parse("scene", &glb, &HashMap::new()).unwrap();
+
+        assert_eq!(model.meshes.len(), 2);
+        let triangle = &model.meshes[0];
+        assert_eq!(triangle.indices, vec![0, 1, 2]);
+        assert_eq!(triangle.vertices[1].position, Point3::new(1., 0., 0.));
+        assert_eq!(triangle.vertices[1].normal, vec3(0., 0., 1.));
+        assert_eq!(triangle.vertices[1].uv, vec2(1., 0.25));
+        let transforms = model.mesh_transforms();
+        assert_eq!(transforms[0].len(), 2, "the first triangle is used by both nodes");
+        assert_eq!(transforms[1].len(), 1);
+        assert_eq!(transforms[1][0].transform_point(Point3::new(0., 0., 0.)), Point3::new(3., 0., 0.));
+    }
+
+    #[test]
+    fn round_trips_materials() {
+        let glb = write_glb(&scene());
+
+        let model = gltf::parse("scene", &glb, &HashMap::new()).unwrap();
+
+        let red = &model.materials[0];
+        assert_eq!(red.name, "red");
+        assert_eq!(red.diffuse, LinearRgb::new(0.8, 0.1, 0.1));
+        assert!((red.shininess - 32.).abs() < 1e-3, "{}", red.shininess);
+        assert_eq!(red.emissive, LinearRgb::new(1., 0.5, 0.), "emission is clamped");
+        assert_eq!(red.alpha, 1.);
+        assert_eq!(model.materials[1].alpha, 0.5);
+        assert_eq!(model.meshes[1].material, Some(1));
+    }
+
+    #[test]
+    fn embeds_known_images_and_references_others() {
+        let glb = write_glb(&scene());
+
+        let model = gltf::parse("scene", &glb, &HashMap::new()).unwrap();
+
+        assert_eq!(model.images, vec![("scene#image0".to_string(), SNOW.to_vec())]);
+        assert_eq!(model.materials[0].diffuse_texture.as_deref(), Some("scene#image0"));
+        assert_eq!(model.materials[1].diffuse_texture.as_deref(), Some("bark.png"));
+    }
+
+    #[test]
+    fn fetched_textures_are_fetched_from_the_same_place_after_import() {
+        let glb = write_glb(&scene());
+
+        let model = gltf::parse("scene", &glb, &HashMap::new()).unwrap();
+
+        let name = model.materials[1].diffuse_texture.as_deref().unwrap();
+        assert_eq!(texture::url(name), "models/bark.png", "the directory of models is added only once");
+    }
+
+    #[test]
+    fn writes_aligned_chunks() {
+        let glb = write_glb(&scene());
+
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32::from_le_bytes([glb[8], glb[9], glb[10], glb[11]]) as usize, glb.len());
+        let json_length = u32::from_le_bytes([glb[12], glb[13], glb[14], glb[15]]) as usize;
+        assert_eq!(json_length % 4, 0);
+        assert_eq!(&glb[20 + json_length + 4..20 + json_length + 8], b"BIN\0");
+    }
+}
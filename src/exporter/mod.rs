@@ -0,0 +1,103 @@
+//! Exporting the scene to files other tools can open. Like importing, it goes through `ModelData`,
+//! so whatever gets written can be read back by the loaders.
+use std::collections::HashMap;
+
+use crate::loader::{MaterialData, MeshData, ModelData, NodeData};
+use crate::material::{Material, MaterialId};
+use crate::mesh::Vertex;
+use crate::model::Instance;
+use crate::texture::{TextureId, Textures};
+
+pub mod gltf;
+pub mod obj;
+
+/// Collects meshes with all their instances, every instance becomes a node placing the mesh in the scene.
+/// Instances of a mesh using different materials get separate copies of the geometry, one for every material.
+/// `material` describes materials by id, each one is asked for once.
+pub fn model_data<'a>(meshes: impl IntoIterator<Item=(&'a [Vertex], &'a [u32], &'a [Instance])>, mut material: impl FnMut(MaterialId) -> MaterialData) -> ModelData {
+    let mut data = ModelData { meshes: vec![], materials: vec![], images: vec![], nodes: vec![] };
+    let mut material_indices: HashMap<MaterialId, usize> = HashMap::new();
+    for (vertices, indices, instances) in meshes {
+        // mesh index for every material used by instances of this mesh
+        let mut mesh_indices: HashMap<MaterialId, usize> = HashMap::new();
+        for instance in instances {
+            let mesh_index = *mesh_indices.entry(instance.material_id).or_insert_with(|| {
+                let material_index = *material_indices.entry(instance.material_id).or_insert_with(|| {
+                    data.materials.push(material(instance.material_id));
+                    data.materials.len() - 1
+                });
+                data.meshes.push(MeshData { vertices: vertices.to_vec(), indices: indices.to_vec(), material: Some(material_index) });
+                data.meshes.len() - 1
+            });
+            data.nodes.push(NodeData { transform: instance.model, meshes: vec![mesh_index], children: vec![] });
+        }
+    }
+    data
+}
+
+/// Material as written to files, textures are referenced by the names they were loaded by
+pub fn material_data(name: &str, material: &Material, textures: &Textures) -> MaterialData {
+    let texture = |texture_id: Option<TextureId>| texture_id.and_then(|id| textures.name(id)).map(String::from);
+    MaterialData {
+        name: name.to_string(),
+        ambient: material.ambient,
+        diffuse: material.diffuse,
+        specular: material.specular,
+        shininess: material.shininess,
+        emissive: material.emissive,
+        reflectivity: material.reflectivity,
+        alpha: material.alpha,
+        diffuse_texture: texture(material.textures.diffuse),
+        normal_texture: texture(material.textures.normal),
+        emissive_texture: texture(material.textures.emissive),
+    }
+}
+
+/// Adds image files of all textures used by the materials that aren't available anywhere else, like the ones embedded in models
+pub fn add_images(data: &mut ModelData, textures: &Textures) {
+    let mut names: Vec<&String> = data.materials.iter()
+        .flat_map(|m| vec![&m.diffuse_texture, &m.normal_texture, &m.emissive_texture])
+        .filter_map(Option::as_ref)
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    data.images = names.into_iter()
+        .filter_map(|name| textures.encoded(name).map(|bytes| (name.clone(), bytes.to_vec())))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix4, Point3, vec2, vec3};
+
+    use crate::color::LinearRgb;
+    use crate::exporter::model_data;
+    use crate::loader::MaterialData;
+    use crate::mesh::Vertex;
+    use crate::model::Instance;
+
+    fn material(material_id: u32) -> MaterialData {
+        let color = LinearRgb::new(material_id as f32, 0., 0.);
+        MaterialData { ambient: color, diffuse: color, specular: color, emissive: color, ..MaterialData::named(&format!("material{}", material_id)) }
+    }
+
+    #[test]
+    fn splits_instances_by_material() {
+        let vertices = [Vertex { position: Point3::new(0., 0., 0.), normal: vec3(0., 1., 0.), uv: vec2(0., 0.) }; 3];
+        let indices = [0, 1, 2];
+        let at = |x: f32, material_id| Instance::new(Matrix4::from_translation(vec3(x, 0., 0.)), material_id);
+        let baubles = [at(0., 7), at(1., 3), at(2., 7)];
+        let ground = [at(0., 3)];
+        let mut asked = vec![];
+
+        let data = model_data(
+            vec![(&vertices[..], &indices[..], &baubles[..]), (&vertices[..], &indices[..], &ground[..])],
+            |material_id| { asked.push(material_id); material(material_id) });
+
+        assert_eq!(asked, vec![7, 3], "every material is described once");
+        assert_eq!(data.materials.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(), vec!["material7", "material3"]);
+        assert_eq!(data.meshes.iter().map(|m| m.material).collect::<Vec<_>>(), vec![Some(0), Some(1), Some(1)]);
+        assert_eq!(data.nodes.iter().map(|n| n.meshes.clone()).collect::<Vec<_>>(), vec![vec![0], vec![1], vec![0], vec![2]]);
+        assert_eq!(data.nodes[2].transform, Matrix4::from_translation(vec3(2., 0., 0.)));
+    }
+}
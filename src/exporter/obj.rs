@@ -0,0 +1,166 @@
+use std::fmt::Write;
+
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Transform};
+
+use crate::color::LinearRgb;
+use crate::loader::{MaterialData, ModelData};
+
+/// Writes the model as OBJ and MTL sources, `mtl_file` is the name the OBJ uses to reference the material library.
+/// OBJ has no hierarchy, every use of a mesh is baked into a separate object.
+pub fn write(data: &ModelData, mtl_file: &str) -> (String, String) {
+    let mut obj = String::new();
+    writeln!(obj, "# {} objects, {} materials", data.mesh_transforms().iter().map(Vec::len).sum::<usize>(), data.materials.len()).unwrap();
+    writeln!(obj, "mtllib {}", mtl_file).unwrap();
+
+    // indices in OBJ are global and start with 1
+    let mut first_vertex = 1;
+    let mut object = 0;
+    for (mesh, transforms) in data.meshes.iter().zip(data.mesh_transforms()) {
+        for transform in transforms {
+            let normal_matrix = normal_matrix(&transform);
+            writeln!(obj, "o object{}", object).unwrap();
+            for v in &mesh.vertices {
+                let position = transform.transform_point(v.position);
+                writeln!(obj, "v {} {} {}", position.x, position.y, position.z).unwrap();
+            }
+            for v in &mesh.vertices {
+                writeln!(obj, "vt {} {}", v.uv.x, v.uv.y).unwrap();
+            }
+            for v in &mesh.vertices {
+                let normal = (normal_matrix * v.normal).normalize();
+                writeln!(obj, "vn {} {} {}", normal.x, normal.y, normal.z).unwrap();
+            }
+            if let Some(material) = mesh.material {
+                writeln!(obj, "usemtl {}", data.materials[material].name).unwrap();
+            }
+            for triangle in mesh.indices.chunks(3) {
+                let [a, b, c] = [triangle[0] + first_vertex, triangle[1] + first_vertex, triangle[2] + first_vertex];
+                writeln!(obj, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c).unwrap();
+            }
+            first_vertex += mesh.vertices.len() as u32;
+            object += 1;
+        }
+    }
+
+    let mtl = data.materials.iter().map(material_source).collect::<Vec<_>>().join("\n");
+    (obj, mtl)
+}
+
+/// Transforms normals the same way the matrix transforms surfaces, even when the scaling isn't uniform
+fn normal_matrix(transform: &Matrix4<f32>) -> Matrix3<f32> {
+    let linear = Matrix3::from_cols(transform.x.truncate(), transform.y.truncate(), transform.z.truncate());
+    linear.invert().map_or(linear, |inverse| inverse.transpose())
+}
+
+fn material_source(material: &MaterialData) -> String {
+    let color = |c: LinearRgb| {
+        let c = c.to_srgb().0;
+        format!("{} {} {}", c.x, c.y, c.z)
+    };
+    let mut mtl = String::new();
+    writeln!(mtl, "newmtl {}", material.name).unwrap();
+    writeln!(mtl, "Ka {}", color(material.ambient)).unwrap();
+    writeln!(mtl, "Kd {}", color(material.diffuse)).unwrap();
+    writeln!(mtl, "Ks {}", color(material.specular)).unwrap();
+    writeln!(mtl, "Ns {}", material.shininess).unwrap();
+    writeln!(mtl, "Ke {}", color(material.emissive)).unwrap();
+    writeln!(mtl, "Pm {}", material.reflectivity).unwrap();
+    writeln!(mtl, "d {}", material.alpha).unwrap();
+    let maps = [("map_Kd", &material.diffuse_texture), ("map_Bump", &material.normal_texture), ("map_Ke", &material.emissive_texture)];
+    for (keyword, texture) in maps.iter() {
+        if let Some(texture) = texture {
+            writeln!(mtl, "{} {}", keyword, texture).unwrap();
+        }
+    }
+    mtl
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Matrix4, Point3, SquareMatrix, vec2, vec3};
+
+    use crate::color::LinearRgb;
+    use crate::exporter::obj::write;
+    use crate::loader::{MaterialData, MeshData, ModelData, NodeData, NormalMode, obj};
+
+    fn material() -> MaterialData {
+        MaterialData {
+            ambient: LinearRgb::new(0.1, 0.1, 0.),
+            diffuse: LinearRgb::new(0.8, 0.6, 0.),
+            specular: LinearRgb::new(1., 1., 0.5),
+            shininess: 32.,
+            emissive: LinearRgb::new(0.5, 0.25, 0.),
+            reflectivity: 0.75,
+            alpha: 0.5,
+            diffuse_texture: Some("gold.png".to_string()),
+            normal_texture: Some("gold_normal.png".to_string()),
+            ..MaterialData::named("gold")
+        }
+    }
+
+    fn model(transforms: Vec<Matrix4<f32>>) -> ModelData {
+        ModelData {
+            meshes: vec![MeshData::triangle(0)],
+            materials: vec![material()],
+            images: vec![],
+            nodes: transforms.into_iter().map(|transform| NodeData { transform, meshes: vec![0], children: vec![] }).collect(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_loader() {
+        let (obj, mtl) = write(&model(vec![Matrix4::identity()]), "scene.mtl");
+
+        let model = obj::parse(obj.as_bytes(), Some(mtl.as_bytes()), NormalMode::Smooth).unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        let triangle = &model.meshes[0];
+        assert_eq!(triangle.indices.len(), 3);
+        assert_eq!(triangle.vertices[1].position, Point3::new(1., 0., 0.));
+        assert_eq!(triangle.vertices[1].uv, vec2(1., 0.25));
+        assert_eq!(triangle.vertices[1].normal, vec3(0., 0., 1.));
+        assert_eq!(triangle.material, Some(0));
+        let (read, written) = (model.materials[0].clone(), material());
+        // colors go through sRGB and back, they can be off in the last digit
+        for (read, written) in [(read.ambient, written.ambient), (read.diffuse, written.diffuse), (read.specular, written.specular), (read.emissive, written.emissive)] {
+            assert!((read.0 - written.0).magnitude() < 1e-6, "{:?} != {:?}", read, written);
+        }
+        assert_eq!(MaterialData { ambient: written.ambient, diffuse: written.diffuse, specular: written.specular, emissive: written.emissive, ..read }, written);
+    }
+
+    #[test]
+    fn writes_colors_in_srgb() {
+        let (_, mtl) = write(&model(vec![Matrix4::identity()]), "scene.mtl");
+
+        let diffuse = material().diffuse.to_srgb().0;
+        assert!(mtl.contains(&format!("Kd {} {} {}\n", diffuse.x, diffuse.y, diffuse.z)), "{}", mtl);
+        assert!(diffuse.x > material().diffuse.0.x);
+    }
+
+    #[test]
+    fn bakes_every_instance_into_geometry() {
+        let moved = Matrix4::from_translation(vec3(5., 0., 0.)) * Matrix4::from_angle_y(Deg(90.));
+        let (obj, mtl) = write(&model(vec![Matrix4::identity(), moved]), "scene.mtl");
+
+        let model = obj::parse(obj.as_bytes(), Some(mtl.as_bytes()), NormalMode::Smooth).unwrap();
+
+        assert_eq!(model.meshes.len(), 2);
+        let moved = &model.meshes[1];
+        assert!((moved.vertices[1].position - Point3::new(5., 0., -1.)).magnitude() < 1e-6);
+        assert!((moved.vertices[1].normal - vec3(1., 0., 0.)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scaling() {
+        let mut data = model(vec![Matrix4::from_nonuniform_scale(1., 4., 1.)]);
+        // slope rising along X, its normal leans towards -X
+        data.meshes[0].vertices[1].normal = vec3(-1., 1., 0.).normalize();
+
+        let (obj, mtl) = write(&data, "scene.mtl");
+        let model = obj::parse(obj.as_bytes(), Some(mtl.as_bytes()), NormalMode::Smooth).unwrap();
+
+        // the slope got 4 times steeper, so the normal leans 4 times more
+        let normal = model.meshes[0].vertices[1].normal;
+        assert!((normal - vec3(-4., 1., 0.).normalize()).magnitude() < 1e-5, "{:?}", normal);
+    }
+}
@@ -0,0 +1,103 @@
+//! Exponential-squared fog, thicker while it snows. Surfaces fade into the color of the horizon with their distance
+//! from the camera, which also hides the edges of the ground and of the volume snow falls in.
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::color::LinearRgb;
+use crate::gpu::Buffer;
+use crate::shader::FOG_UBO_BINDING_POINT;
+#[cfg(test)]
+use crate::shader::validation::UniformBlock;
+
+// std140 offsets in the Fog block, the density fills the padding after the color so both are written at once
+const COLOR_OFFSET: i32 = 0;
+#[cfg(test)]
+const DENSITY_OFFSET: i32 = 12;
+const UBO_SIZE: i32 = 16;
+
+/// How thick the air of the scene is
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Atmosphere {
+    /// Fog density in clear air, 0 for no fog
+    pub fog_density: f32,
+    /// Density snow adds to the fog at full intensity
+    pub snow_haze: f32,
+    /// Fraction of snowflakes falling, 0 to 1
+    pub snow_intensity: f32,
+}
+
+impl Atmosphere {
+    pub fn fog_density(&self) -> f32 {
+        self.fog_density + self.snow_haze * self.snow_intensity
+    }
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        // under 5% of fog on the tree from where the camera starts, the far edges of the ground and of the snow fade more
+        Atmosphere { fog_density: 0.006, snow_haze: 0.006, snow_intensity: 1. }
+    }
+}
+
+pub struct Fog {
+    ubo: Buffer,
+    /// What the block holds, so that unchanged values aren't written again
+    written: Option<(LinearRgb, f32)>,
+}
+
+impl Fog {
+    pub fn setup(gl: &GL) -> Self {
+        let ubo = Buffer::new(gl);
+        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&*ubo));
+        gl.buffer_data_with_i32(GL::UNIFORM_BUFFER, UBO_SIZE, GL::DYNAMIC_DRAW);
+        gl.bind_buffer_base(GL::UNIFORM_BUFFER, FOG_UBO_BINDING_POINT, Some(&*ubo));
+        gl.bind_buffer(GL::UNIFORM_BUFFER, None);
+        Fog { ubo, written: None }
+    }
+
+    /// `color` should be the color of the horizon, so that far away surfaces blend into the sky
+    pub fn update(&mut self, gl: &GL, color: LinearRgb, density: f32) {
+        if self.written == Some((color, density)) {
+            return;
+        }
+        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&*self.ubo));
+        unsafe {
+            let array: [f32; 4] = [color.0.x, color.0.y, color.0.z, density];
+            let js_array = js_sys::Float32Array::view(&array);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, COLOR_OFFSET, &js_array);
+        }
+        gl.bind_buffer(GL::UNIFORM_BUFFER, None);
+        self.written = Some((color, density));
+    }
+}
+
+/// Layout the Fog block is written with, shaders are checked against it in tests
+#[cfg(test)]
+pub fn ubo_layout() -> UniformBlock {
+    UniformBlock::new("Fog", FOG_UBO_BINDING_POINT, UBO_SIZE)
+        .member("fogColor", COLOR_OFFSET)
+        .member("fogDensity", DENSITY_OFFSET)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fog::Atmosphere;
+
+    #[test]
+    fn snow_thickens_the_fog() {
+        let clear = Atmosphere { snow_intensity: 0., ..Atmosphere::default() };
+        let snowing = Atmosphere::default();
+
+        assert_eq!(clear.fog_density(), clear.fog_density);
+        assert!(snowing.fog_density() > clear.fog_density());
+        assert_eq!(Atmosphere { fog_density: 0., snow_haze: 0., ..snowing }.fog_density(), 0.);
+    }
+
+    #[test]
+    fn tree_stays_clear_by_default() {
+        // the same as in fog.glsl, with the tree as far as the camera starts from it
+        let fog = |distance: f32| 1. - (-(Atmosphere::default().fog_density() * distance).powi(2)).exp();
+
+        assert!(fog(18.) < 0.05, "{}", fog(18.));
+        assert!(fog(32.) > 2. * fog(18.), "far edges of the ground fade more");
+    }
+}
@@ -0,0 +1,171 @@
+//! Owned WebGL objects, each one deletes itself when dropped. Live objects are counted per kind, so leaks show up in the numbers.
+use std::cell::RefCell;
+use std::ops::Deref;
+
+use web_sys::{WebGl2RenderingContext as GL, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderbuffer, WebGlShader, WebGlTexture, WebGlVertexArrayObject};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ResourceKind {
+    Buffer,
+    VertexArray,
+    Program,
+    Shader,
+    Texture,
+    Framebuffer,
+    Renderbuffer,
+}
+
+const RESOURCE_KINDS: [ResourceKind; 7] = [
+    ResourceKind::Buffer, ResourceKind::VertexArray, ResourceKind::Program, ResourceKind::Shader, ResourceKind::Texture,
+    ResourceKind::Framebuffer, ResourceKind::Renderbuffer,
+];
+
+thread_local! {
+    static LIVE_RESOURCES: RefCell<[usize; RESOURCE_KINDS.len()]> = const { RefCell::new([0; RESOURCE_KINDS.len()]) };
+}
+
+/// Number of currently alive objects of the given kind
+pub fn live_count(kind: ResourceKind) -> usize {
+    LIVE_RESOURCES.with(|live| live.borrow()[kind as usize])
+}
+
+/// Numbers of all alive objects, by kind
+pub fn live_counts() -> Vec<(ResourceKind, usize)> {
+    RESOURCE_KINDS.iter().map(|&kind| (kind, live_count(kind))).collect()
+}
+
+/// Counts as one live object of its kind for as long as it exists
+struct Tracked(ResourceKind);
+
+impl Tracked {
+    fn new(kind: ResourceKind) -> Self {
+        LIVE_RESOURCES.with(|live| live.borrow_mut()[kind as usize] += 1);
+        Tracked(kind)
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        LIVE_RESOURCES.with(|live| live.borrow_mut()[self.0 as usize] -= 1);
+    }
+}
+
+macro_rules! owned_handle {
+    ($(#[$doc:meta])* $name:ident, $raw:ty, $kind:expr, $delete:ident) => {
+        $(#[$doc])*
+        pub struct $name {
+            gl: GL,
+            raw: $raw,
+            _tracked: Tracked,
+        }
+
+        impl $name {
+            fn wrap(gl: &GL, raw: $raw) -> Self {
+                $name { gl: gl.clone(), raw, _tracked: Tracked::new($kind) }
+            }
+        }
+
+        impl Deref for $name {
+            type Target = $raw;
+
+            fn deref(&self) -> &$raw {
+                &self.raw
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                self.gl.$delete(Some(&self.raw));
+            }
+        }
+    };
+}
+
+owned_handle!(Buffer, WebGlBuffer, ResourceKind::Buffer, delete_buffer);
+owned_handle!(VertexArray, WebGlVertexArrayObject, ResourceKind::VertexArray, delete_vertex_array);
+owned_handle!(Program, WebGlProgram, ResourceKind::Program, delete_program);
+owned_handle!(
+    /// Compiled shader stage, can be dropped as soon as the program using it is linked
+    ShaderObject, WebGlShader, ResourceKind::Shader, delete_shader);
+owned_handle!(Texture, WebGlTexture, ResourceKind::Texture, delete_texture);
+owned_handle!(Framebuffer, WebGlFramebuffer, ResourceKind::Framebuffer, delete_framebuffer);
+owned_handle!(Renderbuffer, WebGlRenderbuffer, ResourceKind::Renderbuffer, delete_renderbuffer);
+
+impl Buffer {
+    pub fn new(gl: &GL) -> Self {
+        Self::wrap(gl, gl.create_buffer().expect("Cannot create buffer"))
+    }
+}
+
+impl VertexArray {
+    pub fn new(gl: &GL) -> Self {
+        Self::wrap(gl, gl.create_vertex_array().expect("Cannot create vertex array"))
+    }
+}
+
+impl Program {
+    pub fn new(gl: &GL) -> Self {
+        Self::wrap(gl, gl.create_program().expect("Cannot create program"))
+    }
+}
+
+impl ShaderObject {
+    pub fn new(gl: &GL, shader_type: u32) -> Self {
+        Self::wrap(gl, gl.create_shader(shader_type).expect("Cannot create shader"))
+    }
+}
+
+impl Texture {
+    pub fn new(gl: &GL) -> Self {
+        Self::wrap(gl, gl.create_texture().expect("Cannot create texture"))
+    }
+}
+
+impl Framebuffer {
+    pub fn new(gl: &GL) -> Self {
+        Self::wrap(gl, gl.create_framebuffer().expect("Cannot create framebuffer"))
+    }
+}
+
+impl Renderbuffer {
+    pub fn new(gl: &GL) -> Self {
+        Self::wrap(gl, gl.create_renderbuffer().expect("Cannot create renderbuffer"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gpu::{live_count, live_counts, ResourceKind, Tracked};
+
+    #[test]
+    fn counts_objects_while_they_live() {
+        let buffer = Tracked::new(ResourceKind::Buffer);
+        let textures = vec![Tracked::new(ResourceKind::Texture), Tracked::new(ResourceKind::Texture)];
+
+        assert_eq!(live_count(ResourceKind::Buffer), 1);
+        assert_eq!(live_count(ResourceKind::Texture), 2);
+        assert_eq!(live_count(ResourceKind::Program), 0);
+
+        drop(textures);
+        assert_eq!(live_count(ResourceKind::Texture), 0);
+        assert_eq!(live_count(ResourceKind::Buffer), 1);
+        drop(buffer);
+    }
+
+    #[test]
+    fn nothing_leaks_once_owners_are_dropped() {
+        struct FakeMesh {
+            _vao: Tracked,
+            _buffers: Vec<Tracked>,
+        }
+        let meshes: Vec<FakeMesh> = (0..3)
+            .map(|_| FakeMesh { _vao: Tracked::new(ResourceKind::VertexArray), _buffers: vec![Tracked::new(ResourceKind::Buffer), Tracked::new(ResourceKind::Buffer)] })
+            .collect();
+        assert_eq!(live_count(ResourceKind::VertexArray), 3);
+        assert_eq!(live_count(ResourceKind::Buffer), 6);
+
+        drop(meshes);
+
+        assert!(live_counts().iter().all(|&(_, count)| count == 0), "leaked: {:?}", live_counts());
+    }
+}
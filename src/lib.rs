@@ -1,3 +1,6 @@
+// offsets are spelled out as `0 * size`, `1 * size`, ... on purpose, it keeps std140 layouts readable
+#![allow(clippy::identity_op, clippy::erasing_op)]
+
 extern crate console_error_panic_hook;
 
 use core::f32::consts::PI;
@@ -7,17 +10,33 @@ use wasm_bindgen::__rt::core::cell::RefCell;
 use wasm_bindgen::__rt::std::rc::Rc;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::*;
-use web_sys::{Event, HtmlCanvasElement, MouseEvent, TouchEvent, WebGl2RenderingContext as GL};
+use web_sys::{Event, HtmlCanvasElement, KeyboardEvent, MediaQueryList, MouseEvent, TouchEvent, WebGl2RenderingContext as GL};
 
-use crate::xmas_tree::scene::Scene;
+use crate::controls::KeyCommand;
+use crate::fog::Atmosphere;
+use crate::shader::{NORMAL_MAPPING, REFLECTIONS, STANDARD_PROGRAM, UNLIT_PROGRAM};
+use crate::shader::preprocessor::Defines;
+use crate::xmas_tree::scene::{Scene, SceneConfig};
 
+pub mod api;
 mod camera;
+mod color;
+mod controls;
 mod coords;
+mod culling;
+mod environment;
+mod exporter;
+mod fog;
+mod gpu;
 mod lights;
+mod loader;
 mod material;
+mod material_library;
 mod mesh;
 mod model;
+mod render;
 mod shader;
+mod texture;
 mod xmas_tree;
 
 fn window() -> web_sys::Window {
@@ -37,6 +56,53 @@ fn request_animation_frame(f: &Closure<dyn FnMut()>) {
         .expect("should register `requestAnimationFrame` OK");
 }
 
+/// Shows the message over the canvas, for problems the page should tell about instead of silently breaking
+fn show_error(message: &str) {
+    let document = window().document().unwrap();
+    let overlay = document.create_element("pre").unwrap();
+    overlay.set_id("error");
+    overlay.set_text_content(Some(message));
+    document.body().unwrap().append_child(&overlay).unwrap();
+}
+
+/// Optional parts of the scene are turned on with `data-` attributes of the canvas, e.g. `<canvas data-aurora>`
+fn scene_config(canvas: &HtmlCanvasElement) -> SceneConfig {
+    let mut atmosphere = Atmosphere::default();
+    let number = |name: &str| canvas.get_attribute(name)
+        .and_then(|value| value.trim().parse::<f32>().ok())
+        .filter(|value| value.is_finite() && *value >= 0.);
+    if let Some(density) = number("data-fog-density") {
+        atmosphere.fog_density = density;
+    }
+    if let Some(haze) = number("data-snow-haze") {
+        atmosphere.snow_haze = haze;
+    }
+    if let Some(intensity) = number("data-snow-intensity") {
+        atmosphere.snow_intensity = intensity.min(1.);
+    }
+    let reduced_motion = reduced_motion_query().is_some_and(|query| query.matches());
+    SceneConfig { aurora: canvas.has_attribute("data-aurora"), atmosphere, reduced_motion }
+}
+
+/// Matches when the user asked the system to minimize animations
+fn reduced_motion_query() -> Option<MediaQueryList> {
+    window().match_media("(prefers-reduced-motion: reduce)").ok().flatten()
+}
+
+/// Sets up the scene with the standard program, or with the unlit one if the device cannot compile it
+fn setup_scene(gl: &GL, config: SceneConfig) -> Result<Scene, JsValue> {
+    let error = match Scene::setup(gl, STANDARD_PROGRAM, Defines::new().flag(NORMAL_MAPPING).flag(REFLECTIONS), config) {
+        Ok(scene) => return Ok(scene),
+        Err(error) => error,
+    };
+    web_sys::console::error_1(&error.to_string().into());
+    show_error(&format!("{}\n\nFalling back to simpler shading without lights and textures.", error));
+    Scene::setup(gl, UNLIT_PROGRAM, Defines::new(), config).map_err(|fallback_error| {
+        show_error(&fallback_error.to_string());
+        JsValue::from(fallback_error.to_string())
+    })
+}
+
 struct Rotation {
     x_offset: i32,
     y_offset: i32,
@@ -54,11 +120,12 @@ pub fn start() -> Result<(), JsValue> {
     gl.enable(GL::DEPTH_TEST);
     gl.enable(GL::CULL_FACE);
 
-    let mut scene = Scene::setup(&gl);
+    let scene = Rc::new(RefCell::new(setup_scene(&gl, scene_config(&canvas))?));
+    api::register(gl.clone(), scene.clone());
 
     {   // handling mouse "dragging" - rotating the scene
         let gl = gl.clone();
-        let mut camera = scene.camera.clone();
+        let mut camera = scene.borrow().camera.clone();
         let canvas2 = canvas.clone();
         let on_mouse_move = Closure::wrap(Box::new(move |event: MouseEvent| {
             if event.buttons() == 1 {
@@ -73,7 +140,7 @@ pub fn start() -> Result<(), JsValue> {
 
     {   // handling touch "dragging" - rotating the scene
         let gl = gl.clone();
-        let mut camera = scene.camera.clone();
+        let mut camera = scene.borrow().camera.clone();
         let canvas2 = canvas.clone();
         let mut rotation = Rotation { x_offset: 0, y_offset: 0 };
         let on_touch_move = Closure::wrap(Box::new(move |event: TouchEvent| {
@@ -96,14 +163,61 @@ pub fn start() -> Result<(), JsValue> {
         on_touch_move.forget();
     }
 
+    {   // handling keyboard - rotating and zooming the scene, pausing the snow
+        // the canvas has to be focusable to get key presses, pages can put it elsewhere in the tab order
+        if !canvas.has_attribute("tabindex") {
+            canvas.set_tab_index(0);
+        }
+        let gl = gl.clone();
+        let scene = scene.clone();
+        let on_key_down = Closure::wrap(Box::new(move |event: KeyboardEvent| {
+            // browser shortcuts, like zooming the page, keep working
+            if event.ctrl_key() || event.meta_key() || event.alt_key() {
+                return;
+            }
+            let command = match controls::key_command(&event.key()) {
+                Some(command) => command,
+                None => return,
+            };
+            // arrows and space would scroll the page otherwise
+            event.prevent_default();
+            let mut scene = scene.borrow_mut();
+            match command {
+                KeyCommand::Orbit(horizontal, vertical) => {
+                    scene.camera.rotate_horizontally(&gl, horizontal);
+                    scene.camera.rotate_vertically(&gl, vertical);
+                }
+                KeyCommand::Zoom(factor) => scene.camera.zoom(&gl, factor),
+                KeyCommand::ToggleSnow => scene.toggle_snow(),
+                KeyCommand::ResetView => scene.camera.reset(&gl),
+            }
+        }) as Box<dyn FnMut(_)>);
+        canvas.set_onkeydown(Some(on_key_down.as_ref().unchecked_ref()));
+        on_key_down.forget();
+    }
+
+    if let Some(query) = reduced_motion_query() {   // following changes of the reduced motion preference
+        let gl = gl.clone();
+        let scene = scene.clone();
+        let query2 = query.clone();
+        let on_change = Closure::wrap(Box::new(move || {
+            let mut scene = scene.borrow_mut();
+            let mut config = scene.config();
+            config.reduced_motion = query2.matches();
+            scene.configure(&gl, config);
+        }) as Box<dyn Fn()>);
+        query.set_onchange(Some(on_change.as_ref().unchecked_ref()));
+        on_change.forget();
+    }
+
     {   // handling resizing the canvas
         let gl = gl.clone();
-        let camera = scene.camera.clone();
+        let scene = scene.clone();
         let on_resize = Closure::wrap(Box::new(move || {
             canvas.set_width(canvas.client_width() as u32);
             canvas.set_height(canvas.client_height() as u32);
             gl.viewport(0, 0, canvas.width() as i32, canvas.height() as i32);
-            camera.on_window_resize(&gl);
+            scene.borrow_mut().on_window_resize(&gl);
         }) as Box<dyn Fn()>);
         window().set_onresize(Some(on_resize.as_ref().unchecked_ref()));
         on_resize.forget();
@@ -113,6 +227,7 @@ pub fn start() -> Result<(), JsValue> {
         let render_loop = Rc::new(RefCell::new(None));
         let render_loop_2 = render_loop.clone();
         *render_loop_2.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+            let mut scene = scene.borrow_mut();
             scene.next_frame(&gl);
             scene.draw(&gl);
 
@@ -1,22 +1,67 @@
 #![allow(dead_code)]
 
 use cgmath::{Point3, Vector3, Vector4};
-use wasm_bindgen::__rt::core::mem;
-use web_sys::{WebGl2RenderingContext as GL, WebGlBuffer};
+use web_sys::WebGl2RenderingContext as GL;
 
+use crate::color::LinearRgb;
+use crate::gpu::Buffer;
 use crate::shader::LIGHTS_UBO_BINDING_POINT;
+#[cfg(test)]
+use crate::shader::validation::UniformBlock;
 
-const MAX_LIGHTS: i32 = 4;
+/// Size of the `light` array in the `Lights` block, shaders get it as `MAX_LIGHTS`
+pub const MAX_LIGHTS: i32 = 4;
 
-struct Light {
-    position: Point3<f32>,
-    ambient: Vector3<f32>,
-    diffuse: Vector3<f32>,
-    specular: Vector3<f32>,
+// std140 offsets in the Lights block, the array is aligned to vec4 and vec3 takes as much space as vec4
+const LIGHTS_OFFSET: i32 = 16;
+const LIGHT_SIZE: i32 = 64;
+const VECTOR3_SIZE: i32 = 16;
+
+/// Index of the light in the block
+pub type LightId = usize;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Light {
+    /// `w` is 1 for lights at a position and 0 for directional ones, like in fixed-function GL,
+    /// `xyz` of a directional light points towards the light
+    position: Vector4<f32>,
+    ambient: LinearRgb,
+    diffuse: LinearRgb,
+    specular: LinearRgb,
+}
+
+impl Light {
+    pub fn point(position: Point3<f32>, ambient: LinearRgb, diffuse: LinearRgb, specular: LinearRgb) -> Self {
+        Light { position: position.to_homogeneous(), ambient, diffuse, specular }
+    }
+
+    /// Light so far away that its rays are parallel, like the moon's
+    pub fn directional(towards_light: Vector3<f32>, ambient: LinearRgb, diffuse: LinearRgb, specular: LinearRgb) -> Self {
+        Light { position: towards_light.extend(0.), ambient, diffuse, specular }
+    }
+
+    pub fn ambient(&self) -> LinearRgb {
+        self.ambient
+    }
+
+    pub fn diffuse(&self) -> LinearRgb {
+        self.diffuse
+    }
+
+    /// Light in std140 layout, vec3 members are padded to vec4
+    fn as_vec(&self) -> Vec<f32> {
+        let [ambient, diffuse, specular] = [self.ambient.0, self.diffuse.0, self.specular.0];
+        vec![
+            self.position.x, self.position.y, self.position.z, self.position.w,
+            ambient.x, ambient.y, ambient.z, 0.,
+            diffuse.x, diffuse.y, diffuse.z, 0.,
+            specular.x, specular.y, specular.z, 0.,
+        ]
+    }
 }
 
 pub struct Lights {
-    ubo: WebGlBuffer,
+    ubo: Buffer,
     lights: Vec<Light>,
 }
 
@@ -25,55 +70,81 @@ impl Lights {
         Lights { ubo: Lights::setup_lights_ubo(gl), lights: vec![] }
     }
 
-    fn setup_lights_ubo(gl: &GL) -> WebGlBuffer {
-        let lights_ubo = gl.create_buffer().unwrap();
-        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&lights_ubo));
-        let vector3_size = mem::size_of::<Vector4<f32>>() as i32; // there's no mistake, Vector3 takes the same amount of memory as Vector4
-        gl.buffer_data_with_i32(GL::UNIFORM_BUFFER, 16 + MAX_LIGHTS * 4 * vector3_size, GL::STATIC_DRAW);
-        gl.bind_buffer_base(GL::UNIFORM_BUFFER, LIGHTS_UBO_BINDING_POINT, Some(&lights_ubo));
+    fn setup_lights_ubo(gl: &GL) -> Buffer {
+        let lights_ubo = Buffer::new(gl);
+        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&*lights_ubo));
+        gl.buffer_data_with_i32(GL::UNIFORM_BUFFER, LIGHTS_OFFSET + MAX_LIGHTS * LIGHT_SIZE, GL::STATIC_DRAW);
+        gl.bind_buffer_base(GL::UNIFORM_BUFFER, LIGHTS_UBO_BINDING_POINT, Some(&*lights_ubo));
         gl.bind_buffer(GL::UNIFORM_BUFFER, None);
         lights_ubo
     }
 
-    pub fn add(&mut self, gl: &GL, position: Point3<f32>, ambient: Vector3<f32>, diffuse: Vector3<f32>, specular: Vector3<f32>) {
-        let light = Light {position, ambient, diffuse, specular};
+    pub fn add(&mut self, gl: &GL, light: Light) -> LightId {
         self.lights.push(light);
-        let lights_no= self.lights.len() as i32;
-
-        let vector3_size = mem::size_of::<Vector4<f32>>() as i32;
-        let light_size = 4 * vector3_size;
-        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&self.ubo));
+        let lights_no = self.lights.len() as i32;
 
+        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&*self.ubo));
         unsafe {
             let array: [i32; 1] = [lights_no];
             let js_array = js_sys::Int32Array::view(&array[..]);
             gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, 0, &js_array);
         }
+        gl.bind_buffer(GL::UNIFORM_BUFFER, None);
 
-        unsafe {
-            let array: [f32; 3] = position.into();
-            let js_array = js_sys::Float32Array::view(&array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, 16 + (lights_no - 1) * light_size + 0 * vector3_size, &js_array);
-        }
+        let id = self.lights.len() - 1;
+        self.write(gl, id);
+        id
+    }
 
-        unsafe {
-            let array: [f32; 3] = ambient.into();
-            let js_array = js_sys::Float32Array::view(&array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, 16 + (lights_no - 1) * light_size + 1 * vector3_size, &js_array);
+    /// Replaces the light, e.g. when it moves or changes color
+    pub fn set(&mut self, gl: &GL, id: LightId, light: Light) {
+        if self.lights[id] != light {
+            self.lights[id] = light;
+            self.write(gl, id);
         }
+    }
 
+    fn write(&self, gl: &GL, id: LightId) {
+        let light_offset = LIGHTS_OFFSET + id as i32 * LIGHT_SIZE;
+        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&*self.ubo));
         unsafe {
-            let array: [f32; 3] = diffuse.into();
-            let js_array = js_sys::Float32Array::view(&array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, 16 + (lights_no - 1) * light_size + 2 * vector3_size, &js_array);
+            let data = self.lights[id].as_vec();
+            let js_array = js_sys::Float32Array::view(&data);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, light_offset, &js_array);
         }
+        gl.bind_buffer(GL::UNIFORM_BUFFER, None);
+    }
+}
 
-        unsafe {
-            let array: [f32; 3] = specular.into();
-            let js_array = js_sys::Float32Array::view(&array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, 16 + (lights_no - 1) * light_size + 3 * vector3_size, &js_array);
+/// Layout the Lights block is written with, shaders are checked against it in tests
+#[cfg(test)]
+pub fn ubo_layout() -> UniformBlock {
+    let mut block = UniformBlock::new("Lights", LIGHTS_UBO_BINDING_POINT, LIGHTS_OFFSET + MAX_LIGHTS * LIGHT_SIZE)
+        .member("lightsNo", 0);
+    for light in 0..MAX_LIGHTS {
+        for (i, name) in ["position", "ambient", "diffuse", "specular"].iter().enumerate() {
+            block = block.member(&format!("light[{}].{}", light, name), LIGHTS_OFFSET + light * LIGHT_SIZE + i as i32 * VECTOR3_SIZE);
         }
+    }
+    block
+}
 
-        gl.bind_buffer(GL::UNIFORM_BUFFER, None);
+#[cfg(test)]
+mod tests {
+    use cgmath::{Point3, vec3};
+
+    use crate::color::LinearRgb;
+    use crate::lights::{Light, LIGHT_SIZE};
+
+    #[test]
+    fn directional_lights_have_no_position() {
+        let white = LinearRgb::new(1., 1., 1.);
+
+        let point = Light::point(Point3::new(1., 2., 3.), white, white, white).as_vec();
+        let directional = Light::directional(vec3(0., 1., 0.), white, white, white).as_vec();
+
+        assert_eq!(point.len() * 4, LIGHT_SIZE as usize);
+        assert_eq!(&point[0..4], &[1., 2., 3., 1.]);
+        assert_eq!(&directional[0..4], &[0., 1., 0., 0.]);
     }
 }
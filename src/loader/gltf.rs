@@ -0,0 +1,421 @@
+//! glTF 2.0 models, both `.gltf` with separate or embedded buffers and binary `.glb`.
+//! Metallic-roughness materials are approximated with the Phong materials the shader uses.
+use std::collections::HashMap;
+
+use base64::Engine;
+use cgmath::{Matrix4, Point3, SquareMatrix, vec2, vec3, Vector3};
+use gltf::{buffer, image, Gltf};
+
+use crate::color::LinearRgb;
+use crate::loader::{flat_normals, LoadError, MaterialData, MeshData, ModelData, NodeData};
+use crate::mesh::Vertex;
+
+/// Specular color of non-metallic surfaces, metals reflect their base color instead
+const DIELECTRIC_SPECULAR: f32 = 0.04;
+/// Ambient light reflected by imported materials, relative to the diffuse color
+const AMBIENT_FACTOR: f32 = 0.2;
+
+/// Parses a glTF model, `name` is used to name images embedded in it. `resources` are files the model references by URI,
+/// like `.bin` buffers or images, by the URI as written in the model. Images missing in resources are fetched by materials.
+pub fn parse(name: &str, bytes: &[u8], resources: &HashMap<String, Vec<u8>>) -> Result<ModelData, LoadError> {
+    let Gltf { document, blob } = Gltf::from_slice(bytes).map_err(LoadError::Gltf)?;
+    let buffers: Vec<Vec<u8>> = document.buffers()
+        .map(|buffer| match buffer.source() {
+            buffer::Source::Bin => blob.clone().ok_or_else(|| LoadError::MissingResource("binary chunk".to_string())),
+            buffer::Source::Uri(uri) => resource(uri, resources),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let images = document.images()
+        .filter_map(|image| match image.source() {
+            image::Source::View { view, .. } => {
+                let start = view.offset();
+                let bytes = buffers.get(view.buffer().index())
+                    .and_then(|buffer| buffer.get(start..start + view.length()))
+                    .ok_or_else(|| LoadError::InvalidData(format!("image {} is outside of its buffer", image.index())));
+                Some(bytes.map(|bytes| (image_name(name, &image), bytes.to_vec())))
+            }
+            image::Source::Uri { uri, .. } => match resource(uri, resources) {
+                Ok(bytes) => Some(Ok((image_name(name, &image), bytes))),
+                // the image may be on the site next to the model
+                Err(LoadError::MissingResource(_)) => None,
+                Err(e) => Some(Err(e)),
+            },
+        })
+        .collect::<Result<_, _>>()?;
+
+    let materials = document.materials().map(|material| material_data(name, &material)).collect();
+
+    // glTF meshes are made of primitives, each with its own material, every primitive becomes a separate mesh
+    let mut meshes = vec![];
+    let mut primitives_of_mesh = vec![];
+    for mesh in document.meshes() {
+        let mut primitives = vec![];
+        for primitive in mesh.primitives() {
+            if let Some(data) = mesh_data(&primitive, &buffers)? {
+                primitives.push(meshes.len());
+                meshes.push(data);
+            }
+        }
+        primitives_of_mesh.push(primitives);
+    }
+    if meshes.is_empty() {
+        return Err(LoadError::NoGeometry);
+    }
+
+    let scene = document.default_scene().or_else(|| document.scenes().next());
+    let nodes = match scene {
+        Some(scene) => scene.nodes().map(|node| node_data(&node, &primitives_of_mesh)).collect(),
+        // a file with no scene is a library of meshes, show all of them
+        None => vec![NodeData { transform: Matrix4::identity(), meshes: (0..meshes.len()).collect(), children: vec![] }],
+    };
+    let model = ModelData { meshes, materials, images, nodes };
+    // meshes are only drawn where nodes of the scene place them
+    if model.mesh_transforms().iter().all(Vec::is_empty) {
+        return Err(LoadError::NoGeometry);
+    }
+    Ok(model)
+}
+
+/// Contents of a buffer or image URI, either embedded as a data URI or given in resources
+fn resource(uri: &str, resources: &HashMap<String, Vec<u8>>) -> Result<Vec<u8>, LoadError> {
+    if uri.starts_with("data:") {
+        return decode_data_uri(uri);
+    }
+    resources.get(uri).cloned().ok_or_else(|| LoadError::MissingResource(uri.to_string()))
+}
+
+/// Only base64 data URIs are valid in glTF, e.g. `data:application/octet-stream;base64,AAAA`
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, LoadError> {
+    let invalid = || LoadError::InvalidDataUri(uri.to_string());
+    let (header, data) = uri.split_once(',').ok_or_else(invalid)?;
+    if !header.ends_with(";base64") {
+        return Err(invalid());
+    }
+    base64::engine::general_purpose::STANDARD.decode(data).map_err(|_| invalid())
+}
+
+/// Embedded images have no file names, they are named after the model, external ones keep their URI
+fn image_name(model_name: &str, image: &image::Image) -> String {
+    match image.source() {
+        image::Source::Uri { uri, .. } if !uri.starts_with("data:") => uri.to_string(),
+        _ => format!("{}#image{}", model_name, image.index()),
+    }
+}
+
+/// Triangles of the primitive, `None` for primitives that aren't drawn
+fn mesh_data(primitive: &gltf::Primitive, buffers: &[Vec<u8>]) -> Result<Option<MeshData>, LoadError> {
+    if primitive.mode() != gltf::mesh::Mode::Triangles {
+        return Ok(None); // points, lines and strips aren't drawn
+    }
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|b| &b[..]));
+    let positions: Vec<[f32; 3]> = match reader.read_positions() {
+        Some(positions) => positions.collect(),
+        None => return Ok(None),
+    };
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+    let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|uvs| uvs.into_f32().collect());
+    let mut indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    indices.truncate(indices.len() / 3 * 3);
+    if indices.is_empty() {
+        return Ok(None); // not even a single triangle
+    }
+    check_primitive(primitive.index(), positions.len(), normals.as_ref().map(Vec::len), uvs.as_ref().map(Vec::len), &indices)?;
+
+    let mut vertices: Vec<Vertex> = positions.iter().enumerate()
+        .map(|(i, p)| Vertex {
+            position: Point3::from(*p),
+            normal: normals.as_ref().map_or(vec3(0., 0., 0.), |n| Vector3::from(n[i])),
+            // glTF puts the origin of texture coordinates in the top left corner, GL in the bottom left one
+            uv: uvs.as_ref().map_or(vec2(0., 0.), |uv| vec2(uv[i][0], 1. - uv[i][1])),
+        })
+        .collect();
+    // the specification says that meshes without normals should be flat shaded
+    if normals.is_none() {
+        let (flat_vertices, flat_indices) = flat_normals(&vertices, &indices);
+        vertices = flat_vertices;
+        indices = flat_indices;
+    }
+    Ok(Some(MeshData { vertices, indices, material: primitive.material().index() }))
+}
+
+/// Every attribute needs a value for every vertex, and indices have to point at vertices
+fn check_primitive(index: usize, positions: usize, normals: Option<usize>, uvs: Option<usize>, indices: &[u32]) -> Result<(), LoadError> {
+    for (attribute, count) in [("NORMAL", normals), ("TEXCOORD_0", uvs)] {
+        if let Some(count) = count.filter(|&count| count != positions) {
+            return Err(LoadError::InvalidData(format!("primitive {} has {} {} values for {} positions", index, count, attribute, positions)));
+        }
+    }
+    if let Some(&vertex) = indices.iter().find(|&&i| i as usize >= positions) {
+        return Err(LoadError::InvalidData(format!("primitive {} uses vertex {} of {}", index, vertex, positions)));
+    }
+    Ok(())
+}
+
+fn material_data(model_name: &str, material: &gltf::Material) -> MaterialData {
+    let pbr = material.pbr_metallic_roughness();
+    // colors in glTF are linear already, only textures are sRGB encoded
+    let [r, g, b, a] = pbr.base_color_factor();
+    let base_color = vec3(r, g, b);
+    let metallic = pbr.metallic_factor();
+    let roughness = pbr.roughness_factor();
+    let reflectance = vec3(DIELECTRIC_SPECULAR, DIELECTRIC_SPECULAR, DIELECTRIC_SPECULAR) * (1. - metallic) + base_color * metallic;
+    let texture = |texture: gltf::Texture| Some(image_name(model_name, &texture.source()));
+    MaterialData {
+        name: material.name().map_or_else(|| format!("{}#material{}", model_name, material.index().unwrap_or(0)), String::from),
+        ambient: LinearRgb(base_color * AMBIENT_FACTOR),
+        // metals have no diffuse reflection, all of their color comes from the specular one
+        diffuse: LinearRgb(base_color * (1. - metallic)),
+        specular: LinearRgb(reflectance * (1. - roughness)),
+        shininess: (2. / roughness.max(0.01).powi(4) - 2.).clamp(1., 256.),
+        emissive: LinearRgb(Vector3::from(material.emissive_factor())),
+        reflectivity: metallic,
+        // there's no alpha testing, masked materials are drawn solid
+        alpha: if material.alpha_mode() == gltf::material::AlphaMode::Blend { a } else { 1. },
+        diffuse_texture: pbr.base_color_texture().and_then(|info| texture(info.texture())),
+        normal_texture: material.normal_texture().and_then(|normal| texture(normal.texture())),
+        emissive_texture: material.emissive_texture().and_then(|info| texture(info.texture())),
+    }
+}
+
+fn node_data(node: &gltf::Node, primitives_of_mesh: &[Vec<usize>]) -> NodeData {
+    NodeData {
+        transform: Matrix4::from(node.transform().matrix()),
+        meshes: node.mesh().map_or_else(Vec::new, |mesh| primitives_of_mesh[mesh.index()].clone()),
+        children: node.children().map(|child| node_data(&child, primitives_of_mesh)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cgmath::{InnerSpace, Point3, Transform, vec3};
+
+    use crate::color::LinearRgb;
+    use crate::loader::LoadError;
+    use crate::loader::gltf::parse;
+    use crate::texture::Image;
+
+    static PRESENT: &[u8] = include_bytes!("../../models/samples/present.gltf");
+    static STAR: &[u8] = include_bytes!("../../models/samples/star.glb");
+
+    /// Single triangle with positions in a separate `triangle.bin` file
+    const TRIANGLE: &str = r#"{
+        "asset": {"version": "2.0"},
+        "scenes": [{"nodes": [0]}],
+        "nodes": [{"mesh": 0}],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+        "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0]}],
+        "bufferViews": [{"buffer": 0, "byteLength": 36}],
+        "buffers": [{"byteLength": 36, "uri": "triangle.bin"}]
+    }"#;
+
+    fn triangle_bin() -> Vec<u8> {
+        [0_f32, 0., 0., 1., 0., 0., 0., 1., 0.].iter().flat_map(|v| v.to_le_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn reads_hierarchy_with_reused_mesh() {
+        let model = parse("present", PRESENT, &HashMap::new()).unwrap();
+
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.nodes.len(), 1);
+        assert_eq!(model.nodes[0].children.len(), 3);
+        let transforms = model.mesh_transforms();
+        assert_eq!(transforms[0].len(), 1, "box is used once");
+        assert_eq!(transforms[1].len(), 2, "ribbon is used twice");
+        // the second ribbon is turned by 90 degrees around the vertical axis, under the root moved one unit up
+        let ribbon_end = transforms[1][1].transform_point(Point3::new(0., 0., 1.));
+        assert!((ribbon_end - Point3::new(1., 1., 0.)).magnitude() < 1e-6, "{:?}", ribbon_end);
+    }
+
+    #[test]
+    fn bounds_include_node_transforms() {
+        let model = parse("present", PRESENT, &HashMap::new()).unwrap();
+
+        let bounds = model.bounds();
+
+        assert!((bounds.min - Point3::new(-1.05, -0.05, -1.05)).magnitude() < 1e-5, "{:?}", bounds);
+        assert!((bounds.max - Point3::new(1.05, 2.05, 1.05)).magnitude() < 1e-5, "{:?}", bounds);
+    }
+
+    #[test]
+    fn converts_metallic_roughness_materials() {
+        let model = parse("present", PRESENT, &HashMap::new()).unwrap();
+
+        let paper = &model.materials[0];
+        assert_eq!(paper.name, "present_paper");
+        assert_eq!(paper.diffuse, LinearRgb::new(1., 1., 1.));
+        assert_eq!(paper.diffuse_texture.as_deref(), Some("present#image0"));
+        let ribbon = &model.materials[1];
+        assert_eq!(ribbon.diffuse, LinearRgb::BLACK, "metals have no diffuse color");
+        assert!(ribbon.specular.0.x > ribbon.specular.0.z, "metals reflect their own color");
+        assert!(ribbon.shininess > paper.shininess, "smoother surfaces have sharper highlights");
+        assert_eq!(model.meshes[1].material, Some(1));
+    }
+
+    #[test]
+    fn extracts_embedded_images() {
+        let model = parse("present", PRESENT, &HashMap::new()).unwrap();
+
+        assert_eq!(model.images.len(), 1);
+        let (name, bytes) = &model.images[0];
+        assert_eq!(name, "present#image0");
+        let image = Image::decode(bytes).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+    }
+
+    #[test]
+    fn flips_texture_coordinates() {
+        let model = parse("present", PRESENT, &HashMap::new()).unwrap();
+
+        // the first vertex of the box is the bottom left corner of its first face, at the top left in glTF
+        assert_eq!(model.meshes[0].vertices[0].uv, cgmath::vec2(0., 0.));
+    }
+
+    #[test]
+    fn reads_binary_model_with_flat_normals() {
+        let model = parse("star", STAR, &HashMap::new()).unwrap();
+
+        let star = &model.meshes[0];
+        assert_eq!(star.indices.len(), 20 * 3);
+        assert_eq!(star.vertices.len(), 20 * 3, "flat shaded triangles don't share vertices");
+        assert!(star.vertices.iter().all(|v| (v.normal.magnitude() - 1.).abs() < 1e-5));
+        assert_eq!(model.materials[0].emissive, LinearRgb::new(1., 0.8, 0.3));
+        assert_eq!(model.mesh_transforms()[0][0].transform_point(Point3::new(0., 1., 0.)), Point3::new(0., 2., 0.));
+    }
+
+    #[test]
+    fn reads_buffers_from_resources() {
+        let resources = vec![("triangle.bin".to_string(), triangle_bin())].into_iter().collect();
+
+        let model = parse("triangle", TRIANGLE.as_bytes(), &resources).unwrap();
+
+        let triangle = &model.meshes[0];
+        assert_eq!(triangle.indices, vec![0, 1, 2]);
+        assert_eq!(triangle.vertices[1].position, Point3::new(1., 0., 0.));
+        assert_eq!(triangle.vertices[0].normal, vec3(0., 0., 1.));
+        assert_eq!(triangle.material, None);
+    }
+
+    #[test]
+    fn reports_missing_buffer() {
+        let result = parse("triangle", TRIANGLE.as_bytes(), &HashMap::new());
+
+        assert!(matches!(result, Err(LoadError::MissingResource(uri)) if uri == "triangle.bin"));
+    }
+
+    /// The triangle with normals and indices read from `extra.bin`, `normals` and `indices` are their counts
+    fn triangle_with(normals: usize, indices: usize) -> String {
+        format!(r#"{{
+            "asset": {{"version": "2.0"}},
+            "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0, "NORMAL": 1}}, "indices": 2}}]}}],
+            "accessors": [
+                {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0]}},
+                {{"bufferView": 1, "componentType": 5126, "count": {}, "type": "VEC3"}},
+                {{"bufferView": 2, "componentType": 5125, "count": {}, "type": "SCALAR"}}
+            ],
+            "bufferViews": [
+                {{"buffer": 0, "byteLength": 36}},
+                {{"buffer": 1, "byteLength": 36}},
+                {{"buffer": 1, "byteOffset": 36, "byteLength": 12}}
+            ],
+            "buffers": [{{"byteLength": 36, "uri": "triangle.bin"}}, {{"byteLength": 48, "uri": "extra.bin"}}]
+        }}"#, normals, indices)
+    }
+
+    /// Normals facing the camera, then indices of the triangle with the last one pointing at `last_index`
+    fn extra_bin(last_index: u32) -> Vec<u8> {
+        let normals = [0_f32, 0., 1., 0., 0., 1., 0., 0., 1.].iter().flat_map(|v| v.to_le_bytes().to_vec());
+        let indices: Vec<u8> = [0_u32, 1, last_index].iter().flat_map(|i| i.to_le_bytes().to_vec()).collect();
+        normals.chain(indices).collect()
+    }
+
+    fn triangle_resources(last_index: u32) -> HashMap<String, Vec<u8>> {
+        vec![("triangle.bin".to_string(), triangle_bin()), ("extra.bin".to_string(), extra_bin(last_index))].into_iter().collect()
+    }
+
+    #[test]
+    fn reads_normals_and_indices() {
+        let model = parse("triangle", triangle_with(3, 3).as_bytes(), &triangle_resources(2)).unwrap();
+
+        assert_eq!(model.meshes[0].indices, vec![0, 1, 2]);
+        assert_eq!(model.meshes[0].vertices[2].normal, vec3(0., 0., 1.));
+    }
+
+    #[test]
+    fn reports_attribute_counts_not_matching_positions() {
+        let result = parse("triangle", triangle_with(2, 3).as_bytes(), &triangle_resources(2));
+
+        assert!(matches!(result, Err(LoadError::InvalidData(_))), "{:?}", result.err());
+    }
+
+    #[test]
+    fn reports_indices_past_the_last_vertex() {
+        let result = parse("triangle", triangle_with(3, 3).as_bytes(), &triangle_resources(7));
+
+        assert!(matches!(result, Err(LoadError::InvalidData(_))), "{:?}", result.err());
+    }
+
+    #[test]
+    fn skips_accessors_past_the_end_of_short_buffers() {
+        let resources = vec![("triangle.bin".to_string(), triangle_bin()[..20].to_vec())].into_iter().collect();
+
+        let result = parse("triangle", TRIANGLE.as_bytes(), &resources);
+
+        assert!(matches!(result, Err(LoadError::NoGeometry)), "{:?}", result.err());
+    }
+
+    #[test]
+    fn skips_primitives_without_a_whole_triangle() {
+        let result = parse("triangle", triangle_with(3, 2).as_bytes(), &triangle_resources(2));
+
+        assert!(matches!(result, Err(LoadError::NoGeometry)), "{:?}", result.err());
+    }
+
+    #[test]
+    fn reports_scene_without_meshes() {
+        let empty_node = TRIANGLE.replace(r#""nodes": [{"mesh": 0}]"#, r#""nodes": [{"name": "camera"}]"#);
+        let resources = vec![("triangle.bin".to_string(), triangle_bin())].into_iter().collect();
+
+        let result = parse("triangle", empty_node.as_bytes(), &resources);
+
+        assert!(matches!(result, Err(LoadError::NoGeometry)), "{:?}", result.err());
+    }
+
+    #[test]
+    fn reports_images_past_the_end_of_short_buffers() {
+        let with_image = TRIANGLE.replace(r#""buffers""#, r#""images": [{"bufferView": 0, "mimeType": "image/png"}], "buffers""#);
+        let resources = vec![("triangle.bin".to_string(), triangle_bin()[..20].to_vec())].into_iter().collect();
+
+        let result = parse("triangle", with_image.as_bytes(), &resources);
+
+        assert!(matches!(result, Err(LoadError::InvalidData(_))), "{:?}", result.err());
+    }
+
+    #[test]
+    fn reads_external_images_from_resources() {
+        let textured = TRIANGLE.replace(r#""buffers""#, r#""materials": [{"pbrMetallicRoughness": {"baseColorTexture": {"index": 0}}}],
+            "textures": [{"source": 0}], "images": [{"uri": "paper.png"}], "buffers""#);
+        let mut resources: HashMap<String, Vec<u8>> = vec![("triangle.bin".to_string(), triangle_bin())].into_iter().collect();
+
+        let fetched = parse("triangle", textured.as_bytes(), &resources).unwrap();
+        resources.insert("paper.png".to_string(), vec![1, 2, 3]);
+        let dropped = parse("triangle", textured.as_bytes(), &resources).unwrap();
+
+        assert!(fetched.images.is_empty(), "images missing in resources are fetched");
+        assert_eq!(dropped.images, vec![("paper.png".to_string(), vec![1, 2, 3])]);
+        assert_eq!(dropped.materials[0].diffuse_texture.as_deref(), Some("paper.png"));
+    }
+
+    #[test]
+    fn reports_broken_model() {
+        let result = parse("broken", b"{\"asset\": ", &HashMap::new());
+
+        assert!(matches!(result, Err(LoadError::Gltf(_))));
+    }
+}
@@ -0,0 +1,376 @@
+//! Importing models from files. Parsing only produces plain data, so it can be tested without WebGL,
+//! `ImportedModel` turns that data into meshes and materials.
+use std::collections::HashMap;
+use std::fmt;
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, vec3, Vector3};
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::camera::View;
+use crate::color::{ColorSpace, LinearRgb};
+use crate::culling::Aabb;
+use crate::material::{MaterialError, MaterialId};
+use crate::material_library::MaterialLibrary;
+use crate::mesh::{Mesh, Vertex};
+use crate::model::{Instance, Model};
+use crate::render::Pipeline;
+use crate::render::queue::RenderQueue;
+use crate::texture::Textures;
+
+pub mod gltf;
+pub mod obj;
+
+/// Used for meshes that don't reference any material
+const DEFAULT_MATERIAL: &str = "default";
+
+#[derive(Debug)]
+pub enum LoadError {
+    Obj(tobj::LoadError),
+    Mtl(tobj::LoadError),
+    Gltf(::gltf::Error),
+    /// File referenced by the model, like a `.bin` buffer, that wasn't provided
+    MissingResource(String),
+    InvalidDataUri(String),
+    /// Data that doesn't fit together, like indices past the last vertex
+    InvalidData(String),
+    NoGeometry,
+    Material(MaterialError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Obj(e) => write!(f, "cannot parse OBJ model: {}", e),
+            LoadError::Mtl(e) => write!(f, "cannot parse MTL materials: {}", e),
+            LoadError::Gltf(e) => write!(f, "cannot parse glTF model: {}", e),
+            LoadError::MissingResource(uri) => write!(f, "missing file '{}' referenced by the model", uri),
+            LoadError::InvalidDataUri(uri) => write!(f, "invalid data URI '{}'", uri.chars().take(40).collect::<String>()),
+            LoadError::InvalidData(problem) => write!(f, "invalid model data: {}", problem),
+            LoadError::NoGeometry => write!(f, "model doesn't contain any triangles"),
+            LoadError::Material(e) => write!(f, "cannot add model materials: {}", e),
+        }
+    }
+}
+
+/// What to do with meshes that come without normals
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NormalMode {
+    /// Averaged over all triangles sharing a position, for rounded surfaces
+    Smooth,
+    /// Every triangle gets its own vertices with the triangle's normal, for hard edges
+    Flat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialData {
+    pub name: String,
+    /// Colors are converted from the color space of the file while parsing
+    pub ambient: LinearRgb,
+    pub diffuse: LinearRgb,
+    pub specular: LinearRgb,
+    pub shininess: f32,
+    pub emissive: LinearRgb,
+    /// How much of the environment the surface mirrors, 0 to 1
+    pub reflectivity: f32,
+    /// Opacity, 0 to 1
+    pub alpha: f32,
+    /// Texture names as used in the file, resolved by `Textures::load`
+    pub diffuse_texture: Option<String>,
+    pub normal_texture: Option<String>,
+    pub emissive_texture: Option<String>,
+}
+
+#[cfg(test)]
+impl MaterialData {
+    /// Black, opaque and untextured, tests fill in what they check with struct update syntax
+    pub fn named(name: &str) -> Self {
+        MaterialData {
+            name: name.to_string(),
+            ambient: LinearRgb::BLACK,
+            diffuse: LinearRgb::BLACK,
+            specular: LinearRgb::BLACK,
+            shininess: 1.,
+            emissive: LinearRgb::BLACK,
+            reflectivity: 0.,
+            alpha: 1.,
+            diffuse_texture: None,
+            normal_texture: None,
+            emissive_texture: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Index into `ModelData::materials`
+    pub material: Option<usize>,
+}
+
+#[cfg(test)]
+impl MeshData {
+    /// Right triangle facing +Z with distinct texture coordinates on every vertex, for round trips through exporters
+    pub fn triangle(material: usize) -> Self {
+        let vertex = |x, y, u| Vertex { position: Point3::new(x, y, 0.), normal: vec3(0., 0., 1.), uv: cgmath::vec2(u, 0.25) };
+        MeshData { vertices: vec![vertex(0., 0., 0.), vertex(1., 0., 1.), vertex(0., 1., 0.)], indices: vec![0, 1, 2], material: Some(material) }
+    }
+}
+
+/// Part of the model hierarchy, placed relative to its parent
+#[derive(Debug)]
+pub struct NodeData {
+    pub transform: Matrix4<f32>,
+    /// Indices into `ModelData::meshes`, the same mesh can be used by many nodes
+    pub meshes: Vec<usize>,
+    pub children: Vec<NodeData>,
+}
+
+impl NodeData {
+    fn visit(&self, parent: Matrix4<f32>, f: &mut impl FnMut(&NodeData, Matrix4<f32>)) {
+        let transform = parent * self.transform;
+        f(self, transform);
+        for child in &self.children {
+            child.visit(transform, f);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialData>,
+    /// Images stored inside the model file, by the name materials use for them
+    pub images: Vec<(String, Vec<u8>)>,
+    /// Roots of the hierarchy
+    pub nodes: Vec<NodeData>,
+}
+
+impl ModelData {
+    /// Model space transformations of every mesh, one for each node using it
+    pub fn mesh_transforms(&self) -> Vec<Vec<Matrix4<f32>>> {
+        let mut transforms = vec![vec![]; self.meshes.len()];
+        for node in &self.nodes {
+            node.visit(Matrix4::identity(), &mut |node, transform| {
+                for &mesh in &node.meshes {
+                    transforms[mesh].push(transform);
+                }
+            });
+        }
+        transforms
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let boxes: Vec<Aabb> = self.meshes.iter().zip(self.mesh_transforms())
+            .filter(|(mesh, _)| !mesh.vertices.is_empty())
+            .flat_map(|(mesh, transforms)| {
+                let aabb = Aabb::from_points(mesh.vertices.iter().map(|v| &v.position));
+                transforms.into_iter().map(move |t| aabb.transform(&t))
+            })
+            .collect();
+        Aabb::from_points(boxes.iter().flat_map(|b| vec![&b.min, &b.max]))
+    }
+
+    /// Adds images textures refer to from `files`, e.g. dropped together with the model. Files are matched by
+    /// the texture name, or by the file name alone when materials name textures with a path.
+    pub fn add_referenced_images(&mut self, files: &HashMap<String, Vec<u8>>) {
+        let names: Vec<String> = self.materials.iter()
+            .flat_map(|m| vec![&m.diffuse_texture, &m.normal_texture, &m.emissive_texture])
+            .flatten()
+            .cloned()
+            .collect();
+        for name in names {
+            if self.images.iter().any(|(image, _)| *image == name) {
+                continue;
+            }
+            let file_name = name.rsplit(['/', '\\']).next().unwrap_or(&name);
+            if let Some(bytes) = files.get(&name).or_else(|| files.get(file_name)) {
+                self.images.push((name.clone(), bytes.clone()));
+            }
+        }
+    }
+
+    /// Images only used as normal maps hold directions, every other image holds colors
+    pub fn image_color_space(&self, image: &str) -> ColorSpace {
+        let uses = |texture: &Option<String>| texture.as_deref() == Some(image);
+        let color = self.materials.iter().any(|m| uses(&m.diffuse_texture) || uses(&m.emissive_texture));
+        let normal = self.materials.iter().any(|m| uses(&m.normal_texture));
+        if normal && !color { ColorSpace::Linear } else { ColorSpace::Srgb }
+    }
+}
+
+/// Sets normals of all vertices, area weighted average of normals of triangles sharing the position
+pub fn smooth_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    // vertices split at UV seams still share the position, they should share the normal as well
+    let key = |v: &Vertex| [v.position.x.to_bits(), v.position.y.to_bits(), v.position.z.to_bits()];
+    let mut sums: HashMap<[u32; 3], Vector3<f32>> = HashMap::new();
+    for triangle in indices.chunks(3) {
+        let [a, b, c] = [&vertices[triangle[0] as usize], &vertices[triangle[1] as usize], &vertices[triangle[2] as usize]];
+        // not normalized on purpose, the length of the cross product is proportional to the triangle's area
+        let normal = (b.position - a.position).cross(c.position - a.position);
+        for v in &[a, b, c] {
+            *sums.entry(key(v)).or_insert_with(|| vec3(0., 0., 0.)) += normal;
+        }
+    }
+    for v in vertices.iter_mut() {
+        let sum = sums.get(&key(v)).copied().unwrap_or_else(|| vec3(0., 0., 0.));
+        v.normal = if sum.magnitude2() > 0. { sum.normalize() } else { vec3(0., 1., 0.) };
+    }
+}
+
+/// Gives every triangle its own vertices with the normal of the triangle
+pub fn flat_normals(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut flat = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks(3) {
+        let [a, b, c] = [vertices[triangle[0] as usize], vertices[triangle[1] as usize], vertices[triangle[2] as usize]];
+        let normal = (b.position - a.position).cross(c.position - a.position);
+        let normal = if normal.magnitude2() > 0. { normal.normalize() } else { vec3(0., 1., 0.) };
+        flat.extend([a, b, c].iter().map(|&v| Vertex { normal, ..v }));
+    }
+    let indices = (0..flat.len() as u32).collect();
+    (flat, indices)
+}
+
+/// Model loaded from a file, every mesh is drawn with its own material, once for every node using it
+pub struct ImportedModel {
+    meshes: Vec<Mesh>,
+    bounds: Aabb,
+}
+
+impl ImportedModel {
+    pub fn new(gl: &GL, data: ModelData, transform: Matrix4<f32>, materials: &mut MaterialLibrary, textures: &mut Textures) -> Result<Self, LoadError> {
+        for (name, bytes) in &data.images {
+            // a broken image shouldn't prevent using the model, it just stays untextured
+            if let Err(e) = textures.load_embedded(gl, name, bytes, data.image_color_space(name)) {
+                web_sys::console::warn_1(&format!("Cannot load image '{}': {}", name, e).into());
+            }
+        }
+        let material_ids: Vec<MaterialId> = data.materials.iter()
+            .map(|m| materials.add_data(gl, m, textures))
+            .collect::<Result<_, _>>()
+            .map_err(LoadError::Material)?;
+        let default_material = materials.get_by_name(DEFAULT_MATERIAL).expect("Default material missing in the library");
+
+        let bounds = data.bounds().transform(&transform);
+        let mesh_transforms = data.mesh_transforms();
+        let meshes = data.meshes.into_iter().zip(mesh_transforms)
+            .filter(|(_, transforms)| !transforms.is_empty())
+            .map(|(mesh_data, transforms)| {
+                let material_id = mesh_data.material.map_or(default_material, |m| material_ids[m]);
+                let material = materials.get(material_id).unwrap();
+                let mut mesh = Mesh::new(gl, mesh_data.vertices, mesh_data.indices, transforms.len());
+                mesh.set_textures(textures.texture_set(&material.textures));
+                mesh.set_pipeline(Pipeline::for_material(material));
+                mesh.set_instances(transforms.into_iter().map(|t| Instance::new(transform * t, material_id)).collect());
+                mesh
+            })
+            .collect();
+        Ok(ImportedModel { meshes, bounds })
+    }
+
+    /// Space taken by the whole model in the scene
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    /// Scales the model to be `size` high, wide or deep (whichever is the biggest) and puts the middle of its bottom at `position`
+    pub fn fit(data: &ModelData, position: Point3<f32>, size: f32) -> Matrix4<f32> {
+        let bounds = data.bounds();
+        let extent = bounds.max - bounds.min;
+        let scale = size / extent.x.max(extent.y).max(extent.z).max(f32::EPSILON);
+        let bottom = Point3::new(bounds.center().x, bounds.min.y, bounds.center().z);
+        Matrix4::from_translation(position.to_vec()) * Matrix4::from_scale(scale) * Matrix4::from_translation(-bottom.to_vec())
+    }
+}
+
+impl Model for ImportedModel {
+    fn next_frame(&mut self, _gl: &GL) {
+        // nothing changes
+    }
+
+    fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        for mesh in &self.meshes {
+            mesh.draw(gl, queue, view);
+        }
+    }
+
+    fn meshes(&self) -> Vec<&Mesh> {
+        self.meshes.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Transform, vec2, vec3};
+
+    use crate::color::ColorSpace;
+    use crate::loader::{flat_normals, ImportedModel, MaterialData, MeshData, ModelData, NodeData, smooth_normals};
+    use crate::mesh::Vertex;
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex { position: Point3::new(x, y, z), normal: vec3(0., 0., 0.), uv: vec2(0., 0.) }
+    }
+
+    /// Two triangles folded along the X axis, like a roof
+    fn roof() -> (Vec<Vertex>, Vec<u32>) {
+        let vertices = vec![vertex(0., 1., 0.), vertex(1., 1., 0.), vertex(0., 0., 1.), vertex(0., 0., -1.)];
+        (vertices, vec![0, 2, 1, 0, 1, 3])
+    }
+
+    #[test]
+    fn smooth_normals_are_averaged_over_shared_positions() {
+        let (mut vertices, indices) = roof();
+
+        smooth_normals(&mut vertices, &indices);
+
+        assert!((vertices[0].normal - vec3(0., 1., 0.)).magnitude() < 1e-6);
+        assert!((vertices[2].normal - vec3(0., 1., 1.).normalize()).magnitude() < 1e-6);
+        assert!((vertices[3].normal - vec3(0., 1., -1.).normalize()).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn flat_normals_split_vertices() {
+        let (vertices, indices) = roof();
+
+        let (vertices, indices) = flat_normals(&vertices, &indices);
+
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+        assert!(vertices[..3].iter().all(|v| (v.normal - vec3(0., 1., 1.).normalize()).magnitude() < 1e-6));
+        assert!(vertices[3..].iter().all(|v| (v.normal - vec3(0., 1., -1.).normalize()).magnitude() < 1e-6));
+    }
+
+    #[test]
+    fn fitted_model_stands_at_position() {
+        let data = ModelData {
+            meshes: vec![MeshData { vertices: vec![vertex(1., 2., 3.), vertex(5., 4., 5.)], indices: vec![], material: None }],
+            materials: vec![],
+            images: vec![],
+            nodes: vec![NodeData { transform: Matrix4::identity(), meshes: vec![0], children: vec![] }],
+        };
+
+        let transform = ImportedModel::fit(&data, Point3::new(0., -5., 0.), 2.);
+
+        assert_eq!(transform.transform_point(Point3::new(3., 2., 4.)), Point3::new(0., -5., 0.));
+        assert_eq!(transform.transform_point(Point3::new(5., 4., 5.)), Point3::new(1., -4., 0.5));
+    }
+
+    #[test]
+    fn only_normal_maps_are_linear() {
+        let material = |diffuse: Option<&str>, normal: Option<&str>| MaterialData {
+            diffuse_texture: diffuse.map(String::from),
+            normal_texture: normal.map(String::from),
+            ..MaterialData::named("material")
+        };
+        let data = ModelData {
+            meshes: vec![],
+            materials: vec![material(Some("paper.png"), Some("bumps.png")), material(Some("shared.png"), None), material(None, Some("shared.png"))],
+            images: vec![],
+            nodes: vec![],
+        };
+
+        assert_eq!(data.image_color_space("paper.png"), ColorSpace::Srgb);
+        assert_eq!(data.image_color_space("bumps.png"), ColorSpace::Linear);
+        assert_eq!(data.image_color_space("shared.png"), ColorSpace::Srgb, "color use wins, the normal map gets its own copy");
+    }
+}
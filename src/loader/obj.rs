@@ -0,0 +1,252 @@
+use std::io::BufReader;
+
+use cgmath::{Matrix4, Point3, SquareMatrix, vec2, vec3, Vector3};
+
+use crate::color::Srgb;
+use crate::loader::{flat_normals, LoadError, MaterialData, MeshData, ModelData, NodeData, NormalMode, smooth_normals};
+use crate::mesh::Vertex;
+
+const LOAD_OPTIONS: tobj::LoadOptions = tobj::LoadOptions {
+    // one index for position, normal and texture coordinates, which is what GL needs
+    single_index: true,
+    triangulate: true,
+    ignore_points: true,
+    ignore_lines: true,
+};
+
+/// Parses an OBJ model, `mtl` is the material library it references, if there is one
+pub fn parse(obj: &[u8], mtl: Option<&[u8]>, normals: NormalMode) -> Result<ModelData, LoadError> {
+    let (models, materials) = tobj::load_obj_buf(&mut BufReader::new(obj), &LOAD_OPTIONS, |_path| match mtl {
+        Some(mtl) => tobj::load_mtl_buf(&mut BufReader::new(mtl)),
+        None => Ok((vec![], Default::default())),
+    }).map_err(LoadError::Obj)?;
+    let materials: Vec<MaterialData> = materials.map_err(LoadError::Mtl)?.iter().map(material_data).collect();
+
+    let meshes: Vec<MeshData> = models.into_iter()
+        .filter(|model| !model.mesh.indices.is_empty())
+        .map(|model| {
+            // a material missing in the library is as good as no material at all
+            let material = model.mesh.material_id.filter(|&m| m < materials.len());
+            mesh_data(model.mesh, material, normals)
+        })
+        .collect();
+    if meshes.is_empty() {
+        return Err(LoadError::NoGeometry);
+    }
+    // OBJ has no hierarchy, everything is in a single node
+    let root = NodeData { transform: Matrix4::identity(), meshes: (0..meshes.len()).collect(), children: vec![] };
+    Ok(ModelData { meshes, materials, images: vec![], nodes: vec![root] })
+}
+
+/// Parses an MTL material library on its own
+pub fn parse_materials(mtl: &[u8]) -> Result<Vec<MaterialData>, tobj::LoadError> {
+    let (materials, _) = tobj::load_mtl_buf(&mut BufReader::new(mtl))?;
+    Ok(materials.iter().map(material_data).collect())
+}
+
+fn mesh_data(mesh: tobj::Mesh, material: Option<usize>, normals: NormalMode) -> MeshData {
+    let has_normals = !mesh.normals.is_empty();
+    let mut vertices: Vec<Vertex> = (0..mesh.positions.len() / 3)
+        .map(|i| Vertex {
+            position: Point3::new(mesh.positions[3 * i], mesh.positions[3 * i + 1], mesh.positions[3 * i + 2]),
+            normal: if has_normals { vec3(mesh.normals[3 * i], mesh.normals[3 * i + 1], mesh.normals[3 * i + 2]) } else { vec3(0., 0., 0.) },
+            uv: if mesh.texcoords.is_empty() { vec2(0., 0.) } else { vec2(mesh.texcoords[2 * i], mesh.texcoords[2 * i + 1]) },
+        })
+        .collect();
+    let mut indices = mesh.indices;
+
+    if !has_normals {
+        match normals {
+            NormalMode::Smooth => smooth_normals(&mut vertices, &indices),
+            NormalMode::Flat => {
+                let (flat_vertices, flat_indices) = flat_normals(&vertices, &indices);
+                vertices = flat_vertices;
+                indices = flat_indices;
+            }
+        }
+    }
+    MeshData { vertices, indices, material }
+}
+
+fn material_data(material: &tobj::Material) -> MaterialData {
+    // tobj doesn't know about emission, it ends up with other unrecognised parameters
+    let emissive = material.unknown_param.get("Ke")
+        .map(|ke| ke.split_whitespace().filter_map(|v| v.parse().ok()).collect::<Vec<f32>>())
+        .filter(|ke| ke.len() == 3)
+        .map_or(vec3(0., 0., 0.), |ke| vec3(ke[0], ke[1], ke[2]));
+    // MTL colors are picked by eye, like all authored colors they're sRGB
+    let color = |color: Vector3<f32>| Srgb(color).to_linear();
+    let texture = |name: &str| if name.is_empty() { None } else { Some(name.to_string()) };
+    MaterialData {
+        name: material.name.clone(),
+        ambient: color(Vector3::from(material.ambient)),
+        diffuse: color(Vector3::from(material.diffuse)),
+        specular: color(Vector3::from(material.specular)),
+        shininess: material.shininess,
+        emissive: color(emissive),
+        // metallic of the PBR extension of MTL, metals are the surfaces that mirror their surroundings
+        reflectivity: material.unknown_param.get("Pm").and_then(|pm| pm.trim().parse().ok()).unwrap_or(0.),
+        // some exporters write transparency (`Tr`) instead of dissolve (`d`), it's the opposite of opacity
+        alpha: match material.unknown_param.get("Tr").and_then(|tr| tr.trim().parse::<f32>().ok()) {
+            Some(transparency) if material.dissolve == 1. => 1. - transparency,
+            _ => material.dissolve,
+        },
+        diffuse_texture: texture(&material.diffuse_texture),
+        normal_texture: texture(&material.normal_texture),
+        emissive_texture: material.unknown_param.get("map_Ke").and_then(|name| texture(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cgmath::{InnerSpace, vec3};
+
+    use crate::color::Srgb;
+    use crate::loader::{LoadError, NormalMode};
+    use crate::loader::obj::{parse, parse_materials};
+
+    /// Unit cube made of quads, without normals and texture coordinates
+    const CUBE: &[u8] = b"
+mtllib lamp.mtl
+o cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+usemtl lamp
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 2 3 7 6
+f 3 4 8 7
+f 4 1 5 8
+";
+
+    const LAMP: &[u8] = b"
+newmtl lamp
+Ka 0.1 0.1 0.1
+Kd 0.8 0.7 0.6
+Ks 0.5 0.5 0.5
+Ns 10
+Ke 1.0 0.5 0.0
+Pm 0.25
+d 0.75
+map_Kd lamp.png
+map_Ke glow.png
+";
+
+    #[test]
+    fn loads_embedded_tree() {
+        let model = parse(include_bytes!("../../models/tree.obj"), Some(include_bytes!("../../models/tree.mtl")), NormalMode::Smooth).unwrap();
+
+        assert_eq!(model.materials.len(), 3);
+        assert!(model.meshes.iter().all(|m| m.material.is_some()));
+        assert_eq!(model.meshes.iter().map(|m| m.indices.len()).sum::<usize>(), 588 * 3);
+        for mesh in &model.meshes {
+            assert!(mesh.indices.iter().all(|&i| (i as usize) < mesh.vertices.len()));
+            assert!(mesh.vertices.iter().all(|v| (v.normal.magnitude() - 1.).abs() < 1e-3));
+        }
+    }
+
+    #[test]
+    fn triangulates_quads_and_shares_vertices() {
+        let model = parse(CUBE, Some(LAMP), NormalMode::Smooth).unwrap();
+
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.vertices.len(), 8);
+        assert_eq!(mesh.indices.len(), 6 * 2 * 3);
+    }
+
+    #[test]
+    fn generates_smooth_normals_pointing_outside() {
+        let model = parse(CUBE, None, NormalMode::Smooth).unwrap();
+
+        for v in &model.meshes[0].vertices {
+            let outside = (v.position - cgmath::Point3::new(0.5, 0.5, 0.5)).normalize();
+            assert!((v.normal.magnitude() - 1.).abs() < 1e-5);
+            assert!(v.normal.dot(outside) > 0.9, "{:?}", v);
+        }
+    }
+
+    #[test]
+    fn generates_flat_normals() {
+        let model = parse(CUBE, None, NormalMode::Flat).unwrap();
+
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.vertices.len(), 36);
+        let axes = [vec3(1., 0., 0.), vec3(0., 1., 0.), vec3(0., 0., 1.)];
+        assert!(mesh.vertices.iter().all(|v| axes.iter().any(|a| v.normal.dot(*a).abs() == 1.)));
+    }
+
+    #[test]
+    fn mesh_without_material_library_has_no_material() {
+        let model = parse(CUBE, None, NormalMode::Smooth).unwrap();
+
+        assert!(model.materials.is_empty());
+        assert_eq!(model.meshes[0].material, None);
+    }
+
+    #[test]
+    fn reads_materials_with_emission() {
+        let model = parse(CUBE, Some(LAMP), NormalMode::Smooth).unwrap();
+
+        assert_eq!(model.meshes[0].material, Some(0));
+        let lamp = &model.materials[0];
+        assert_eq!(lamp.name, "lamp");
+        assert_eq!(lamp.diffuse, Srgb::new(0.8, 0.7, 0.6).to_linear());
+        assert_eq!(lamp.emissive, Srgb::new(1.0, 0.5, 0.0).to_linear());
+        assert_eq!(lamp.reflectivity, 0.25);
+        assert_eq!(lamp.alpha, 0.75);
+        assert_eq!(lamp.diffuse_texture.as_deref(), Some("lamp.png"));
+        assert_eq!(lamp.normal_texture, None);
+        assert_eq!(lamp.emissive_texture.as_deref(), Some("glow.png"));
+    }
+
+    #[test]
+    fn takes_textures_from_files_dropped_with_the_model() {
+        let mut model = parse(CUBE, Some(LAMP), NormalMode::Smooth).unwrap();
+        let files: HashMap<String, Vec<u8>> = vec![
+            ("lamp.png".to_string(), vec![1]),
+            ("unused.png".to_string(), vec![2]),
+        ].into_iter().collect();
+
+        model.add_referenced_images(&files);
+
+        assert_eq!(model.images, vec![("lamp.png".to_string(), vec![1])], "glow.png wasn't dropped, it will be fetched");
+    }
+
+    #[test]
+    fn parses_material_library_alone() {
+        let materials = parse_materials(LAMP).unwrap();
+
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials[0].shininess, 10.);
+    }
+
+    #[test]
+    fn reads_transparency_as_the_opposite_of_opacity() {
+        let materials = parse_materials(b"newmtl glass\nKd 1 1 1\nTr 0.6\n").unwrap();
+
+        assert!((materials[0].alpha - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reports_broken_model() {
+        let result = parse(b"v 0 0 0\nv 1 0 0\nf 1 2 7\n", None, NormalMode::Smooth);
+
+        assert!(matches!(result, Err(LoadError::Obj(tobj::LoadError::FaceVertexOutOfBounds))));
+    }
+
+    #[test]
+    fn reports_model_without_triangles() {
+        let result = parse(b"v 0 0 0\nv 1 0 0\n", None, NormalMode::Smooth);
+
+        assert!(matches!(result, Err(LoadError::NoGeometry)));
+    }
+}
@@ -1,81 +1,304 @@
-use cgmath::{Vector3, Vector4};
+use std::fmt;
+
+use cgmath::Vector4;
 use wasm_bindgen::__rt::core::mem;
-use web_sys::{WebGl2RenderingContext as GL, WebGlBuffer};
+use web_sys::{WebGl2RenderingContext as GL, WebGlTexture};
+
+use crate::color::LinearRgb;
+use crate::gpu::{Buffer, Texture};
+use crate::shader::{MATERIALS_TEXTURE_UNIT, MATERIALS_UBO_BINDING_POINT};
+#[cfg(test)]
+use crate::shader::validation::UniformBlock;
+use crate::texture::TextureId;
 
-use crate::shader::MATERIALS_UBO_BINDING_POINT;
+/// Size of the `material` array in the `Materials` block, shaders get it as `MAX_MATERIALS`, the rest go to the overflow texture
+pub const MAX_MATERIALS: i32 = 100;
+/// The array in the `Materials` block starts after the number of materials kept in the UBO, padded to vec4
+const MATERIALS_OFFSET: i32 = 16;
+/// Materials that don't fit into the UBO are stored in a float texture, one row per material
+const OVERFLOW_INITIAL_ROWS: i32 = 64;
+/// `MAX_UNIFORM_BLOCK_SIZE` every WebGL2 implementation supports, the Materials block always fits into it
+#[cfg(test)]
+const MIN_UNIFORM_BLOCK_SIZE: i32 = 16384;
 
-const MAX_MATERIALS: i32 = 100;
+pub type MaterialId = u32;
+
+#[derive(Debug)]
+pub enum MaterialError {
+    TooManyMaterials { max: usize },
+    Mtl(tobj::LoadError),
+}
 
-pub type MaterialId = f32;
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialError::TooManyMaterials { max } => write!(f, "too many materials, at most {} are supported", max),
+            MaterialError::Mtl(e) => write!(f, "cannot parse materials: {}", e),
+        }
+    }
+}
+
+/// Optional texture maps, each one modulates the matching color of the material
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MaterialTextures {
+    pub diffuse: Option<TextureId>,
+    pub normal: Option<TextureId>,
+    pub emissive: Option<TextureId>,
+}
 
 #[derive(Debug, Copy, Clone)]
 pub struct Material {
-    pub ambient: Vector3<f32>,
-    pub diffuse: Vector3<f32>,
-    pub specular: Vector3<f32>,
+    pub ambient: LinearRgb,
+    pub diffuse: LinearRgb,
+    pub specular: LinearRgb,
     pub shininess: f32,
+    pub emissive: LinearRgb,
+    /// How much the surface mirrors the environment when looked at straight on, 0 for no reflections
+    pub reflectivity: f32,
+    /// Opacity, 1 for solid surfaces, translucent ones are blended over what's behind them
+    pub alpha: f32,
+    pub textures: MaterialTextures,
 }
 
 impl Material {
     fn size() -> i32 {
         let vector3_size = mem::size_of::<Vector4<f32>>() as i32; // there's no mistake, Vector3 takes the same amount of memory as Vector4
-        3 * vector3_size
+        5 * vector3_size
+    }
+
+    /// Material in std140 layout, the same data goes to the UBO and to the overflow texture (one vec4 per texel)
+    fn as_vec(&self) -> Vec<f32> {
+        // the shader cannot index samplers by material, it only needs to know which of the currently bound maps to use
+        let flag = |texture: Option<TextureId>| if texture.is_some() { 1. } else { 0. };
+        let [ambient, diffuse, specular, emissive] = [self.ambient.0, self.diffuse.0, self.specular.0, self.emissive.0];
+        vec![
+            ambient.x, ambient.y, ambient.z, 0.,
+            diffuse.x, diffuse.y, diffuse.z, self.alpha,
+            // small hack here, shininess is not passed as a separate value, but as specular.w, 4th value in vec4
+            specular.x, specular.y, specular.z, self.shininess,
+            emissive.x, emissive.y, emissive.z, 0.,
+            // reflectivity rides along with the map flags, the same way shininess does with specular
+            flag(self.textures.diffuse), flag(self.textures.normal), flag(self.textures.emissive), self.reflectivity,
+        ]
     }
 }
 
+/// Where the material is stored on the GPU
+#[derive(Debug, PartialEq)]
+enum MaterialSlot {
+    Ubo(i32),
+    Texture(i32),
+}
+
+impl MaterialSlot {
+    fn of(material_id: MaterialId, ubo_capacity: i32) -> Self {
+        let index = material_id as i32;
+        if index < ubo_capacity {
+            MaterialSlot::Ubo(index)
+        } else {
+            MaterialSlot::Texture(index - ubo_capacity)
+        }
+    }
+}
+
+/// Number of overflow texture rows needed to store `needed` materials, doubling the current size.
+/// `None` if that would exceed the maximum texture size.
+fn grown_rows(current: i32, needed: i32, max: i32) -> Option<i32> {
+    if needed <= current {
+        return Some(current);
+    }
+    let mut rows = current.max(OVERFLOW_INITIAL_ROWS);
+    while rows < needed {
+        rows *= 2;
+    }
+    if needed > max {
+        None
+    } else {
+        Some(rows.min(max))
+    }
+}
+
+struct OverflowTexture {
+    texture: Texture,
+    rows: i32,
+}
+
 pub struct Materials {
-    ubo: WebGlBuffer,
+    ubo: Buffer,
+    overflow: Option<OverflowTexture>,
+    max_texture_rows: i32,
     materials: Vec<Material>,
 }
 
 impl Materials {
     pub fn setup(gl: &GL) -> Self {
-        Materials { ubo: Materials::setup_lights_ubo(gl), materials: vec![] }
+        let max_texture_rows = gl.get_parameter(GL::MAX_TEXTURE_SIZE).unwrap().as_f64().unwrap() as i32;
+        let ubo = Materials::setup_materials_ubo(gl);
+        Materials { ubo, overflow: None, max_texture_rows, materials: vec![] }
     }
 
-    fn setup_lights_ubo(gl: &GL) -> WebGlBuffer {
-        let materials_ubo = gl.create_buffer().unwrap();
-        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&materials_ubo));
-        gl.buffer_data_with_i32(GL::UNIFORM_BUFFER, MAX_MATERIALS * Material::size(), GL::STATIC_DRAW);
-        gl.bind_buffer_base(GL::UNIFORM_BUFFER, MATERIALS_UBO_BINDING_POINT, Some(&materials_ubo));
+    fn setup_materials_ubo(gl: &GL) -> Buffer {
+        let materials_ubo = Buffer::new(gl);
+        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&*materials_ubo));
+        gl.buffer_data_with_i32(GL::UNIFORM_BUFFER, MATERIALS_OFFSET + MAX_MATERIALS * Material::size(), GL::STATIC_DRAW);
+        unsafe {
+            let array: [u32; 1] = [MAX_MATERIALS as u32];
+            let js_array = js_sys::Uint32Array::view(&array);
+            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, 0, &js_array);
+        }
+        gl.bind_buffer_base(GL::UNIFORM_BUFFER, MATERIALS_UBO_BINDING_POINT, Some(&*materials_ubo));
         gl.bind_buffer(GL::UNIFORM_BUFFER, None);
         materials_ubo
     }
 
-    pub fn add(&mut self, gl: &GL, material: Material) -> MaterialId {
+    pub fn add(&mut self, gl: &GL, material: Material) -> Result<MaterialId, MaterialError> {
+        let material_id = self.materials.len() as MaterialId;
+        if let MaterialSlot::Texture(row) = MaterialSlot::of(material_id, MAX_MATERIALS) {
+            self.ensure_overflow_rows(gl, row + 1)?;
+        }
         self.materials.push(material);
-        let material_id = self.materials.len() as usize - 1;
-        let vector3_size = mem::size_of::<Vector4<f32>>() as i32;
+        self.write(gl, material_id);
+        Ok(material_id)
+    }
 
-        gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&self.ubo));
+    pub fn get(&self, material_id: MaterialId) -> Option<&Material> {
+        self.materials.get(material_id as usize)
+    }
 
-        unsafe {
-            let array: [f32; 3] = self.materials[material_id].ambient.into();
-            let js_array = js_sys::Float32Array::view(&array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, material_id as i32 * Material::size() + 0 * vector3_size, &js_array);
+    /// Replaces the material and rewrites its slot on the GPU, all instances using it change immediately
+    pub fn update(&mut self, gl: &GL, material_id: MaterialId, material: Material) {
+        self.materials[material_id as usize] = material;
+        self.write(gl, material_id);
+    }
+
+    /// Binds the overflow texture, has to be called before drawing in case other code used its texture unit
+    pub fn bind(&self, gl: &GL) {
+        gl.active_texture(GL::TEXTURE0 + MATERIALS_TEXTURE_UNIT);
+        gl.bind_texture(GL::TEXTURE_2D, self.overflow.as_ref().map(|o| &*o.texture));
+    }
+
+    fn write(&self, gl: &GL, material_id: MaterialId) {
+        let data = self.materials[material_id as usize].as_vec();
+        match MaterialSlot::of(material_id, MAX_MATERIALS) {
+            MaterialSlot::Ubo(index) => {
+                gl.bind_buffer(GL::UNIFORM_BUFFER, Some(&*self.ubo));
+                unsafe {
+                    let js_array = js_sys::Float32Array::view(&data);
+                    gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, MATERIALS_OFFSET + index * Material::size(), &js_array);
+                }
+                gl.bind_buffer(GL::UNIFORM_BUFFER, None);
+            }
+            MaterialSlot::Texture(row) => {
+                let overflow = self.overflow.as_ref().expect("Overflow texture should be allocated before writing to it");
+                Self::write_overflow_row(gl, &overflow.texture, row, &data);
+            }
         }
+    }
 
-        unsafe {
-            let array: [f32; 3] = self.materials[material_id].diffuse.into();
-            let js_array = js_sys::Float32Array::view(&array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, material_id as i32 * Material::size() + 1 * vector3_size, &js_array);
+    fn ensure_overflow_rows(&mut self, gl: &GL, needed: i32) -> Result<(), MaterialError> {
+        let current = self.overflow.as_ref().map_or(0, |o| o.rows);
+        let rows = grown_rows(current, needed, self.max_texture_rows)
+            .ok_or(MaterialError::TooManyMaterials { max: (MAX_MATERIALS + self.max_texture_rows) as usize })?;
+        if rows == current {
+            return Ok(());
         }
 
-        unsafe {
-            let array: [f32; 3] = self.materials[material_id].specular.into();
-            let js_array = js_sys::Float32Array::view(&array);
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, material_id as i32 * Material::size() + 2 * vector3_size, &js_array);
+        let texture = Texture::new(gl);
+        gl.active_texture(GL::TEXTURE0 + MATERIALS_TEXTURE_UNIT);
+        gl.bind_texture(GL::TEXTURE_2D, Some(&*texture));
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::NEAREST as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::NEAREST as i32);
+        let width = Material::size() / 16;
+        gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+            GL::TEXTURE_2D, 0, GL::RGBA32F as i32, width, rows, 0, GL::RGBA, GL::FLOAT, None)
+            .expect("Cannot allocate materials texture");
+        // a bigger texture starts empty, everything stored so far has to be copied over
+        for material_id in MAX_MATERIALS..self.materials.len() as i32 {
+            let data = self.materials[material_id as usize].as_vec();
+            Self::write_overflow_row(gl, &texture, material_id - MAX_MATERIALS, &data);
         }
+        self.overflow = Some(OverflowTexture { texture, rows }); // the old texture gets deleted here
+        Ok(())
+    }
 
+    fn write_overflow_row(gl: &GL, texture: &WebGlTexture, row: i32, data: &[f32]) {
+        gl.active_texture(GL::TEXTURE0 + MATERIALS_TEXTURE_UNIT);
+        gl.bind_texture(GL::TEXTURE_2D, Some(texture));
         unsafe {
-            let array: [f32; 1] = [self.materials[material_id].shininess];
-            let js_array = js_sys::Float32Array::view(&array);
-            // small hack here, shininess is not passed as a separate value, but as specular.w, 4th value in vec4
-            gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::UNIFORM_BUFFER, material_id as i32 * Material::size() + 2 * vector3_size + 12, &js_array);
+            let js_array = js_sys::Float32Array::view(data);
+            gl.tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_array_buffer_view(
+                GL::TEXTURE_2D, 0, 0, row, data.len() as i32 / 4, 1, GL::RGBA, GL::FLOAT, Some(&js_array))
+                .expect("Cannot write material to the texture");
         }
+    }
+}
 
-        gl.bind_buffer(GL::UNIFORM_BUFFER, None);
+/// Layout the Materials block is written with, shaders are checked against it in tests
+#[cfg(test)]
+pub fn ubo_layout() -> UniformBlock {
+    let mut block = UniformBlock::new("Materials", MATERIALS_UBO_BINDING_POINT, MATERIALS_OFFSET + MAX_MATERIALS * Material::size())
+        .member("materialsInUbo", 0);
+    for material in 0..MAX_MATERIALS {
+        // one vec4 per member, in the order of `Material::as_vec`
+        for (i, name) in ["ambient", "diffuse", "specular", "emissive", "maps"].iter().enumerate() {
+            block = block.member(&format!("material[{}].{}", material, name), MATERIALS_OFFSET + material * Material::size() + i as i32 * 16);
+        }
+    }
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::LinearRgb;
+    use crate::material::{grown_rows, ubo_layout, Material, MaterialSlot, MaterialTextures, MIN_UNIFORM_BLOCK_SIZE};
+
+    #[test]
+    fn materials_go_to_texture_once_ubo_is_full() {
+        assert_eq!(MaterialSlot::of(0, 100), MaterialSlot::Ubo(0));
+        assert_eq!(MaterialSlot::of(99, 100), MaterialSlot::Ubo(99));
+        assert_eq!(MaterialSlot::of(100, 100), MaterialSlot::Texture(0));
+        assert_eq!(MaterialSlot::of(150, 100), MaterialSlot::Texture(50));
+    }
+
+    #[test]
+    fn overflow_texture_grows_by_doubling() {
+        assert_eq!(grown_rows(0, 1, 4096), Some(64));
+        assert_eq!(grown_rows(64, 64, 4096), Some(64));
+        assert_eq!(grown_rows(64, 65, 4096), Some(128));
+        assert_eq!(grown_rows(64, 300, 4096), Some(512));
+    }
+
+    #[test]
+    fn overflow_texture_is_limited_by_max_texture_size() {
+        assert_eq!(grown_rows(2048, 3000, 4096), Some(4096));
+        assert_eq!(grown_rows(2048, 4096, 3000), None);
+    }
+
+    #[test]
+    fn material_layout_matches_std140() {
+        let material = Material {
+            ambient: LinearRgb::new(1., 2., 3.),
+            diffuse: LinearRgb::new(4., 5., 6.),
+            specular: LinearRgb::new(7., 8., 9.),
+            shininess: 10.,
+            emissive: LinearRgb::new(11., 12., 13.),
+            reflectivity: 0.5,
+            alpha: 0.25,
+            textures: MaterialTextures { diffuse: Some(0), normal: None, emissive: Some(1) },
+        };
+
+        let data = material.as_vec();
+
+        assert_eq!(data.len() * 4, Material::size() as usize);
+        assert_eq!(&data[0..3], &[1., 2., 3.]);
+        assert_eq!(&data[4..8], &[4., 5., 6., 0.25]);
+        assert_eq!(&data[8..12], &[7., 8., 9., 10.]);
+        assert_eq!(&data[12..15], &[11., 12., 13.]);
+        assert_eq!(&data[16..20], &[1., 0., 1., 0.5]);
+    }
 
-        material_id as MaterialId
+    #[test]
+    fn materials_block_fits_on_every_device() {
+        assert!(ubo_layout().size <= MIN_UNIFORM_BLOCK_SIZE as u32);
     }
 }
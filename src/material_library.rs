@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::color::ColorSpace;
+use crate::loader::MaterialData;
+use crate::loader::obj;
+use crate::material::{Material, MaterialError, MaterialId, Materials, MaterialTextures};
+use crate::texture::Textures;
+
+/// Materials used by the procedural models, in the same format as materials exported together with OBJ models
+static DEFAULT_MATERIALS: &str = include_str!("../models/materials.mtl");
+
+/// All materials of the scene, addressable both by id and by name
+pub struct MaterialLibrary {
+    materials: Materials,
+    by_name: HashMap<String, MaterialId>,
+}
+
+impl MaterialLibrary {
+    pub fn setup(gl: &GL, textures: &mut Textures) -> Self {
+        let mut library = MaterialLibrary { materials: Materials::setup(gl), by_name: HashMap::new() };
+        library.load(gl, DEFAULT_MATERIALS, textures).expect("Cannot load default materials");
+        library
+    }
+
+    /// Adds all materials from MTL source, returns their ids in the order they are defined
+    pub fn load(&mut self, gl: &GL, mtl: &str, textures: &mut Textures) -> Result<Vec<MaterialId>, MaterialError> {
+        let mtl_materials = obj::parse_materials(mtl.as_bytes()).map_err(MaterialError::Mtl)?;
+        mtl_materials.iter()
+            .map(|m| self.add_data(gl, m, textures))
+            .collect()
+    }
+
+    /// Adds a material read from a model file, loading its textures
+    pub fn add_data(&mut self, gl: &GL, material: &MaterialData, textures: &mut Textures) -> Result<MaterialId, MaterialError> {
+        let mut load = |name: &Option<String>, space| name.as_ref().map(|name| textures.load(gl, name, space));
+        let material_textures = MaterialTextures {
+            diffuse: load(&material.diffuse_texture, ColorSpace::Srgb),
+            normal: load(&material.normal_texture, ColorSpace::Linear),
+            emissive: load(&material.emissive_texture, ColorSpace::Srgb),
+        };
+        let my_material = Material {
+            ambient: material.ambient,
+            diffuse: material.diffuse,
+            specular: material.specular,
+            shininess: material.shininess,
+            emissive: material.emissive,
+            reflectivity: material.reflectivity,
+            alpha: material.alpha,
+            textures: material_textures,
+        };
+        self.add(gl, &material.name, my_material)
+    }
+
+    /// Adds a material, a material with the same name already in the library stays available only by id
+    pub fn add(&mut self, gl: &GL, name: &str, material: Material) -> Result<MaterialId, MaterialError> {
+        let material_id = self.materials.add(gl, material)?;
+        self.by_name.insert(name.to_string(), material_id);
+        Ok(material_id)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<MaterialId> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Name the material is available by, `None` if another material took over its name
+    pub fn name(&self, material_id: MaterialId) -> Option<&str> {
+        self.by_name.iter().find(|(_, &id)| id == material_id).map(|(name, _)| name.as_str())
+    }
+
+    pub fn get(&self, material_id: MaterialId) -> Option<&Material> {
+        self.materials.get(material_id)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.by_name.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn bind(&self, gl: &GL) {
+        self.materials.bind(gl);
+    }
+
+    pub fn update(&mut self, gl: &GL, material_id: MaterialId, material: Material) {
+        self.materials.update(gl, material_id, material);
+    }
+}
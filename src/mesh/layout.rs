@@ -0,0 +1,197 @@
+use web_sys::WebGl2RenderingContext as GL;
+
+/// Every component, float or integer, takes 4 bytes
+const COMPONENT_SIZE: i32 = 4;
+/// Matrices are passed column by column, each column takes its own location
+const MATRIX_COLUMNS: i32 = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AttributeType {
+    Float,
+    UnsignedInt,
+}
+
+/// Single vertex attribute as declared with `layout (location = ...) in ...` in the vertex shader
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: &'static str,
+    pub location: u32,
+    /// 1 to 4 for scalars and vectors, 16 for `mat4`
+    pub components: i32,
+    pub kind: AttributeType,
+    /// 0 advances with every vertex, 1 with every instance
+    pub divisor: u32,
+}
+
+impl Attribute {
+    pub const fn per_vertex(name: &'static str, location: u32, components: i32, kind: AttributeType) -> Self {
+        Attribute { name, location, components, kind, divisor: 0 }
+    }
+
+    pub const fn per_instance(name: &'static str, location: u32, components: i32, kind: AttributeType) -> Self {
+        Attribute { name, location, components, kind, divisor: 1 }
+    }
+
+    fn is_matrix(&self) -> bool {
+        self.components == MATRIX_COLUMNS * MATRIX_COLUMNS
+    }
+
+    /// Type reported by `getActiveAttrib` for this attribute
+    pub fn gl_type(&self) -> u32 {
+        match (self.kind, self.components) {
+            (AttributeType::Float, 1) => GL::FLOAT,
+            (AttributeType::Float, 2) => GL::FLOAT_VEC2,
+            (AttributeType::Float, 3) => GL::FLOAT_VEC3,
+            (AttributeType::Float, 4) => GL::FLOAT_VEC4,
+            (AttributeType::Float, 16) => GL::FLOAT_MAT4,
+            (AttributeType::UnsignedInt, 1) => GL::UNSIGNED_INT,
+            (AttributeType::UnsignedInt, 2) => GL::UNSIGNED_INT_VEC2,
+            (AttributeType::UnsignedInt, 3) => GL::UNSIGNED_INT_VEC3,
+            (AttributeType::UnsignedInt, 4) => GL::UNSIGNED_INT_VEC4,
+            (kind, components) => panic!("Unsupported attribute {}: {:?} with {} components", self.name, kind, components),
+        }
+    }
+}
+
+/// Attribute as reported by the linked program
+#[derive(Debug)]
+pub struct ActiveAttribute {
+    pub name: String,
+    pub location: i32,
+    pub gl_type: u32,
+}
+
+/// Description of interleaved attributes in one buffer, shared by meshes filling the buffers and shaders reading them
+#[derive(Debug, Clone)]
+pub struct VertexLayout {
+    attributes: Vec<(Attribute, i32)>,
+    stride: i32,
+}
+
+impl VertexLayout {
+    /// Attributes are laid out one after another in the given order
+    pub fn new(attributes: &[Attribute]) -> Self {
+        let mut offset = 0;
+        let attributes = attributes.iter()
+            .map(|&a| {
+                let attribute = (a, offset);
+                offset += a.components * COMPONENT_SIZE;
+                attribute
+            })
+            .collect();
+        VertexLayout { attributes, stride: offset }
+    }
+
+    pub fn stride(&self) -> i32 {
+        self.stride
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().map(|(a, _)| a).find(|a| a.name == name)
+    }
+
+    /// Points attributes to the buffer currently bound to `ARRAY_BUFFER`, has to be called with the VAO bound
+    pub fn apply(&self, gl: &GL) {
+        for (attribute, offset) in &self.attributes {
+            // size can be at most 4, so matrices need a separate call for every column
+            let (columns, components) = if attribute.is_matrix() { (MATRIX_COLUMNS, MATRIX_COLUMNS) } else { (1, attribute.components) };
+            for column in 0..columns {
+                let location = attribute.location + column as u32;
+                let column_offset = offset + column * components * COMPONENT_SIZE;
+                match attribute.kind {
+                    AttributeType::Float => gl.vertex_attrib_pointer_with_i32(location, components, GL::FLOAT, false, self.stride, column_offset),
+                    AttributeType::UnsignedInt => gl.vertex_attrib_i_pointer_with_i32(location, components, GL::UNSIGNED_INT, self.stride, column_offset),
+                }
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_divisor(location, attribute.divisor);
+            }
+        }
+    }
+
+    /// Checks that every attribute the program uses is provided by one of the layouts, at the same location and with the same type
+    pub fn validate(layouts: &[&VertexLayout], active: &[ActiveAttribute]) -> Result<(), String> {
+        for active_attribute in active {
+            if active_attribute.name.starts_with("gl_") {
+                continue;   // built-ins like gl_VertexID don't come from buffers
+            }
+            let attribute = layouts.iter()
+                .find_map(|l| l.find(&active_attribute.name))
+                .ok_or_else(|| format!("attribute '{}' is not provided by any vertex layout", active_attribute.name))?;
+            if attribute.location as i32 != active_attribute.location {
+                return Err(format!("attribute '{}' is at location {} in the shader, but at {} in the vertex layout",
+                                   attribute.name, active_attribute.location, attribute.location));
+            }
+            if attribute.gl_type() != active_attribute.gl_type {
+                return Err(format!("attribute '{}' has type 0x{:x} in the shader, but 0x{:x} in the vertex layout",
+                                   attribute.name, active_attribute.gl_type, attribute.gl_type()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Anything that can be stored in a vertex buffer described by a layout
+pub trait VertexFormat {
+    fn layout() -> VertexLayout;
+
+    fn as_vec(&self) -> Vec<f32>;
+}
+
+#[cfg(test)]
+mod tests {
+    use web_sys::WebGl2RenderingContext as GL;
+
+    use crate::mesh::layout::{ActiveAttribute, Attribute, AttributeType, VertexLayout};
+
+    fn layout() -> VertexLayout {
+        VertexLayout::new(&[
+            Attribute::per_vertex("aPos", 0, 3, AttributeType::Float),
+            Attribute::per_vertex("aTexCoords", 1, 2, AttributeType::Float),
+            Attribute::per_instance("instanceModel", 2, 16, AttributeType::Float),
+            Attribute::per_instance("instanceId", 6, 1, AttributeType::UnsignedInt),
+        ])
+    }
+
+    fn active(name: &str, location: i32, gl_type: u32) -> ActiveAttribute {
+        ActiveAttribute { name: name.to_string(), location, gl_type }
+    }
+
+    #[test]
+    fn stride_covers_all_attributes() {
+        assert_eq!(layout().stride(), (3 + 2 + 16 + 1) * 4);
+    }
+
+    #[test]
+    fn accepts_matching_program() {
+        let layout = layout();
+        let active = vec![
+            active("aPos", 0, GL::FLOAT_VEC3),
+            active("instanceModel", 2, GL::FLOAT_MAT4),
+            active("instanceId", 6, GL::UNSIGNED_INT),
+            active("gl_VertexID", -1, GL::INT),
+        ];
+
+        assert_eq!(VertexLayout::validate(&[&layout], &active), Ok(()));
+    }
+
+    #[test]
+    fn rejects_unknown_attribute() {
+        let result = VertexLayout::validate(&[&layout()], &[active("aColor", 3, GL::FLOAT_VEC4)]);
+
+        assert!(result.unwrap_err().contains("'aColor' is not provided"));
+    }
+
+    #[test]
+    fn rejects_wrong_location() {
+        let result = VertexLayout::validate(&[&layout()], &[active("aTexCoords", 2, GL::FLOAT_VEC2)]);
+
+        assert!(result.unwrap_err().contains("at location 2 in the shader, but at 1"));
+    }
+
+    #[test]
+    fn rejects_wrong_type() {
+        let result = VertexLayout::validate(&[&layout()], &[active("instanceId", 6, GL::FLOAT)]);
+
+        assert!(result.unwrap_err().contains("'instanceId' has type"));
+    }
+}
@@ -0,0 +1,248 @@
+use std::cell::{Cell, RefCell};
+
+use cgmath::{MetricSpace, Point3, Vector2, Vector3};
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::camera::View;
+use crate::culling::{Aabb, BoundingSphere};
+use crate::gpu::{Buffer, VertexArray};
+use crate::mesh::layout::{Attribute, AttributeType, VertexFormat, VertexLayout};
+use crate::model::Instance;
+use crate::render::Pipeline;
+use crate::render::queue::{back_to_front, DrawCall, RenderQueue};
+use crate::texture::TextureSet;
+
+pub mod layout;
+pub mod primitives;
+
+#[repr(C)]  // to make sure memory representation is like in the code
+#[derive(Debug, Copy, Clone)]
+pub struct Vertex {
+    pub position: Point3<f32>,
+    pub normal: Vector3<f32>,
+    pub uv: Vector2<f32>,
+}
+
+impl VertexFormat for Vertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::new(&[
+            Attribute::per_vertex("aPos", 0, 3, AttributeType::Float),
+            Attribute::per_vertex("aNormal", 1, 3, AttributeType::Float),
+            Attribute::per_vertex("aTexCoords", 2, 2, AttributeType::Float),
+        ])
+    }
+
+    fn as_vec(&self) -> Vec<f32> {
+        let pos: [f32; 3] = self.position.into();
+        let norm: [f32; 3] = self.normal.into();
+        let uv: [f32; 2] = self.uv.into();
+        [&pos[..], &norm[..], &uv[..]].concat()
+    }
+}
+
+/// One version of the geometry, used for instances not further from the camera than `max_distance`
+struct Lod {
+    indices: Vec<u32>,
+    max_distance: f32,
+    vao: VertexArray,
+    #[allow(dead_code)] // only kept so that buffers used by the VAO live as long as it
+    vbo: Buffer,
+    #[allow(dead_code)]
+    ebo: Buffer,
+    /// Every level has its own instances, so each one needs a separate VBO
+    instances_vbo: Buffer,
+    /// How many instances fit into the VBO, it only gets reallocated when more become visible
+    capacity: Cell<usize>,
+    /// Which instances are in the VBO right now, `None` if instances changed since the last upload
+    uploaded: RefCell<Option<Vec<usize>>>,
+}
+
+/// Index of the first level reaching `distance`, the last level takes everything further away
+fn lod_for_distance(max_distances: &[f32], distance: f32) -> usize {
+    max_distances.iter()
+        .position(|&max_distance| distance <= max_distance)
+        .unwrap_or(max_distances.len() - 1)
+}
+
+pub struct Mesh {
+    /// Ordered from the most detailed one
+    lods: Vec<Lod>,
+    textures: TextureSet,
+    pipeline: Pipeline,
+    /// In model space, every instance moves it with its own model matrix
+    bounds: BoundingSphere,
+    /// Geometry of the most detailed level, kept for exporting
+    vertices: Vec<Vertex>,
+    instances: Vec<Instance>,
+}
+
+impl Mesh {
+    pub fn new(gl: &GL, vertices: Vec<Vertex>, indices: Vec<u32>, max_instances: usize) -> Self {
+        Self::with_lods(gl, vec![(vertices, indices, f32::INFINITY)], max_instances)
+    }
+
+    /// Mesh with levels of detail given as vertices, indices and maximum distance from the camera, starting with the most detailed one
+    pub fn with_lods(gl: &GL, levels: Vec<(Vec<Vertex>, Vec<u32>, f32)>, max_instances: usize) -> Self {
+        let (vertices, _, _) = levels.first().expect("Mesh needs at least one level of detail");
+        let bounds = Aabb::from_points(vertices.iter().map(|v| &v.position)).bounding_sphere();
+        let vertices = vertices.clone();
+        let lods = levels.into_iter()
+            .map(|(vertices, indices, max_distance)| {
+                let instances_vbo = Self::create_instances_vbo(gl, max_instances);
+                let (vao, vbo, ebo) = Self::create_vao(gl, &vertices, &indices, &instances_vbo);
+                Lod { indices, max_distance, vao, vbo, ebo, instances_vbo, capacity: Cell::new(max_instances), uploaded: RefCell::new(None) }
+            })
+            .collect();
+        Self { lods, textures: TextureSet::default(), pipeline: Pipeline::Lit, bounds, vertices, instances: vec![] }
+    }
+
+    /// Vertices and indices of the most detailed level
+    pub fn geometry(&self) -> (&[Vertex], &[u32]) {
+        (&self.vertices, &self.lods[0].indices)
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Textures used by all instances, a single draw call can bind only one set
+    pub fn set_textures(&mut self, textures: TextureSet) {
+        self.textures = textures;
+    }
+
+    /// How all instances are drawn, `Pipeline::Lit` unless set otherwise
+    pub fn set_pipeline(&mut self, pipeline: Pipeline) {
+        self.pipeline = pipeline;
+    }
+
+    /// VAO is returned together with buffers it reads from, they have to live as long as the VAO
+    fn create_vao(gl: &GL, vertices: &[Vertex], indices: &[u32], instances_vbo: &Buffer) -> (VertexArray, Buffer, Buffer) {
+        let vao = VertexArray::new(gl); // create VAO
+        gl.bind_vertex_array(Some(&*vao)); // ...and bind it
+
+        let vbo = Self::create_vbo(gl, vertices);
+        let ebo = Self::create_ebo(gl, indices);
+
+        // this call also connects my VBO to the vertex attributes
+        Vertex::layout().apply(gl);
+
+        // enter instancing, using completely different VBO
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(instances_vbo));
+        Instance::layout().apply(gl);
+
+        gl.bind_buffer(GL::ARRAY_BUFFER, None); // unbind instances VBO
+        // do NOT unbind EBO, VAO would remember that
+        gl.bind_vertex_array(None); // unbind my VAO
+        (vao, vbo, ebo)
+    }
+
+    fn create_vbo(gl: &GL, vertices: &[Vertex]) -> Buffer {
+        let vbo = Buffer::new(gl); // create buffer for my data
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&*vbo)); // ARRAY_BUFFER now "points" to my buffer
+        unsafe {
+            let vec: Vec<f32> = vertices.iter().flat_map(|v| {let vec: Vec<f32> = v.as_vec(); vec}).collect();
+            let js_array = js_sys::Float32Array::view(&vec);
+            gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_array, GL::STATIC_DRAW); // actually fill ARRAY_BUFFER (my buffer) with data
+        }
+        vbo
+    }
+
+    fn create_ebo(gl: &GL, indices: &[u32]) -> Buffer {
+        let ebo = Buffer::new(gl); // create buffer for indices (elements)
+        gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&*ebo)); // ELEMENT_ARRAY_BUFFER now "points" to my buffer
+        unsafe {
+            let js_array = js_sys::Uint32Array::view(indices);
+            gl.buffer_data_with_array_buffer_view(GL::ELEMENT_ARRAY_BUFFER, &js_array, GL::STATIC_DRAW); // actually fill ELEMENT_ARRAY_BUFFER with data
+        }
+        ebo
+    }
+
+    /// Instances are kept on the CPU side, only the visible ones get uploaded when drawing
+    pub fn set_instances(&mut self, instances: Vec<Instance>) {
+        self.instances = instances;
+        for lod in &self.lods {
+            lod.uploaded.replace(None);
+        }
+    }
+
+    fn upload_instances(&self, gl: &GL, lod: &Lod, visible: Vec<usize>) {
+        if lod.uploaded.borrow().as_ref() == Some(&visible) {
+            return; // nothing moved and the camera sees the same instances as before
+        }
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&*lod.instances_vbo)); // ARRAY_BUFFER now "points" to my buffer
+
+        unsafe {
+            let vec: Vec<f32> = visible.iter().flat_map(|&i| self.instances[i].as_vec()).collect();
+            let js_array = js_sys::Float32Array::view(&vec);
+            if visible.len() > lod.capacity.get() {
+                gl.buffer_data_with_array_buffer_view(GL::ARRAY_BUFFER, &js_array, GL::DYNAMIC_DRAW);
+                lod.capacity.set(visible.len());
+            } else {
+                gl.buffer_sub_data_with_i32_and_array_buffer_view(GL::ARRAY_BUFFER, 0, &js_array); // reuses the storage allocated up front
+            }
+        }
+        lod.uploaded.replace(Some(visible));
+    }
+
+    fn create_instances_vbo(gl: &GL, max_instances: usize) -> Buffer {
+        let instances_vbo = Buffer::new(gl); // create buffer for my data
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&*instances_vbo)); // ARRAY_BUFFER now "points" to my buffer
+        gl.buffer_data_with_i32(GL::ARRAY_BUFFER, max_instances as i32 * Instance::layout().stride(), GL::DYNAMIC_DRAW);
+        instances_vbo
+    }
+
+    /// Uploads all instances at least partially inside the view frustum and queues one draw call per level of detail in use
+    pub fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        let max_distances: Vec<f32> = self.lods.iter().map(|lod| lod.max_distance).collect();
+        let mut visible_per_lod: Vec<Vec<(usize, f32)>> = vec![vec![]; self.lods.len()];
+        for (i, instance) in self.instances.iter().enumerate() {
+            let bounds = self.bounds.transform(&instance.model);
+            if view.frustum.intersects_sphere(&bounds) {
+                let distance = bounds.center.distance(view.position);
+                visible_per_lod[lod_for_distance(&max_distances, distance)].push((i, distance));
+            }
+        }
+
+        for (lod, mut visible) in self.lods.iter().zip(visible_per_lod) {
+            if visible.is_empty() {
+                continue;
+            }
+            if self.pipeline.is_translucent() {
+                // instances are drawn in the order they are uploaded
+                back_to_front(&mut visible, |&(_, distance)| distance);
+            }
+            let count = visible.len() as i32;
+            let distance = visible.iter().map(|&(_, distance)| distance).fold(0., f32::max);
+            self.upload_instances(gl, lod, visible.into_iter().map(|(i, _)| i).collect());
+            queue.push(DrawCall {
+                pipeline: self.pipeline,
+                vao: &lod.vao,
+                textures: &self.textures,
+                index_count: lod.indices.len() as i32,
+                instance_count: count,
+                distance,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mesh::lod_for_distance;
+
+    #[test]
+    fn picks_first_level_reaching_the_distance() {
+        let max_distances = [10., 20., f32::INFINITY];
+
+        assert_eq!(lod_for_distance(&max_distances, 0.), 0);
+        assert_eq!(lod_for_distance(&max_distances, 10.), 0);
+        assert_eq!(lod_for_distance(&max_distances, 10.5), 1);
+        assert_eq!(lod_for_distance(&max_distances, 1000.), 2);
+    }
+
+    #[test]
+    fn last_level_takes_everything_beyond_thresholds() {
+        assert_eq!(lod_for_distance(&[5., 15.], 16.), 1);
+        assert_eq!(lod_for_distance(&[f32::INFINITY], 16.), 0);
+    }
+}
@@ -0,0 +1,369 @@
+//! Procedurally generated meshes, all centered at (0, 0, 0), with outward facing normals and counter-clockwise front faces.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Point3, vec2, vec3, Vector2, Vector3};
+
+use crate::mesh::Vertex;
+
+fn vertex(position: Vector3<f32>, normal: Vector3<f32>, uv: Vector2<f32>) -> Vertex {
+    Vertex { position: Point3::new(position.x, position.y, position.z), normal, uv }
+}
+
+/// Direction in the OXZ plane, angle measured from the positive part of Z axis towards X axis, as `SphericalPoint3::phi`
+fn horizontal(angle: f32) -> Vector3<f32> {
+    vec3(angle.sin(), 0., angle.cos())
+}
+
+/// Indices for a grid of `rows` x `columns` quads, vertices are stored row by row, top to bottom, with `columns + 1` vertices per row.
+/// Triangles touching the first or last row are skipped on the side where the row collapses into a single point.
+fn grid_indices(rows: u32, columns: u32, first_row_collapsed: bool, last_row_collapsed: bool) -> Vec<u32> {
+    let mut indices = vec![];
+    for row in 0..rows {
+        for column in 0..columns {
+            let top = row * (columns + 1) + column;
+            let bottom = top + columns + 1;
+            if !(row == 0 && first_row_collapsed) {
+                indices.extend([top, bottom + 1, top + 1].iter());
+            }
+            if !(row == rows - 1 && last_row_collapsed) {
+                indices.extend([top, bottom, bottom + 1].iter());
+            }
+        }
+    }
+    indices
+}
+
+/// Sphere made of `stacks` horizontal layers and `sectors` vertical slices
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(((stacks + 1) * (sectors + 1)) as usize);
+    for stack in 0..=stacks {
+        let v_angle = PI * stack as f32 / stacks as f32;   // vertically I'm doing only half rotation
+        for sector in 0..=sectors {
+            let h_angle = 2. * PI * sector as f32 / sectors as f32;   // horizontally I'm doing full circle
+            let normal = horizontal(h_angle) * v_angle.sin() + vec3(0., v_angle.cos(), 0.);
+            let uv = vec2(sector as f32 / sectors as f32, 1. - stack as f32 / stacks as f32);
+            vertices.push(vertex(normal * radius, normal, uv));
+        }
+    }
+    (vertices, grid_indices(stacks, sectors, true, true))
+}
+
+/// Sphere made of equally sized triangles, starting from an icosahedron and splitting every triangle into 4 `subdivisions` times
+#[allow(dead_code)]
+pub fn icosphere(radius: f32, subdivisions: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let t = (1. + 5_f32.sqrt()) / 2.;
+    let mut points: Vec<Vector3<f32>> = vec![
+        vec3(-1., t, 0.), vec3(1., t, 0.), vec3(-1., -t, 0.), vec3(1., -t, 0.),
+        vec3(0., -1., t), vec3(0., 1., t), vec3(0., -1., -t), vec3(0., 1., -t),
+        vec3(t, 0., -1.), vec3(t, 0., 1.), vec3(-t, 0., -1.), vec3(-t, 0., 1.),
+    ].into_iter().map(|p| p.normalize()).collect();
+    let mut indices: Vec<u32> = vec![
+        0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11,
+        1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7, 1, 8,
+        3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9,
+        4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9, 8, 1,
+    ];
+
+    for _ in 0..subdivisions {
+        // neighbouring triangles share edges, so they have to share midpoints as well
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, points: &mut Vec<Vector3<f32>>| {
+            let key = (a.min(b), a.max(b));
+            *midpoints.entry(key).or_insert_with(|| {
+                points.push(((points[a as usize] + points[b as usize]) / 2.).normalize());
+                points.len() as u32 - 1
+            })
+        };
+        let mut subdivided = Vec::with_capacity(indices.len() * 4);
+        for triangle in indices.chunks(3) {
+            let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+            let ab = midpoint(a, b, &mut points);
+            let bc = midpoint(b, c, &mut points);
+            let ca = midpoint(c, a, &mut points);
+            subdivided.extend([a, ab, ca, b, bc, ab, c, ca, bc, ab, bc, ca].iter());
+        }
+        indices = subdivided;
+    }
+
+    let vertices = points.into_iter()
+        .map(|normal| {
+            let uv = vec2(0.5 + normal.x.atan2(normal.z) / (2. * PI), 0.5 + normal.y.asin() / PI);
+            vertex(normal * radius, normal, uv)
+        })
+        .collect();
+    (vertices, indices)
+}
+
+/// Cone standing on its base, with the apex pointing up
+pub fn cone(radius: f32, height: f32, sectors: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let (top, bottom) = (height / 2., -height / 2.);
+
+    // side, every sector has its own apex vertex so that normals at the tip follow the sector
+    for sector in 0..sectors {
+        let h_angle = 2. * PI * (sector as f32 + 0.5) / sectors as f32;
+        let normal = (horizontal(h_angle) * height + vec3(0., radius, 0.)).normalize();
+        vertices.push(vertex(vec3(0., top, 0.), normal, vec2((sector as f32 + 0.5) / sectors as f32, 1.)));
+    }
+    for sector in 0..=sectors {
+        let h_angle = 2. * PI * sector as f32 / sectors as f32;
+        let normal = (horizontal(h_angle) * height + vec3(0., radius, 0.)).normalize();
+        vertices.push(vertex(horizontal(h_angle) * radius + vec3(0., bottom, 0.), normal, vec2(sector as f32 / sectors as f32, 0.)));
+    }
+    for sector in 0..sectors {
+        indices.extend([sector, sectors + sector, sectors + sector + 1].iter());
+    }
+
+    add_cap(&mut vertices, &mut indices, radius, bottom, sectors, false);
+    (vertices, indices)
+}
+
+/// Cylinder standing on its base
+#[allow(dead_code)]
+pub fn cylinder(radius: f32, height: f32, sectors: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![];
+    for &(y, v) in &[(height / 2., 1.), (-height / 2., 0.)] {
+        for sector in 0..=sectors {
+            let h_angle = 2. * PI * sector as f32 / sectors as f32;
+            let normal = horizontal(h_angle);
+            vertices.push(vertex(normal * radius + vec3(0., y, 0.), normal, vec2(sector as f32 / sectors as f32, v)));
+        }
+    }
+    let mut indices = grid_indices(1, sectors, false, false);
+
+    add_cap(&mut vertices, &mut indices, radius, height / 2., sectors, true);
+    add_cap(&mut vertices, &mut indices, radius, -height / 2., sectors, false);
+    (vertices, indices)
+}
+
+/// Flat disc closing a cone or a cylinder, facing up or down
+fn add_cap(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, radius: f32, y: f32, sectors: u32, facing_up: bool) {
+    let normal = vec3(0., if facing_up { 1. } else { -1. }, 0.);
+    let center = vertices.len() as u32;
+    vertices.push(vertex(vec3(0., y, 0.), normal, vec2(0.5, 0.5)));
+    for sector in 0..=sectors {
+        let direction = horizontal(2. * PI * sector as f32 / sectors as f32);
+        vertices.push(vertex(direction * radius + vec3(0., y, 0.), normal, vec2(0.5 + direction.x / 2., 0.5 + direction.z / 2.)));
+    }
+    for sector in 0..sectors {
+        let (current, next) = (center + 1 + sector, center + 2 + sector);
+        if facing_up {
+            indices.extend([center, current, next].iter());
+        } else {
+            indices.extend([center, next, current].iter());
+        }
+    }
+}
+
+/// Ring lying in the OXZ plane, `major_radius` is measured to the center of the tube
+#[allow(dead_code)]
+pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(((major_segments + 1) * (minor_segments + 1)) as usize);
+    for major in 0..=major_segments {
+        let u = 2. * PI * major as f32 / major_segments as f32;
+        let tube_center = horizontal(u) * major_radius;
+        for minor in 0..=minor_segments {
+            let v = 2. * PI * minor as f32 / minor_segments as f32;
+            let normal = horizontal(u) * v.cos() + vec3(0., v.sin(), 0.);
+            let uv = vec2(major as f32 / major_segments as f32, minor as f32 / minor_segments as f32);
+            vertices.push(vertex(tube_center + normal * minor_radius, normal, uv));
+        }
+    }
+
+    let mut indices = vec![];
+    for major in 0..major_segments {
+        for minor in 0..minor_segments {
+            let current = major * (minor_segments + 1) + minor;
+            let next = current + minor_segments + 1;
+            indices.extend([current, next, current + 1, current + 1, next, next + 1].iter());
+        }
+    }
+    (vertices, indices)
+}
+
+/// Box, every face has its own vertices, so edges stay sharp
+#[allow(dead_code)]
+pub fn cuboid(width: f32, height: f32, depth: f32) -> (Vec<Vertex>, Vec<u32>) {
+    let half = vec3(width, height, depth) / 2.;
+    let extent = |axis: Vector3<f32>| axis.x.abs() * half.x + axis.y.abs() * half.y + axis.z.abs() * half.z;
+    // normal, then two axes spanning the face, their cross product is the normal
+    let faces = [
+        (vec3(1., 0., 0.), vec3(0., 0., -1.), vec3(0., 1., 0.)),
+        (vec3(-1., 0., 0.), vec3(0., 0., 1.), vec3(0., 1., 0.)),
+        (vec3(0., 1., 0.), vec3(1., 0., 0.), vec3(0., 0., -1.)),
+        (vec3(0., -1., 0.), vec3(1., 0., 0.), vec3(0., 0., 1.)),
+        (vec3(0., 0., 1.), vec3(1., 0., 0.), vec3(0., 1., 0.)),
+        (vec3(0., 0., -1.), vec3(-1., 0., 0.), vec3(0., 1., 0.)),
+    ];
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for &(normal, u, v) in faces.iter() {
+        let center = normal * extent(normal);
+        let (u, v) = (u * extent(u), v * extent(v));
+        let first = vertices.len() as u32;
+        vertices.push(vertex(center - u - v, normal, vec2(0., 0.)));
+        vertices.push(vertex(center + u - v, normal, vec2(1., 0.)));
+        vertices.push(vertex(center + u + v, normal, vec2(1., 1.)));
+        vertices.push(vertex(center - u + v, normal, vec2(0., 1.)));
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3].iter());
+    }
+    (vertices, indices)
+}
+
+/// Flat rectangle in the OXZ plane facing up, split into `subdivisions` x `subdivisions` quads
+pub fn plane(width: f32, depth: f32, subdivisions: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(((subdivisions + 1) * (subdivisions + 1)) as usize);
+    for row in 0..=subdivisions {
+        let v = row as f32 / subdivisions as f32;
+        for column in 0..=subdivisions {
+            let u = column as f32 / subdivisions as f32;
+            let position = vec3(width * (u - 0.5), 0., depth * (v - 0.5));
+            vertices.push(vertex(position, vec3(0., 1., 0.), vec2(u, 1. - v)));
+        }
+    }
+
+    let mut indices = vec![];
+    for row in 0..subdivisions {
+        for column in 0..subdivisions {
+            let far = row * (subdivisions + 1) + column;
+            let near = far + subdivisions + 1;
+            indices.extend([far, near, far + 1, far + 1, near, near + 1].iter());
+        }
+    }
+    (vertices, indices)
+}
+
+/// Cylinder of `height` with hemispheres of `radius` on both ends, every hemisphere has `rings` layers
+#[allow(dead_code)]
+pub fn capsule(radius: f32, height: f32, sectors: u32, rings: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let total_height = height + 2. * radius;
+    let mut vertices = Vec::with_capacity(((2 * rings + 2) * (sectors + 1)) as usize);
+    // upper hemisphere ends and lower one starts at the equator, the band between them is the cylinder
+    let layers = (0..=rings).map(|ring| (ring, height / 2.))
+        .chain((rings..=2 * rings).map(|ring| (ring, -height / 2.)));
+    for (ring, offset) in layers {
+        let v_angle = PI * ring as f32 / (2 * rings) as f32;
+        for sector in 0..=sectors {
+            let h_angle = 2. * PI * sector as f32 / sectors as f32;
+            let normal = horizontal(h_angle) * v_angle.sin() + vec3(0., v_angle.cos(), 0.);
+            let position = normal * radius + vec3(0., offset, 0.);
+            let uv = vec2(sector as f32 / sectors as f32, 0.5 + position.y / total_height);
+            vertices.push(vertex(position, normal, uv));
+        }
+    }
+    (vertices, grid_indices(2 * rings + 1, sectors, true, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{EuclideanSpace, InnerSpace, Vector3};
+
+    use crate::mesh::primitives::{capsule, cone, cuboid, cylinder, icosphere, plane, torus, uv_sphere};
+    use crate::mesh::Vertex;
+
+    /// Checks what every primitive has to fulfill: valid, non-degenerate triangles with counter-clockwise winding
+    /// agreeing with unit length, outward facing normals
+    fn assert_well_formed(vertices: &[Vertex], indices: &[u32]) {
+        assert_eq!(indices.len() % 3, 0, "indices don't form triangles");
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()), "index out of bounds");
+        for v in vertices {
+            assert!((v.normal.magnitude() - 1.).abs() < 1e-5, "normal {:?} is not normalized", v.normal);
+        }
+        for (t, triangle) in indices.chunks(3).enumerate() {
+            let [a, b, c] = [&vertices[triangle[0] as usize], &vertices[triangle[1] as usize], &vertices[triangle[2] as usize]];
+            let face_normal: Vector3<f32> = (b.position - a.position).cross(c.position - a.position);
+            assert!(face_normal.magnitude() > 1e-7, "triangle {} is degenerate", t);
+            let vertex_normals = a.normal + b.normal + c.normal;
+            assert!(face_normal.dot(vertex_normals) > 0., "triangle {} {:?} is wound clockwise", t, triangle);
+        }
+    }
+
+    #[test]
+    fn uv_sphere_is_well_formed() {
+        let (vertices, indices) = uv_sphere(2., 16, 8);
+
+        assert_eq!(vertices.len(), 9 * 17);
+        assert_eq!(indices.len(), 16 * (2 * 8 - 2) * 3);
+        assert_well_formed(&vertices, &indices);
+        for v in &vertices {
+            assert!((v.position.to_vec().magnitude() - 2.).abs() < 1e-5);
+            assert!((v.position.to_vec() / 2. - v.normal).magnitude() < 1e-5, "normal doesn't point away from the center");
+        }
+    }
+
+    #[test]
+    fn icosphere_is_well_formed() {
+        let (vertices, indices) = icosphere(1.5, 2);
+
+        assert_eq!(vertices.len(), 10 * 4_usize.pow(2) + 2);
+        assert_eq!(indices.len(), 20 * 4_usize.pow(2) * 3);
+        assert_well_formed(&vertices, &indices);
+        for v in &vertices {
+            assert!((v.position.to_vec().magnitude() - 1.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn cone_is_well_formed() {
+        let (vertices, indices) = cone(1., 2., 12);
+
+        assert_eq!(vertices.len(), 12 + 13 + 1 + 13);
+        assert_eq!(indices.len(), 2 * 12 * 3);
+        assert_well_formed(&vertices, &indices);
+        assert!(vertices.iter().all(|v| v.position.y >= -1. && v.position.y <= 1.));
+    }
+
+    #[test]
+    fn cylinder_is_well_formed() {
+        let (vertices, indices) = cylinder(0.5, 3., 10);
+
+        assert_eq!(vertices.len(), 2 * 11 + 2 * (1 + 11));
+        assert_eq!(indices.len(), 4 * 10 * 3);
+        assert_well_formed(&vertices, &indices);
+    }
+
+    #[test]
+    fn torus_is_well_formed() {
+        let (vertices, indices) = torus(2., 0.5, 24, 12);
+
+        assert_eq!(vertices.len(), 25 * 13);
+        assert_eq!(indices.len(), 24 * 12 * 6);
+        assert_well_formed(&vertices, &indices);
+    }
+
+    #[test]
+    fn cuboid_is_well_formed() {
+        let (vertices, indices) = cuboid(1., 2., 3.);
+
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(indices.len(), 36);
+        assert_well_formed(&vertices, &indices);
+        for v in &vertices {
+            assert_eq!([v.position.x.abs(), v.position.y.abs(), v.position.z.abs()], [0.5, 1., 1.5]);
+        }
+    }
+
+    #[test]
+    fn plane_is_well_formed() {
+        let (vertices, indices) = plane(4., 2., 3);
+
+        assert_eq!(vertices.len(), 16);
+        assert_eq!(indices.len(), 9 * 6);
+        assert_well_formed(&vertices, &indices);
+        assert!(vertices.iter().all(|v| v.position.y == 0.));
+    }
+
+    #[test]
+    fn capsule_is_well_formed() {
+        let (vertices, indices) = capsule(0.5, 2., 12, 4);
+
+        assert_eq!(vertices.len(), 10 * 13);
+        assert_eq!(indices.len(), 12 * (2 * 9 - 2) * 3);
+        assert_well_formed(&vertices, &indices);
+        let top = vertices.iter().map(|v| v.position.y).fold(f32::MIN, f32::max);
+        assert!((top - 1.5).abs() < 1e-5);
+    }
+}
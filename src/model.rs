@@ -1,26 +1,53 @@
-use cgmath::Matrix4;
-use wasm_bindgen::__rt::core::mem;
+use cgmath::{Matrix4, vec3, Vector3};
 use web_sys::WebGl2RenderingContext as GL;
 
+use crate::camera::View;
 use crate::material::MaterialId;
-use crate::shader::Shader;
+use crate::mesh::Mesh;
+use crate::mesh::layout::{Attribute, AttributeType, VertexFormat, VertexLayout};
+use crate::render::queue::RenderQueue;
 
 #[derive(Debug)]
 #[repr(C)]  // to make sure memory representation is like in the code
 pub struct Instance {
     pub model: Matrix4<f32>,
     pub material_id: MaterialId,
+    /// Multiplies ambient and diffuse colors of the material, white leaves them unchanged
+    pub tint: Vector3<f32>,
+    /// How much the instance glows in its own diffuse color, on top of the material's emission
+    pub emissive: f32,
+    /// Free for models to pass any per-instance value, only the aurora shader reads it
+    pub user: f32,
 }
 
 impl Instance {
-    pub fn size() -> i32 {
-        (mem::size_of::<Matrix4<f32>>() + mem::size_of::<u32>()) as i32
+    pub fn new(model: Matrix4<f32>, material_id: MaterialId) -> Self {
+        Instance { model, material_id, tint: vec3(1., 1., 1.), emissive: 0., user: 0. }
     }
 
-    pub fn as_vec(&self) -> Vec<f32> {
+    pub fn with_tint(mut self, tint: Vector3<f32>) -> Self {
+        self.tint = tint;
+        self
+    }
+}
+
+impl VertexFormat for Instance {
+    fn layout() -> VertexLayout {
+        VertexLayout::new(&[
+            Attribute::per_instance("instanceModel", 3, 16, AttributeType::Float),
+            Attribute::per_instance("instanceMaterialId", 7, 1, AttributeType::UnsignedInt),
+            Attribute::per_instance("instanceTint", 8, 3, AttributeType::Float),
+            Attribute::per_instance("instanceEmissive", 9, 1, AttributeType::Float),
+            Attribute::per_instance("instanceUser", 10, 1, AttributeType::Float),
+        ])
+    }
+
+    fn as_vec(&self) -> Vec<f32> {
         let model: &[f32; 16] = self.model.as_ref();
-        let material: [f32; 1] = [self.material_id];
-        [&model[..], &material[..]].concat()
+        // material id is an integer attribute, only its bits travel in the float buffer
+        let material: [f32; 1] = [f32::from_bits(self.material_id)];
+        let tint: [f32; 3] = self.tint.into();
+        [&model[..], &material[..], &tint[..], &[self.emissive, self.user][..]].concat()
     }
 }
 
@@ -28,6 +55,30 @@ pub trait Model {
     /// Do all necessary things to advance the model to the next frame
     fn next_frame(&mut self, gl: &GL);
 
-    /// Draw the model using given shader
-    fn draw(&self, gl: &GL, shader: &Shader);
+    /// Queue draw calls of the model, skipping whatever is outside of the view
+    fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View);
+
+    /// Meshes that make up the model as it looks right now, for exporting the scene
+    fn meshes(&self) -> Vec<&Mesh>;
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix4, SquareMatrix, vec3};
+
+    use crate::mesh::layout::VertexFormat;
+    use crate::model::Instance;
+
+    #[test]
+    fn instance_data_matches_layout() {
+        let mut instance = Instance::new(Matrix4::identity(), 7).with_tint(vec3(0.5, 0.6, 0.7));
+        instance.emissive = 2.;
+        instance.user = 3.;
+
+        let data = instance.as_vec();
+
+        assert_eq!(data.len() * 4, Instance::layout().stride() as usize);
+        assert_eq!(data[16].to_bits(), 7);
+        assert_eq!(&data[17..], &[0.5, 0.6, 0.7, 2., 3.]);
+    }
 }
@@ -0,0 +1,144 @@
+//! Drawing of the scene: pipelines models ask for, the queue ordering their draw calls and the cache of GL state
+
+use crate::material::Material;
+use crate::shader::{AURORA_PROGRAM, DOUBLE_SIDED, ProgramSource, SKY_PROGRAM, STANDARD_PROGRAM, UNLIT_PROGRAM};
+use crate::shader::preprocessor::Defines;
+
+pub mod post;
+pub mod queue;
+pub mod state;
+
+/// Animations advance by a frame every time the scene is drawn, they assume frames come at this rate
+pub const FRAMES_PER_SECOND: f32 = 60.;
+
+/// How a mesh is drawn, a program variant together with fixed-function state. Pipelines are drawn in this order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Pipeline {
+    /// Phong lighting with materials and textures, the default
+    Lit,
+    /// Thin flat geometry lit from whichever side the camera sees, so it needs only one side
+    Snow,
+    /// Material colors without lights, for things that only glow
+    Unlit,
+    /// Background around everything, drawn where nothing else is, seen from the inside
+    Sky,
+    /// Light in the sky blended over it, both sides of the curtains are seen
+    Aurora,
+    /// Lit translucent surfaces blended over everything else, so they go last, sorted back to front
+    Glass,
+}
+
+/// Fixed-function state of a pipeline
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DrawState {
+    pub cull_face: bool,
+    pub blend: bool,
+    pub depth_write: bool,
+}
+
+impl DrawState {
+    pub const OPAQUE: DrawState = DrawState { cull_face: true, blend: false, depth_write: true };
+    /// What every frame starts with, clearing the depth buffer only works with depth writes on
+    pub const DEFAULT: DrawState = DrawState::OPAQUE;
+    /// Surfaces behind everything don't need to hide anything either
+    pub const BACKGROUND: DrawState = DrawState { cull_face: false, blend: false, depth_write: false };
+    /// Translucent surfaces are still hidden by solid ones in front of them, but don't hide anything themselves
+    pub const TRANSLUCENT: DrawState = DrawState { cull_face: true, blend: true, depth_write: false };
+}
+
+impl Pipeline {
+    pub const ALL: [Pipeline; 6] = [Pipeline::Lit, Pipeline::Snow, Pipeline::Unlit, Pipeline::Sky, Pipeline::Aurora, Pipeline::Glass];
+
+    pub fn program(self) -> ProgramSource {
+        match self {
+            Pipeline::Lit | Pipeline::Snow | Pipeline::Glass => STANDARD_PROGRAM,
+            Pipeline::Unlit => UNLIT_PROGRAM,
+            Pipeline::Sky => SKY_PROGRAM,
+            Pipeline::Aurora => AURORA_PROGRAM,
+        }
+    }
+
+    /// Defines of the program variant, `features` are the optional ones turned on for lit surfaces
+    pub fn defines(self, features: &Defines) -> Defines {
+        match self {
+            Pipeline::Lit | Pipeline::Glass => features.clone(),
+            Pipeline::Snow => features.clone().flag(DOUBLE_SIDED),
+            Pipeline::Unlit | Pipeline::Sky | Pipeline::Aurora => Defines::new(),
+        }
+    }
+
+    pub fn state(self) -> DrawState {
+        match self {
+            Pipeline::Lit | Pipeline::Unlit => DrawState::OPAQUE,
+            Pipeline::Snow => DrawState { cull_face: false, ..DrawState::OPAQUE },
+            Pipeline::Sky => DrawState::BACKGROUND,
+            Pipeline::Aurora => DrawState { cull_face: false, ..DrawState::TRANSLUCENT },
+            Pipeline::Glass => DrawState::TRANSLUCENT,
+        }
+    }
+
+    /// Blended draws depend on what's already drawn behind them, they have to be ordered by distance
+    pub fn is_translucent(self) -> bool {
+        self.state().blend
+    }
+
+    /// Materials that neither reflect nor shine, only glow (like lamps in imported models), don't need lights
+    pub fn for_material(material: &Material) -> Pipeline {
+        if material.alpha < 1. {
+            Pipeline::Glass
+        } else if material.diffuse.is_black() && material.specular.is_black() && !material.emissive.is_black() {
+            Pipeline::Unlit
+        } else {
+            Pipeline::Lit
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::LinearRgb;
+    use crate::material::{Material, MaterialTextures};
+    use crate::render::{DrawState, Pipeline};
+    use crate::shader::{DOUBLE_SIDED, NORMAL_MAPPING};
+    use crate::shader::preprocessor::Defines;
+
+    fn material(diffuse: f32, emissive: f32) -> Material {
+        Material {
+            ambient: LinearRgb::BLACK,
+            diffuse: LinearRgb::new(diffuse, diffuse, diffuse),
+            specular: LinearRgb::BLACK,
+            shininess: 1.,
+            emissive: LinearRgb::new(emissive, emissive, 0.),
+            reflectivity: 0.,
+            alpha: 1.,
+            textures: MaterialTextures::default(),
+        }
+    }
+
+    #[test]
+    fn only_glowing_materials_are_unlit() {
+        assert_eq!(Pipeline::for_material(&material(0., 1.)), Pipeline::Unlit);
+        assert_eq!(Pipeline::for_material(&material(0.5, 1.)), Pipeline::Lit);
+        assert_eq!(Pipeline::for_material(&material(0., 0.)), Pipeline::Lit);
+    }
+
+    #[test]
+    fn translucent_materials_are_glass() {
+        let glass = Material { alpha: 0.4, ..material(0., 1.) };
+
+        assert_eq!(Pipeline::for_material(&glass), Pipeline::Glass);
+        assert!(Pipeline::Glass.is_translucent());
+        assert!(!Pipeline::Glass.state().depth_write);
+        assert_eq!(Pipeline::ALL.iter().max(), Some(&Pipeline::Glass), "glass is drawn last");
+    }
+
+    #[test]
+    fn snow_is_lit_from_both_sides() {
+        let features = Defines::new().flag(NORMAL_MAPPING);
+
+        assert_eq!(Pipeline::Snow.defines(&features), features.clone().flag(DOUBLE_SIDED));
+        assert!(!Pipeline::Snow.state().cull_face);
+        assert_eq!(Pipeline::Unlit.defines(&features), Defines::new());
+        assert_eq!(Pipeline::Lit.state(), DrawState::OPAQUE);
+    }
+}
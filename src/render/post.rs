@@ -0,0 +1,366 @@
+//! Post-processing: the scene is drawn into an offscreen HDR target, multisampled and resolved if MSAA is on,
+//! and gets to the screen through fullscreen passes, bloom (bright pass and Gaussian blur), tone mapping with vignette
+//! and grain, and FXAA.
+use std::rc::Rc;
+
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::gpu::{Framebuffer, Renderbuffer, Texture, VertexArray};
+use crate::render::FRAMES_PER_SECOND;
+use crate::shader::{ProgramSource, Shader};
+use crate::shader::cache::ShaderCache;
+use crate::shader::error::ShaderError;
+use crate::shader::preprocessor::Defines;
+
+pub const BLOOM: &str = "BLOOM";
+pub const TONE_MAPPING: &str = "TONE_MAPPING";
+pub const FXAA: &str = "FXAA";
+pub const VIGNETTE: &str = "VIGNETTE";
+pub const FILM_GRAIN: &str = "FILM_GRAIN";
+pub const EFFECTS: &[&str] = &[BLOOM, TONE_MAPPING, FXAA, VIGNETTE, FILM_GRAIN];
+
+/// Only colors brighter than this glow
+const BLOOM_THRESHOLD: f32 = 1.;
+const BLOOM_INTENSITY: f32 = 0.6;
+/// Horizontal and vertical blur passes, more spread the glow further
+const BLUR_ITERATIONS: usize = 3;
+/// MSAA samples per pixel unless the page asks for another count, limited by what the device supports
+pub const DEFAULT_SAMPLES: i32 = 4;
+
+const FULLSCREEN_VERTEX: &str = include_str!("../../shaders/post/fullscreen.vert");
+
+pub const BRIGHT_PROGRAM: ProgramSource = ProgramSource {
+    name: "bright",
+    vertex: FULLSCREEN_VERTEX,
+    fragment: include_str!("../../shaders/post/bright.frag"),
+};
+
+pub const BLUR_PROGRAM: ProgramSource = ProgramSource {
+    name: "blur",
+    vertex: FULLSCREEN_VERTEX,
+    fragment: include_str!("../../shaders/post/blur.frag"),
+};
+
+pub const COMPOSITE_PROGRAM: ProgramSource = ProgramSource {
+    name: "composite",
+    vertex: FULLSCREEN_VERTEX,
+    fragment: include_str!("../../shaders/post/composite.frag"),
+};
+
+pub const FXAA_PROGRAM: ProgramSource = ProgramSource {
+    name: "fxaa",
+    vertex: FULLSCREEN_VERTEX,
+    fragment: include_str!("../../shaders/post/fxaa.frag"),
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ColorFormat {
+    internal: u32,
+    format: u32,
+    data_type: u32,
+}
+
+const HDR_FORMAT: ColorFormat = ColorFormat { internal: GL::RGBA16F, format: GL::RGBA, data_type: GL::HALF_FLOAT };
+const LDR_FORMAT: ColorFormat = ColorFormat { internal: GL::RGBA8, format: GL::RGBA, data_type: GL::UNSIGNED_BYTE };
+
+/// Texture to draw into, with a depth buffer if the scene itself is drawn into it
+struct RenderTarget {
+    framebuffer: Framebuffer,
+    color: Texture,
+    #[allow(dead_code)] // only kept so that the depth buffer lives as long as the framebuffer
+    depth: Option<Renderbuffer>,
+    width: i32,
+    height: i32,
+}
+
+impl RenderTarget {
+    fn new(gl: &GL, width: i32, height: i32, format: ColorFormat, with_depth: bool) -> Self {
+        let color = Texture::new(gl);
+        gl.bind_texture(GL::TEXTURE_2D, Some(&*color));
+        gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+            GL::TEXTURE_2D, 0, format.internal as i32, width, height, 0, format.format, format.data_type, None)
+            .expect("Cannot allocate render target");
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
+
+        let framebuffer = Framebuffer::new(gl);
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&*framebuffer));
+        gl.framebuffer_texture_2d(GL::FRAMEBUFFER, GL::COLOR_ATTACHMENT0, GL::TEXTURE_2D, Some(&*color), 0);
+        let depth = if with_depth {
+            let depth = Renderbuffer::new(gl);
+            gl.bind_renderbuffer(GL::RENDERBUFFER, Some(&*depth));
+            gl.renderbuffer_storage(GL::RENDERBUFFER, GL::DEPTH_COMPONENT24, width, height);
+            gl.framebuffer_renderbuffer(GL::FRAMEBUFFER, GL::DEPTH_ATTACHMENT, GL::RENDERBUFFER, Some(&*depth));
+            gl.bind_renderbuffer(GL::RENDERBUFFER, None);
+            Some(depth)
+        } else {
+            None
+        };
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        RenderTarget { framebuffer, color, depth, width, height }
+    }
+
+    fn bind(&self, gl: &GL) {
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&*self.framebuffer));
+        gl.viewport(0, 0, self.width, self.height);
+    }
+}
+
+/// Renderbuffers the scene is drawn into with MSAA, textures can't be multisampled in WebGL.
+/// They're resolved into the scene target before post-processing.
+struct MultisampledTarget {
+    framebuffer: Framebuffer,
+    #[allow(dead_code)] // only kept so that the buffers live as long as the framebuffer
+    buffers: [Renderbuffer; 2],
+}
+
+impl MultisampledTarget {
+    fn new(gl: &GL, width: i32, height: i32, format: ColorFormat, samples: i32) -> Self {
+        let framebuffer = Framebuffer::new(gl);
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&*framebuffer));
+        let buffer = |internal: u32, attachment: u32| {
+            let buffer = Renderbuffer::new(gl);
+            gl.bind_renderbuffer(GL::RENDERBUFFER, Some(&*buffer));
+            gl.renderbuffer_storage_multisample(GL::RENDERBUFFER, samples, internal, width, height);
+            gl.framebuffer_renderbuffer(GL::FRAMEBUFFER, attachment, GL::RENDERBUFFER, Some(&*buffer));
+            buffer
+        };
+        let buffers = [buffer(format.internal, GL::COLOR_ATTACHMENT0), buffer(GL::DEPTH_COMPONENT24, GL::DEPTH_ATTACHMENT)];
+        gl.bind_renderbuffer(GL::RENDERBUFFER, None);
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        MultisampledTarget { framebuffer, buffers }
+    }
+
+    /// Averages the samples of every pixel into the color texture of `target`
+    fn resolve(&self, gl: &GL, target: &RenderTarget) {
+        gl.bind_framebuffer(GL::READ_FRAMEBUFFER, Some(&*self.framebuffer));
+        gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, Some(&*target.framebuffer));
+        let (width, height) = (target.width, target.height);
+        gl.blit_framebuffer(0, 0, width, height, 0, 0, width, height, GL::COLOR_BUFFER_BIT, GL::NEAREST);
+        gl.bind_framebuffer(GL::READ_FRAMEBUFFER, None);
+        gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, None);
+    }
+}
+
+/// Render targets, they have to be recreated whenever the canvas changes size
+struct Targets {
+    /// Where the scene is drawn with MSAA, `None` when it's off
+    multisampled: Option<MultisampledTarget>,
+    /// Scene as drawn or resolved, input of the passes, it needs its own depth buffer only without MSAA
+    scene: RenderTarget,
+    /// Tone mapped result, input of FXAA
+    ldr: RenderTarget,
+    /// Blur goes back and forth between two half sized targets
+    bloom: [RenderTarget; 2],
+}
+
+impl Targets {
+    fn new(gl: &GL, width: i32, height: i32, hdr: ColorFormat, samples: i32) -> Self {
+        let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+        let multisampled = if samples > 1 { Some(MultisampledTarget::new(gl, width, height, hdr, samples)) } else { None };
+        Targets {
+            scene: RenderTarget::new(gl, width, height, hdr, multisampled.is_none()),
+            multisampled,
+            ldr: RenderTarget::new(gl, width, height, LDR_FORMAT, false),
+            bloom: [RenderTarget::new(gl, half_width, half_height, hdr, false), RenderTarget::new(gl, half_width, half_height, hdr, false)],
+        }
+    }
+}
+
+pub struct PostProcessing {
+    /// Enabled effects, as defines of the composite program
+    effects: Defines,
+    hdr: ColorFormat,
+    /// MSAA samples per pixel, 1 without MSAA
+    samples: i32,
+    max_samples: i32,
+    targets: Targets,
+    /// Fullscreen passes take no vertex data, but something has to be bound
+    empty_vao: VertexArray,
+    bright: Rc<Shader>,
+    blur: Rc<Shader>,
+    composite: Rc<Shader>,
+    fxaa: Rc<Shader>,
+    frame: u32,
+}
+
+impl PostProcessing {
+    pub fn new(gl: &GL, shaders: &mut ShaderCache, width: i32, height: i32) -> Result<Self, ShaderError> {
+        // float targets keep colors above 1.0 for bloom and tone mapping, without the extension they're clamped
+        let hdr = match gl.get_extension("EXT_color_buffer_float") {
+            Ok(Some(_)) => HDR_FORMAT,
+            _ => LDR_FORMAT,
+        };
+        let effects = Defines::new().flag(BLOOM).flag(TONE_MAPPING).flag(FXAA);
+        let max_samples = max_samples(gl, hdr);
+        let samples = sample_count(DEFAULT_SAMPLES, max_samples);
+        Ok(PostProcessing {
+            hdr,
+            samples,
+            max_samples,
+            targets: Targets::new(gl, width, height, hdr, samples),
+            empty_vao: VertexArray::new(gl),
+            bright: shaders.get(gl, &BRIGHT_PROGRAM, &Defines::new())?,
+            blur: shaders.get(gl, &BLUR_PROGRAM, &Defines::new())?,
+            composite: shaders.get(gl, &COMPOSITE_PROGRAM, &composite_defines(&effects))?,
+            fxaa: shaders.get(gl, &FXAA_PROGRAM, &Defines::new())?,
+            effects,
+            frame: 0,
+        })
+    }
+
+    pub fn resize(&mut self, gl: &GL, width: i32, height: i32) {
+        self.targets = Targets::new(gl, width, height, self.hdr, self.samples);
+    }
+
+    /// Turns MSAA on with about `samples` samples per pixel, or off with 0 or 1, returns the count actually used
+    pub fn set_samples(&mut self, gl: &GL, samples: i32) -> i32 {
+        let samples = sample_count(samples, self.max_samples);
+        if samples != self.samples {
+            self.samples = samples;
+            let scene = &self.targets.scene;
+            self.targets = Targets::new(gl, scene.width, scene.height, self.hdr, samples);
+        }
+        samples
+    }
+
+    /// Turns one of `EFFECTS` on or off, if the new variant doesn't compile the effects stay as they were
+    pub fn set_effect(&mut self, gl: &GL, shaders: &mut ShaderCache, effect: &str, enabled: bool) -> Result<(), ShaderError> {
+        let effects = if enabled { self.effects.clone().flag(effect) } else { self.effects.clone().without(effect) };
+        self.composite = shaders.get(gl, &COMPOSITE_PROGRAM, &composite_defines(&effects))?;
+        self.effects = effects;
+        Ok(())
+    }
+
+    /// Whatever is drawn from now on goes to the HDR target
+    pub fn begin(&self, gl: &GL) {
+        match &self.targets.multisampled {
+            Some(multisampled) => {
+                gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&*multisampled.framebuffer));
+                gl.viewport(0, 0, self.targets.scene.width, self.targets.scene.height);
+            }
+            None => self.targets.scene.bind(gl),
+        }
+    }
+
+    /// Runs the passes, the result ends up in the default framebuffer
+    pub fn finish(&mut self, gl: &GL) {
+        self.frame = self.frame.wrapping_add(1);
+        gl.disable(GL::DEPTH_TEST);
+        gl.bind_vertex_array(Some(&*self.empty_vao));
+
+        let targets = &self.targets;
+        if let Some(multisampled) = &targets.multisampled {
+            multisampled.resolve(gl, &targets.scene);
+        }
+        if self.effects.contains(BLOOM) {
+            self.bloom(gl);
+        }
+
+        let fxaa = self.effects.contains(FXAA);
+        if fxaa {
+            targets.ldr.bind(gl);
+        } else {
+            Self::bind_screen(gl, &targets.scene);
+        }
+        gl.use_program(Some(&*self.composite.program));
+        Self::bind_input(gl, &self.composite, "sceneColor", 0, &targets.scene.color);
+        Self::bind_input(gl, &self.composite, "bloomColor", 1, &targets.bloom[0].color);
+        self.composite.set_float(gl, "bloomIntensity", BLOOM_INTENSITY);
+        self.composite.set_float(gl, "time", self.frame as f32 / FRAMES_PER_SECOND);
+        Self::draw_fullscreen(gl);
+
+        if fxaa {
+            Self::bind_screen(gl, &targets.ldr);
+            gl.use_program(Some(&*self.fxaa.program));
+            Self::bind_input(gl, &self.fxaa, "image", 0, &targets.ldr.color);
+            self.fxaa.set_vec2(gl, "texelSize", 1. / targets.ldr.width as f32, 1. / targets.ldr.height as f32);
+            Self::draw_fullscreen(gl);
+        }
+
+        gl.bind_vertex_array(None);
+        gl.enable(GL::DEPTH_TEST);
+    }
+
+    /// Bright parts of the scene, downsampled and blurred, end up in the first bloom target
+    fn bloom(&self, gl: &GL) {
+        let [first, second] = &self.targets.bloom;
+        first.bind(gl);
+        gl.use_program(Some(&*self.bright.program));
+        Self::bind_input(gl, &self.bright, "sceneColor", 0, &self.targets.scene.color);
+        self.bright.set_float(gl, "threshold", BLOOM_THRESHOLD);
+        Self::draw_fullscreen(gl);
+
+        gl.use_program(Some(&*self.blur.program));
+        let (texel_width, texel_height) = (1. / first.width as f32, 1. / first.height as f32);
+        for _ in 0..BLUR_ITERATIONS {
+            second.bind(gl);
+            Self::bind_input(gl, &self.blur, "image", 0, &first.color);
+            self.blur.set_vec2(gl, "direction", texel_width, 0.);
+            Self::draw_fullscreen(gl);
+
+            first.bind(gl);
+            Self::bind_input(gl, &self.blur, "image", 0, &second.color);
+            self.blur.set_vec2(gl, "direction", 0., texel_height);
+            Self::draw_fullscreen(gl);
+        }
+    }
+
+    /// The canvas is as big as the full sized targets
+    fn bind_screen(gl: &GL, full_size: &RenderTarget) {
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        gl.viewport(0, 0, full_size.width, full_size.height);
+    }
+
+    fn bind_input(gl: &GL, shader: &Shader, name: &str, unit: u32, texture: &Texture) {
+        gl.active_texture(GL::TEXTURE0 + unit);
+        gl.bind_texture(GL::TEXTURE_2D, Some(&**texture));
+        shader.set_int(gl, name, unit as i32);
+    }
+
+    fn draw_fullscreen(gl: &GL) {
+        gl.draw_arrays(GL::TRIANGLES, 0, 3);
+    }
+}
+
+/// FXAA is a pass of its own, the composite program doesn't need to know about it
+pub fn composite_defines(effects: &Defines) -> Defines {
+    effects.clone().without(FXAA)
+}
+
+/// Most samples per pixel renderbuffers of the format can have, for float formats it can be less than `MAX_SAMPLES`
+fn max_samples(gl: &GL, format: ColorFormat) -> i32 {
+    let max = gl.get_parameter(GL::MAX_SAMPLES).ok().and_then(|max| max.as_f64()).map_or(1, |max| max as i32);
+    let supported = gl.get_internalformat_parameter(GL::RENDERBUFFER, format.internal, GL::SAMPLES).ok()
+        .filter(|counts| !counts.is_null())
+        .and_then(|counts| js_sys::Int32Array::new(&counts).to_vec().into_iter().max());
+    supported.map_or(max, |supported| supported.min(max))
+}
+
+/// Samples per pixel to use when `requested` ones are asked for, 1 means MSAA is off
+fn sample_count(requested: i32, max_samples: i32) -> i32 {
+    requested.clamp(1, max_samples.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::post::{BLOOM, composite_defines, FXAA, sample_count};
+    use crate::shader::preprocessor::Defines;
+
+    #[test]
+    fn composite_program_ignores_fxaa() {
+        let effects = Defines::new().flag(BLOOM).flag(FXAA);
+
+        assert_eq!(composite_defines(&effects), Defines::new().flag(BLOOM));
+    }
+
+    #[test]
+    fn sample_count_is_limited_by_the_device() {
+        assert_eq!(sample_count(4, 16), 4);
+        assert_eq!(sample_count(8, 4), 4);
+        assert_eq!(sample_count(0, 4), 1, "no samples means MSAA off");
+        assert_eq!(sample_count(4, 0), 1, "devices without MSAA");
+    }
+}
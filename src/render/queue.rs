@@ -0,0 +1,123 @@
+use std::cmp::Reverse;
+
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::gpu::VertexArray;
+use crate::render::Pipeline;
+use crate::render::state::StateCache;
+use crate::shader::Shader;
+use crate::texture::TextureSet;
+
+/// Instanced draw call waiting for its turn, instances are already uploaded
+pub struct DrawCall<'a> {
+    pub pipeline: Pipeline,
+    pub vao: &'a VertexArray,
+    pub textures: &'a TextureSet,
+    pub index_count: i32,
+    pub instance_count: i32,
+    /// Distance of the farthest instance from the camera, translucent draws are ordered by it
+    pub distance: f32,
+}
+
+/// What draws are ordered by, the most expensive state changes first
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    pipeline: Pipeline,
+    /// Bits of the distance for translucent draws, farthest first, 0 for the others. Bits of non-negative floats
+    /// are ordered the same way as their values.
+    depth: Reverse<u32>,
+    textures: [usize; 3],
+    vao: usize,
+}
+
+impl DrawCall<'_> {
+    fn sort_key(&self) -> SortKey {
+        let depth = if self.pipeline.is_translucent() { self.distance.max(0.).to_bits() } else { 0 };
+        SortKey { pipeline: self.pipeline, depth: Reverse(depth), textures: self.textures.key(), vao: self.vao as *const VertexArray as usize }
+    }
+}
+
+/// Orders items from the farthest to the nearest, so that translucent ones are blended over what's behind them.
+/// Instances of one draw call can be sorted like this, separate draw calls are sorted by the queue.
+pub fn back_to_front<T>(items: &mut [T], distance: impl Fn(&T) -> f32) {
+    items.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+}
+
+/// Draw calls of a frame, models fill it and the scene executes it grouped by pipeline, so that state changes are rare
+#[derive(Default)]
+pub struct RenderQueue<'a> {
+    draws: Vec<DrawCall<'a>>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> Self {
+        RenderQueue::default()
+    }
+
+    pub fn push(&mut self, draw: DrawCall<'a>) {
+        self.draws.push(draw);
+    }
+
+    /// `shader` gives the compiled program of a pipeline
+    pub fn execute<'s>(mut self, gl: &GL, state: &mut StateCache, shader: impl Fn(Pipeline) -> &'s Shader) {
+        // stable sort, draws with the same state keep the order models queued them in
+        self.draws.sort_by_key(DrawCall::sort_key);
+        for draw in &self.draws {
+            state.use_program(gl, shader(draw.pipeline));
+            state.apply(gl, draw.pipeline.state());
+            state.bind_textures(gl, draw.textures);
+            state.bind_vertex_array(gl, draw.vao);
+            gl.draw_elements_instanced_with_i32(GL::TRIANGLES, draw.index_count, GL::UNSIGNED_INT, 0, draw.instance_count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+
+    use crate::render::Pipeline;
+    use crate::render::queue::{back_to_front, SortKey};
+
+    fn key(pipeline: Pipeline, distance: f32, texture: usize, vao: usize) -> SortKey {
+        let depth = if pipeline.is_translucent() { distance.to_bits() } else { 0 };
+        SortKey { pipeline, depth: Reverse(depth), textures: [texture, 0, 0], vao }
+    }
+
+    #[test]
+    fn draws_are_grouped_by_pipeline_before_textures() {
+        let mut keys = [
+            key(Pipeline::Unlit, 5., 1, 1),
+            key(Pipeline::Lit, 1., 2, 2),
+            key(Pipeline::Lit, 9., 1, 3),
+            key(Pipeline::Snow, 0., 0, 4),
+        ];
+
+        keys.sort();
+
+        assert_eq!(keys.iter().map(|k| k.vao).collect::<Vec<_>>(), vec![3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn translucent_draws_go_last_from_the_farthest() {
+        let mut keys = [
+            key(Pipeline::Glass, 2.5, 0, 1),
+            key(Pipeline::Glass, 10., 1, 2),
+            key(Pipeline::Lit, 20., 0, 3),
+            key(Pipeline::Glass, 0.5, 0, 4),
+        ];
+
+        keys.sort();
+
+        assert_eq!(keys.iter().map(|k| k.vao).collect::<Vec<_>>(), vec![3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn sorts_back_to_front() {
+        let mut instances = [(0, 3.), (1, 12.), (2, 0.), (3, 7.5)];
+
+        back_to_front(&mut instances, |&(_, distance)| distance);
+
+        assert_eq!(instances.iter().map(|&(i, _)| i).collect::<Vec<_>>(), vec![1, 3, 0, 2]);
+    }
+}
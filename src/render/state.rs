@@ -0,0 +1,127 @@
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::gpu::VertexArray;
+use crate::render::DrawState;
+use crate::shader::Shader;
+use crate::texture::TextureSet;
+
+/// Remembers what is bound, so that binding the same thing again doesn't cost a call into WebGL.
+/// Anything changing the state behind its back (creating meshes, loading textures) has to be followed by `reset`.
+#[derive(Default)]
+pub struct StateCache {
+    program: Option<usize>,
+    vao: Option<usize>,
+    textures: Option<[usize; 3]>,
+    draw_state: Option<DrawState>,
+}
+
+/// Objects are told apart by their addresses, they don't move while they are drawn
+fn address<T>(object: &T) -> usize {
+    object as *const T as usize
+}
+
+/// Stores the new value, `true` if it differs from the current one, so the GL call is needed
+fn switch<T: PartialEq>(current: &mut Option<T>, new: T) -> bool {
+    if current.as_ref() == Some(&new) {
+        return false;
+    }
+    *current = Some(new);
+    true
+}
+
+/// Whether going from `previous` to `state` changes the flag, always when the previous state is unknown
+fn changed(previous: Option<DrawState>, state: DrawState, flag: fn(&DrawState) -> bool) -> bool {
+    previous.map_or(true, |previous| flag(&previous) != flag(&state))
+}
+
+impl StateCache {
+    pub fn new() -> Self {
+        StateCache::default()
+    }
+
+    /// Forgets everything, so the next calls bind again, and restores the default draw state.
+    /// The last draw of a frame may leave depth writes off, which would keep the next clear from clearing depth.
+    pub fn reset(&mut self, gl: &GL) {
+        self.forget();
+        self.apply(gl, DrawState::DEFAULT);
+    }
+
+    fn forget(&mut self) {
+        *self = StateCache::default();
+    }
+
+    pub fn use_program(&mut self, gl: &GL, shader: &Shader) {
+        if switch(&mut self.program, address(shader)) {
+            gl.use_program(Some(&*shader.program));
+        }
+    }
+
+    pub fn bind_vertex_array(&mut self, gl: &GL, vao: &VertexArray) {
+        if switch(&mut self.vao, address(vao)) {
+            gl.bind_vertex_array(Some(&**vao));
+        }
+    }
+
+    pub fn bind_textures(&mut self, gl: &GL, textures: &TextureSet) {
+        if switch(&mut self.textures, textures.key()) {
+            textures.bind(gl);
+        }
+    }
+
+    pub fn apply(&mut self, gl: &GL, state: DrawState) {
+        let previous = self.draw_state;
+        if !switch(&mut self.draw_state, state) {
+            return;
+        }
+        let changed = |flag| changed(previous, state, flag);
+        if changed(|s| s.cull_face) {
+            Self::toggle(gl, GL::CULL_FACE, state.cull_face);
+        }
+        if changed(|s| s.blend) {
+            Self::toggle(gl, GL::BLEND, state.blend);
+            if state.blend {
+                // destination alpha stays 1, the canvas mustn't show the page through glass
+                gl.blend_func_separate(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA, GL::ONE, GL::ONE_MINUS_SRC_ALPHA);
+            }
+        }
+        if changed(|s| s.depth_write) {
+            gl.depth_mask(state.depth_write);
+        }
+    }
+
+    fn toggle(gl: &GL, capability: u32, enabled: bool) {
+        if enabled {
+            gl.enable(capability);
+        } else {
+            gl.disable(capability);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::DrawState;
+    use crate::render::state::{changed, switch, StateCache};
+
+    #[test]
+    fn switches_only_to_different_values() {
+        let mut current = None;
+
+        assert!(switch(&mut current, 1));
+        assert!(!switch(&mut current, 1));
+        assert!(switch(&mut current, 2));
+        assert_eq!(current, Some(2));
+    }
+
+    #[test]
+    fn reset_turns_depth_writes_back_on_for_the_next_clear() {
+        // glass is drawn last and leaves depth writes off
+        let mut cache = StateCache { draw_state: Some(DrawState::TRANSLUCENT), ..StateCache::default() };
+
+        cache.forget();
+
+        assert!(changed(cache.draw_state, DrawState::DEFAULT, |s| s.depth_write));
+        assert!(changed(cache.draw_state, DrawState::DEFAULT, |s| s.blend));
+        assert_eq!(DrawState::DEFAULT, DrawState { cull_face: true, blend: false, depth_write: true });
+    }
+}
@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::shader::{ProgramSource, Shader};
+use crate::shader::error::ShaderError;
+use crate::shader::preprocessor::Defines;
+
+/// Compiled programs by name and defines, every variant is compiled once, when it's first needed
+#[derive(Default)]
+pub struct ShaderCache {
+    shaders: HashMap<(&'static str, Defines), Rc<Shader>>,
+}
+
+impl ShaderCache {
+    pub fn new() -> Self {
+        ShaderCache::default()
+    }
+
+    /// Variants that fail to compile aren't cached, asking for them again retries the compilation
+    pub fn get(&mut self, gl: &GL, source: &ProgramSource, defines: &Defines) -> Result<Rc<Shader>, ShaderError> {
+        let key = (source.name, defines.clone());
+        if let Some(shader) = self.shaders.get(&key) {
+            return Ok(shader.clone());
+        }
+        let shader = Rc::new(Shader::new(gl, source, defines)?);
+        self.shaders.insert(key, shader.clone());
+        Ok(shader)
+    }
+}
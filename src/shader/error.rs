@@ -0,0 +1,150 @@
+use std::fmt;
+
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::shader::preprocessor::{PreprocessError, Preprocessed};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+}
+
+impl Stage {
+    pub fn gl_type(self) -> u32 {
+        match self {
+            Stage::Vertex => GL::VERTEX_SHADER,
+            Stage::Fragment => GL::FRAGMENT_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Vertex => write!(f, "vertex"),
+            Stage::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+/// Message of the driver about a single line, with the line itself if it could be found
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    /// Line numbers start with 1, like in editors
+    pub line: usize,
+    pub message: String,
+    pub source: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)?;
+        if let Some(source) = &self.source {
+            write!(f, "\n    {}", source.trim())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ShaderError {
+    Preprocess(PreprocessError),
+    /// `diagnostics` are the lines of `log` that point into the source, they can be empty with unusual drivers
+    Compile { program: String, stage: Stage, log: String, diagnostics: Vec<Diagnostic> },
+    Link { program: String, log: String },
+    VertexLayout { program: String, message: String },
+}
+
+impl ShaderError {
+    pub fn compile(program: &str, stage: Stage, log: String, source: &Preprocessed) -> Self {
+        let diagnostics = diagnostics(&log, source);
+        ShaderError::Compile { program: program.to_string(), stage, log, diagnostics }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Preprocess(e) => write!(f, "{}", e),
+            ShaderError::Compile { program, stage, log, diagnostics } => {
+                write!(f, "{} shader of the {} program does not compile:", stage, program)?;
+                if diagnostics.is_empty() {
+                    return write!(f, "\n{}", log.trim());
+                }
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            ShaderError::Link { program, log } => write!(f, "{} program does not link:\n{}", program, log.trim()),
+            ShaderError::VertexLayout { program, message } => write!(f, "{} program doesn't match vertex layouts: {}", program, message),
+        }
+    }
+}
+
+impl From<PreprocessError> for ShaderError {
+    fn from(e: PreprocessError) -> Self {
+        ShaderError::Preprocess(e)
+    }
+}
+
+/// Parses lines like `ERROR: 1:12: 'colour' : undeclared identifier`, the first number is the index of the file
+/// set by `#line`, so it's mapped back to the file name
+fn diagnostics(log: &str, source: &Preprocessed) -> Vec<Diagnostic> {
+    log.lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("ERROR:").or_else(|| line.strip_prefix("WARNING:"))?;
+            let mut parts = rest.splitn(3, ':');
+            let file = parts.next()?.trim().parse::<usize>().ok()?;
+            let number = parts.next()?.trim().parse::<usize>().ok()?;
+            let message = parts.next()?.trim().to_string();
+            Some(Diagnostic {
+                file: source.files.get(file).cloned().unwrap_or_else(|| format!("file {}", file)),
+                line: number,
+                message,
+                source: source.line(file, number).map(String::from),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shader::error::{Diagnostic, diagnostics, ShaderError, Stage};
+    use crate::shader::preprocessor::{Defines, preprocess};
+
+    const FRAGMENT: &str = "#version 300 es\nprecision highp float;\n#include \"camera.glsl\"\nout vec4 FragColor;\nvoid main() {\n    FragColor = colour;\n}\n";
+
+    #[test]
+    fn maps_driver_messages_to_files_and_lines() {
+        let source = preprocess("broken.frag", FRAGMENT, &Defines::new().with("MAX_LIGHTS", 4)).unwrap();
+        let log = "ERROR: 0:6: 'colour' : undeclared identifier\nERROR: 1:2: 'cameraPosition' : redefinition\nERROR: 2 compilation errors.  No code generated.\n";
+
+        let diagnostics = diagnostics(log, &source);
+
+        assert_eq!(diagnostics, vec![
+            Diagnostic { file: "broken.frag".to_string(), line: 6, message: "'colour' : undeclared identifier".to_string(), source: Some("    FragColor = colour;".to_string()) },
+            Diagnostic { file: "camera.glsl".to_string(), line: 2, message: "'cameraPosition' : redefinition".to_string(), source: Some("    vec3 cameraPosition;".to_string()) },
+        ]);
+    }
+
+    #[test]
+    fn shows_offending_lines() {
+        let source = preprocess("broken.frag", FRAGMENT, &Defines::new()).unwrap();
+
+        let error = ShaderError::compile("broken", Stage::Fragment, "ERROR: 0:6: 'colour' : undeclared identifier\n".to_string(), &source);
+
+        assert_eq!(error.to_string(), "fragment shader of the broken program does not compile:\nbroken.frag:6: 'colour' : undeclared identifier\n    FragColor = colour;");
+    }
+
+    #[test]
+    fn shows_the_whole_log_when_it_has_no_line_numbers() {
+        let source = preprocess("broken.frag", FRAGMENT, &Defines::new()).unwrap();
+
+        let error = ShaderError::compile("broken", Stage::Vertex, "Out of memory\n".to_string(), &source);
+
+        assert_eq!(error.to_string(), "vertex shader of the broken program does not compile:\nOut of memory");
+    }
+}
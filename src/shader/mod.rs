@@ -0,0 +1,200 @@
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::gpu::{Program, ShaderObject};
+use crate::lights::MAX_LIGHTS;
+use crate::material::MAX_MATERIALS;
+use crate::mesh::layout::{ActiveAttribute, VertexFormat, VertexLayout};
+use crate::mesh::Vertex;
+use crate::model::Instance;
+use crate::shader::error::{ShaderError, Stage};
+use crate::shader::preprocessor::{Defines, preprocess, Preprocessed};
+
+pub mod cache;
+pub mod error;
+pub mod preprocessor;
+#[cfg(test)]
+pub mod validation;
+
+pub const CAMERA_UBO_BINDING_POINT: u32 = 0;
+pub const LIGHTS_UBO_BINDING_POINT: u32 = 1;
+pub const MATERIALS_UBO_BINDING_POINT: u32 = 2;
+pub const FOG_UBO_BINDING_POINT: u32 = 3;
+
+pub const DIFFUSE_TEXTURE_UNIT: u32 = 0;
+pub const NORMAL_TEXTURE_UNIT: u32 = 1;
+pub const EMISSIVE_TEXTURE_UNIT: u32 = 2;
+pub const MATERIALS_TEXTURE_UNIT: u32 = 3;
+pub const ENVIRONMENT_TEXTURE_UNIT: u32 = 4;
+
+/// Vertex and fragment shader sources of a program, before preprocessing
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ProgramSource {
+    pub name: &'static str,
+    pub vertex: &'static str,
+    pub fragment: &'static str,
+}
+
+/// Phong lighting with materials and textures, used by all models
+pub const STANDARD_PROGRAM: ProgramSource = ProgramSource {
+    name: "standard",
+    vertex: include_str!("../../shaders/standard.vert"),
+    fragment: include_str!("../../shaders/standard.frag"),
+};
+
+/// Material colors without lights or textures, for when the standard program doesn't work on the device
+pub const UNLIT_PROGRAM: ProgramSource = ProgramSource {
+    name: "unlit",
+    vertex: include_str!("../../shaders/unlit.vert"),
+    fragment: include_str!("../../shaders/unlit.frag"),
+};
+
+/// Gradient of the sky with stars, the sun and the moon, drawn on a dome around the camera
+pub const SKY_PROGRAM: ProgramSource = ProgramSource {
+    name: "sky",
+    vertex: include_str!("../../shaders/sky.vert"),
+    fragment: include_str!("../../shaders/sky.frag"),
+};
+
+/// Glowing curtains of the aurora, animated with noise
+pub const AURORA_PROGRAM: ProgramSource = ProgramSource {
+    name: "aurora",
+    vertex: include_str!("../../shaders/aurora.vert"),
+    fragment: include_str!("../../shaders/aurora.frag"),
+};
+
+/// Optional features of the standard program, each one is a `#define` checked with `#ifdef`
+pub const NORMAL_MAPPING: &str = "NORMAL_MAPPING";
+/// Reflective materials mirror the environment cubemap
+pub const REFLECTIONS: &str = "REFLECTIONS";
+pub const FEATURES: &[&str] = &[NORMAL_MAPPING, REFLECTIONS];
+/// Not a feature users turn on, pipelines drawing thin surfaces set it
+pub const DOUBLE_SIDED: &str = "DOUBLE_SIDED";
+
+/// Defines of a variant together with the ones every program gets
+pub fn program_defines(defines: &Defines) -> Defines {
+    // array sizes are shared with the Rust side, shaders don't hardcode them
+    defines.clone().with("MAX_LIGHTS", MAX_LIGHTS).with("MAX_MATERIALS", MAX_MATERIALS)
+}
+
+pub struct Shader {
+    pub program: Program,
+}
+
+impl Shader {
+    /// Compiles a variant of the program with the given defines, on top of the ones every program gets
+    pub fn new(gl: &GL, source: &ProgramSource, defines: &Defines) -> Result<Shader, ShaderError> {
+        let defines = program_defines(defines);
+        let vertex_source = preprocess(&format!("{}.vert", source.name), source.vertex, &defines)?;
+        let fragment_source = preprocess(&format!("{}.frag", source.name), source.fragment, &defines)?;
+
+        let program = Program::new(gl);
+        let shader = Shader { program };
+        // compiled stages are only needed for linking, they get deleted as soon as they go out of scope
+        let vertex_shader = shader.add_shader(gl, source.name, Stage::Vertex, &vertex_source)?;
+        let fragment_shader = shader.add_shader(gl, source.name, Stage::Fragment, &fragment_source)?;
+        gl.link_program(&shader.program);
+        gl.detach_shader(&shader.program, &vertex_shader);
+        gl.detach_shader(&shader.program, &fragment_shader);
+
+        let success = gl
+            .get_program_parameter(&shader.program, GL::LINK_STATUS)
+            .as_bool()
+            .expect("Kaboom, Cannot cast linking result status to boolean");
+        if !success {
+            let log = gl.get_program_info_log(&shader.program)
+                .expect("Cannot get info log");
+            return Err(ShaderError::Link { program: source.name.to_string(), log });
+        }
+
+        VertexLayout::validate(&[&Vertex::layout(), &Instance::layout()], &shader.active_attributes(gl))
+            .map_err(|message| ShaderError::VertexLayout { program: source.name.to_string(), message })?;
+
+        shader.bind_camera_ubo(gl);
+        shader.bind_lights_ubo(gl);
+        shader.bind_materials_ubo(gl);
+        shader.bind_fog_ubo(gl);
+        shader.bind_texture_units(gl);
+
+        Ok(shader)
+    }
+
+    fn add_shader(&self, gl: &GL, program: &str, stage: Stage, source: &Preprocessed) -> Result<ShaderObject, ShaderError> {
+        let shader = ShaderObject::new(gl, stage.gl_type());
+        gl.shader_source(&shader, &source.source);
+        gl.compile_shader(&shader);
+        let success = gl
+            .get_shader_parameter(&shader, GL::COMPILE_STATUS)
+            .as_bool()
+            .expect("Kaboom, Cannot cast compilation result status to boolean");
+        if !success {
+            let log = gl.get_shader_info_log(&shader)
+                .expect("Cannot get info log");
+            return Err(ShaderError::compile(program, stage, log, source));
+        }
+        gl.attach_shader(&self.program, &shader);
+        Ok(shader)
+    }
+
+    /// Sets a uniform outside of blocks, the program has to be in use
+    pub fn set_int(&self, gl: &GL, name: &str, value: i32) {
+        gl.uniform1i(gl.get_uniform_location(&self.program, name).as_ref(), value);
+    }
+
+    pub fn set_float(&self, gl: &GL, name: &str, value: f32) {
+        gl.uniform1f(gl.get_uniform_location(&self.program, name).as_ref(), value);
+    }
+
+    pub fn set_vec2(&self, gl: &GL, name: &str, x: f32, y: f32) {
+        gl.uniform2f(gl.get_uniform_location(&self.program, name).as_ref(), x, y);
+    }
+
+    pub fn set_vec3(&self, gl: &GL, name: &str, value: [f32; 3]) {
+        gl.uniform3f(gl.get_uniform_location(&self.program, name).as_ref(), value[0], value[1], value[2]);
+    }
+
+    fn active_attributes(&self, gl: &GL) -> Vec<ActiveAttribute> {
+        let count = gl.get_program_parameter(&self.program, GL::ACTIVE_ATTRIBUTES).as_f64().unwrap() as u32;
+        (0..count)
+            .filter_map(|i| gl.get_active_attrib(&self.program, i))
+            .map(|info| ActiveAttribute {
+                location: gl.get_attrib_location(&self.program, &info.name()),
+                name: info.name(),
+                gl_type: info.type_(),
+            })
+            .collect()
+    }
+
+    fn bind_camera_ubo(&self, gl: &GL) {
+        self.bind_ubo(gl, "Camera", CAMERA_UBO_BINDING_POINT);
+    }
+
+    fn bind_lights_ubo(&self, gl: &GL) {
+        self.bind_ubo(gl, "Lights", LIGHTS_UBO_BINDING_POINT);
+    }
+
+    fn bind_materials_ubo(&self, gl: &GL) {
+        self.bind_ubo(gl, "Materials", MATERIALS_UBO_BINDING_POINT);
+    }
+
+    fn bind_fog_ubo(&self, gl: &GL) {
+        self.bind_ubo(gl, "Fog", FOG_UBO_BINDING_POINT);
+    }
+
+    /// Simpler programs don't use every block
+    fn bind_ubo(&self, gl: &GL, name: &str, binding_point: u32) {
+        let uniform_block_index = gl.get_uniform_block_index(&self.program, name);
+        if uniform_block_index != GL::INVALID_INDEX {
+            gl.uniform_block_binding(&self.program, uniform_block_index, binding_point);
+        }
+    }
+
+    fn bind_texture_units(&self, gl: &GL) {
+        gl.use_program(Some(&*self.program));
+        gl.uniform1i(gl.get_uniform_location(&self.program, "diffuseMap").as_ref(), DIFFUSE_TEXTURE_UNIT as i32);
+        gl.uniform1i(gl.get_uniform_location(&self.program, "normalMap").as_ref(), NORMAL_TEXTURE_UNIT as i32);
+        gl.uniform1i(gl.get_uniform_location(&self.program, "emissiveMap").as_ref(), EMISSIVE_TEXTURE_UNIT as i32);
+        gl.uniform1i(gl.get_uniform_location(&self.program, "materialsOverflow").as_ref(), MATERIALS_TEXTURE_UNIT as i32);
+        gl.uniform1i(gl.get_uniform_location(&self.program, "environment").as_ref(), ENVIRONMENT_TEXTURE_UNIT as i32);
+        gl.use_program(None);
+    }
+}
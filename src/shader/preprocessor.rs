@@ -0,0 +1,257 @@
+//! Minimal GLSL preprocessing done before the driver sees the source: `#include "chunk.glsl"` of shared chunks
+//! and `#define`s of the variant. Conditionals like `#ifdef` are left to the GLSL preprocessor itself.
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Chunks shared by shaders, embedded at build time
+static CHUNKS: &[(&str, &str)] = &[
+    ("camera.glsl", include_str!("../../shaders/include/camera.glsl")),
+    ("lights.glsl", include_str!("../../shaders/include/lights.glsl")),
+    ("fog.glsl", include_str!("../../shaders/include/fog.glsl")),
+    ("materials.glsl", include_str!("../../shaders/include/materials.glsl")),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum PreprocessError {
+    /// Line numbers start with 1, like in editors
+    UnknownInclude { file: String, line: usize, name: String },
+    MalformedInclude { file: String, line: usize },
+    MissingVersion { file: String },
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessError::UnknownInclude { file, line, name } => write!(f, "{}:{}: unknown include \"{}\"", file, line, name),
+            PreprocessError::MalformedInclude { file, line } => write!(f, "{}:{}: expected #include \"name\"", file, line),
+            PreprocessError::MissingVersion { file } => write!(f, "{}: has to start with #version", file),
+        }
+    }
+}
+
+/// Macros defined for a single variant of a program, ordered so that equal sets compare and hash equally
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Defines(BTreeMap<String, String>);
+
+impl Defines {
+    pub fn new() -> Self {
+        Defines::default()
+    }
+
+    /// Macro with a value, like `#define MAX_LIGHTS 4`
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.0.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Feature flag, checked in shaders with `#ifdef`
+    pub fn flag(self, name: &str) -> Self {
+        self.with(name, "")
+    }
+
+    pub fn without(mut self, name: &str) -> Self {
+        self.0.remove(name);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+}
+
+/// Lists the macros like `FOG, MAX_LIGHTS=4`
+impl fmt::Display for Defines {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let defines = self.0.iter()
+            .map(|(name, value)| if value.is_empty() { name.clone() } else { format!("{}={}", name, value) })
+            .collect::<Vec<_>>();
+        write!(f, "{}", defines.join(", "))
+    }
+}
+
+/// Source ready for compilation, with the names of all files it was put together from.
+/// `#line` directives number the files, so the driver reports errors as `file index:line`.
+#[derive(Debug)]
+pub struct Preprocessed {
+    pub source: String,
+    pub files: Vec<String>,
+}
+
+impl Preprocessed {
+    /// Which file is which in driver messages, e.g. `0: standard.frag, 1: camera.glsl`
+    pub fn legend(&self) -> String {
+        self.files.iter().enumerate().map(|(i, file)| format!("{}: {}", i, file)).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Text of the line as the driver numbers it, following the `#line` directives
+    pub fn line(&self, file: usize, line: usize) -> Option<&str> {
+        let mut position = (0, 1);
+        let mut found = None;
+        for text in self.source.lines() {
+            if let Some(directive) = text.strip_prefix("#line ") {
+                let mut numbers = directive.split_whitespace().filter_map(|n| n.parse().ok());
+                if let (Some(number), Some(index)) = (numbers.next(), numbers.next()) {
+                    position = (index, number);
+                    continue;
+                }
+            }
+            // defines take the numbers of the first lines until `#line` resets them, the last match is the real one
+            if position == (file, line) {
+                found = Some(text);
+            }
+            position.1 += 1;
+        }
+        found
+    }
+}
+
+/// Resolves includes and puts the defines right after `#version`, which has to stay the first line
+pub fn preprocess(file: &str, source: &str, defines: &Defines) -> Result<Preprocessed, PreprocessError> {
+    preprocess_with(file, source, defines, |name| CHUNKS.iter().find(|(chunk, _)| *chunk == name).map(|(_, source)| *source))
+}
+
+fn preprocess_with<'a>(file: &str, source: &str, defines: &Defines, chunks: impl Fn(&str) -> Option<&'a str>) -> Result<Preprocessed, PreprocessError> {
+    let mut lines = source.lines();
+    let version = lines.next().filter(|line| line.trim_start().starts_with("#version"))
+        .ok_or_else(|| PreprocessError::MissingVersion { file: file.to_string() })?;
+
+    let mut output = Output { source: format!("{}\n", version), files: vec![file.to_string()] };
+    for (name, value) in &defines.0 {
+        output.source.push_str(&format!("#define {} {}\n", name, value).replace(" \n", "\n"));
+    }
+    output.source.push_str("#line 2 0\n");
+    output.append(0, lines, 2, &chunks)?;
+    Ok(Preprocessed { source: output.source, files: output.files })
+}
+
+struct Output {
+    source: String,
+    files: Vec<String>,
+}
+
+impl Output {
+    /// Appends lines of the file with the given index, `first_line` is the number of the first one of them
+    fn append<'a, 'b>(&mut self, index: usize, lines: impl Iterator<Item=&'b str>, first_line: usize, chunks: &impl Fn(&str) -> Option<&'a str>) -> Result<(), PreprocessError> {
+        for (number, line) in (first_line..).zip(lines) {
+            let directive = line.trim_start();
+            if !directive.starts_with("#include") {
+                self.source.push_str(line);
+                self.source.push('\n');
+                continue;
+            }
+            let file = &self.files[index];
+            let name = directive["#include".len()..].trim();
+            let name = name.strip_prefix('"').and_then(|name| name.strip_suffix('"'))
+                .ok_or_else(|| PreprocessError::MalformedInclude { file: file.clone(), line: number })?;
+            // every chunk is included at most once, like with include guards, which also stops include cycles
+            if self.files.iter().any(|f| f == name) {
+                continue;
+            }
+            let chunk = chunks(name).ok_or_else(|| PreprocessError::UnknownInclude { file: file.clone(), line: number, name: name.to_string() })?;
+            self.files.push(name.to_string());
+            let chunk_index = self.files.len() - 1;
+            self.source.push_str(&format!("#line 1 {}\n", chunk_index));
+            self.append(chunk_index, chunk.lines(), 1, chunks)?;
+            self.source.push_str(&format!("#line {} {}\n", number + 1, index));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shader::preprocessor::{Defines, preprocess, preprocess_with, PreprocessError};
+
+    fn chunks(name: &str) -> Option<&'static str> {
+        match name {
+            "a.glsl" => Some("float a;\n#include \"b.glsl\"\n"),
+            "b.glsl" => Some("float b;\n"),
+            "cycle.glsl" => Some("#include \"cycle.glsl\"\nfloat c;\n"),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn puts_defines_after_version() {
+        let defines = Defines::new().with("MAX_LIGHTS", 4).flag("FOG");
+
+        let result = preprocess_with("main.frag", "#version 300 es\nvoid main() {}\n", &defines, chunks).unwrap();
+
+        assert_eq!(result.source, "#version 300 es\n#define FOG\n#define MAX_LIGHTS 4\n#line 2 0\nvoid main() {}\n");
+        assert_eq!(result.files, vec!["main.frag"]);
+    }
+
+    #[test]
+    fn includes_nested_chunks_with_line_directives() {
+        let source = "#version 300 es\n#include \"a.glsl\"\nvoid main() {}\n";
+
+        let result = preprocess_with("main.frag", source, &Defines::new(), chunks).unwrap();
+
+        assert_eq!(result.source, "#version 300 es\n#line 2 0\n\
+            #line 1 1\nfloat a;\n#line 1 2\nfloat b;\n#line 3 1\n#line 3 0\nvoid main() {}\n");
+        assert_eq!(result.files, vec!["main.frag", "a.glsl", "b.glsl"]);
+        assert_eq!(result.legend(), "0: main.frag, 1: a.glsl, 2: b.glsl");
+    }
+
+    #[test]
+    fn includes_every_chunk_once() {
+        let source = "#version 300 es\n#include \"b.glsl\"\n#include \"a.glsl\"\n#include \"cycle.glsl\"\n";
+
+        let result = preprocess_with("main.frag", source, &Defines::new(), chunks).unwrap();
+
+        assert_eq!(result.source.matches("float b;").count(), 1);
+        assert_eq!(result.source.matches("float c;").count(), 1);
+    }
+
+    #[test]
+    fn finds_lines_by_file_and_number() {
+        let source = "#version 300 es\n#include \"a.glsl\"\nvoid main() {}\n";
+
+        let result = preprocess_with("main.frag", source, &Defines::new().flag("FOG"), chunks).unwrap();
+
+        assert_eq!(result.line(0, 1), Some("#version 300 es"));
+        assert_eq!(result.line(0, 3), Some("void main() {}"));
+        assert_eq!(result.line(1, 1), Some("float a;"));
+        assert_eq!(result.line(2, 1), Some("float b;"));
+        assert_eq!(result.line(2, 2), None);
+    }
+
+    #[test]
+    fn reports_unknown_and_malformed_includes() {
+        let unknown = preprocess_with("main.frag", "#version 300 es\n\n#include \"c.glsl\"\n", &Defines::new(), chunks);
+        let malformed = preprocess_with("main.frag", "#version 300 es\n#include <a.glsl>\n", &Defines::new(), chunks);
+
+        assert_eq!(unknown.unwrap_err(), PreprocessError::UnknownInclude { file: "main.frag".to_string(), line: 3, name: "c.glsl".to_string() });
+        assert_eq!(malformed.unwrap_err(), PreprocessError::MalformedInclude { file: "main.frag".to_string(), line: 2 });
+    }
+
+    #[test]
+    fn requires_version_first() {
+        let result = preprocess_with("main.frag", "precision highp float;\n", &Defines::new(), chunks);
+
+        assert_eq!(result.unwrap_err(), PreprocessError::MissingVersion { file: "main.frag".to_string() });
+    }
+
+    #[test]
+    fn variants_with_the_same_defines_are_equal() {
+        assert_eq!(Defines::new().flag("A").with("B", 1), Defines::new().with("B", 1).flag("A"));
+        assert_ne!(Defines::new().flag("A"), Defines::new());
+        assert_eq!(Defines::new().flag("A").without("A"), Defines::new());
+    }
+
+    #[test]
+    fn displays_defines_with_values() {
+        assert_eq!(Defines::new().with("MAX_LIGHTS", 4).flag("FOG").to_string(), "FOG, MAX_LIGHTS=4");
+    }
+
+    #[test]
+    fn embedded_chunks_resolve() {
+        let source = "#version 300 es\n#include \"camera.glsl\"\n#include \"lights.glsl\"\n#include \"materials.glsl\"\n";
+
+        let result = preprocess("main.frag", source, &Defines::new()).unwrap();
+
+        assert!(result.source.contains("uniform Camera"));
+        assert!(result.source.contains("uniform Lights"));
+        assert!(result.source.contains("uniform Materials"));
+    }
+}
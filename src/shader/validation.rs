@@ -0,0 +1,429 @@
+//! Native checks of the shaders with naga, so mistakes fail `cargo test` instead of panicking in the browser.
+//! naga only reads Vulkan flavoured desktop GLSL, the preprocessed sources are translated line by line first,
+//! which keeps line numbers of diagnostics the same as in the source the driver gets.
+use std::collections::HashMap;
+
+use naga::{AddressSpace, ArraySize, Binding, Handle, Module, ShaderStage, Type, TypeInner};
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+use crate::render::Pipeline;
+use crate::render::post::{BLUR_PROGRAM, BRIGHT_PROGRAM, composite_defines, COMPOSITE_PROGRAM, EFFECTS, FXAA_PROGRAM};
+use crate::shader::{FEATURES, program_defines, ProgramSource, STANDARD_PROGRAM, UNLIT_PROGRAM};
+use crate::shader::preprocessor::{Defines, preprocess, Preprocessed};
+
+/// Vulkan GLSL has no uniforms outside of blocks, each one gets a block of its own after the application's blocks
+const FIRST_LOOSE_UNIFORM_BINDING: u32 = 8;
+/// Textures and samplers are separate in Vulkan GLSL, they get bindings after the uniform blocks
+const FIRST_SAMPLER_BINDING: u32 = 16;
+
+/// std140 layout of a uniform block as the Rust side writes it, members are flattened to paths like `light[1].diffuse`
+#[derive(Debug, PartialEq)]
+pub struct UniformBlock {
+    pub name: String,
+    pub binding: u32,
+    pub size: u32,
+    pub members: Vec<(String, u32)>,
+}
+
+impl UniformBlock {
+    pub fn new(name: &str, binding: u32, size: i32) -> Self {
+        UniformBlock { name: name.to_string(), binding, size: size as u32, members: vec![] }
+    }
+
+    pub fn member(mut self, path: &str, offset: i32) -> Self {
+        self.members.push((path.to_string(), offset as u32));
+        self
+    }
+}
+
+fn rust_layouts() -> Vec<UniformBlock> {
+    vec![crate::camera::ubo_layout(), crate::lights::ubo_layout(), crate::material::ubo_layout(), crate::fog::ubo_layout()]
+}
+
+/// Both stages of a program variant, parsed and validated
+struct Compiled {
+    vertex: Module,
+    fragment: Module,
+}
+
+/// All combinations of the optional features
+fn feature_sets(features: &[&str]) -> Vec<Defines> {
+    (0..1 << features.len())
+        .map(|mask: u32| features.iter().enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .fold(Defines::new(), |defines, (_, feature)| defines.flag(feature)))
+        .collect()
+}
+
+/// Every program variant the application can compile, for any features turned on,
+/// including the unlit program lit pipelines fall back to and the post-processing passes for any effects
+fn variants() -> Vec<(ProgramSource, Defines)> {
+    let mut variants: Vec<(ProgramSource, Defines)> = vec![];
+    for features in feature_sets(FEATURES) {
+        for pipeline in Pipeline::ALL.iter() {
+            let program = pipeline.program();
+            let fallback = if program == STANDARD_PROGRAM { Some(UNLIT_PROGRAM) } else { None };
+            for program in Some(program).into_iter().chain(fallback) {
+                let variant = (program, pipeline.defines(&features));
+                if !variants.contains(&variant) {
+                    variants.push(variant);
+                }
+            }
+        }
+    }
+    for effects in feature_sets(EFFECTS) {
+        let variant = (COMPOSITE_PROGRAM, composite_defines(&effects));
+        if !variants.contains(&variant) {
+            variants.push(variant);
+        }
+    }
+    for program in [BRIGHT_PROGRAM, BLUR_PROGRAM, FXAA_PROGRAM] {
+        variants.push((program, Defines::new()));
+    }
+    variants
+}
+
+fn compile(program: &ProgramSource, defines: &Defines) -> Result<Compiled, String> {
+    let defines = program_defines(defines);
+    let vertex = preprocess(&format!("{}.vert", program.name), program.vertex, &defines).map_err(|e| e.to_string())?;
+    let fragment = preprocess(&format!("{}.frag", program.name), program.fragment, &defines).map_err(|e| e.to_string())?;
+    let varyings = declarations(&vertex.source).into_iter()
+        .filter(|d| d.layout.is_none() && d.qualifiers.contains(&"out"))
+        .map(|d| d.name)
+        .collect::<Vec<_>>();
+    let vertex = parse(ShaderStage::Vertex, &vertex, &varyings)?;
+    let fragment = parse(ShaderStage::Fragment, &fragment, &varyings)?;
+    Ok(Compiled { vertex, fragment })
+}
+
+fn parse(stage: ShaderStage, source: &Preprocessed, varyings: &[String]) -> Result<Module, String> {
+    let file = &source.files[0];
+    let translated = desktop_dialect(stage, &source.source, varyings).map_err(|e| format!("{}: {}", file, e))?;
+    let module = Frontend::default().parse(&Options::from(stage), &translated)
+        .map_err(|e| format!("{} does not compile ({}):\n{}", file, source.legend(), e.emit_to_string(&translated)))?;
+    Validator::new(ValidationFlags::all(), Capabilities::all()).validate(&module)
+        .map_err(|e| format!("{} is invalid ({}):\n{}", file, source.legend(), e.emit_to_string(&translated)))?;
+    Ok(module)
+}
+
+/// Declaration of a global variable or a block member, the interesting part of it for translation
+#[derive(Debug)]
+struct Declaration<'a> {
+    layout: Option<&'a str>,
+    qualifiers: Vec<&'a str>,
+    ty: &'a str,
+    name: String,
+}
+
+fn declaration(line: &str) -> Option<Declaration<'_>> {
+    let line = line.trim().strip_suffix(';')?;
+    let (layout, rest) = if line.starts_with("layout") {
+        let end = line.find(')')?;
+        (Some(&line[..=end]), &line[end + 1..])
+    } else {
+        (None, line)
+    };
+    if rest.contains(['(', '=', '[']) {
+        return None;
+    }
+    let mut tokens = rest.split_whitespace().collect::<Vec<_>>();
+    let name = tokens.pop()?.to_string();
+    let ty = tokens.pop()?;
+    Some(Declaration { layout, qualifiers: tokens, ty, name })
+}
+
+fn declarations(source: &str) -> Vec<Declaration<'_>> {
+    source.lines().filter_map(declaration).filter(|d| !d.qualifiers.is_empty()).collect()
+}
+
+/// Replaces whole identifiers only, `view` in `viewPos` stays
+fn replace_identifier(line: &str, name: &str, with: &str) -> String {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(name) {
+        let end = start + name.len();
+        let before = rest[..start].chars().last().is_some_and(is_identifier);
+        let after = rest[end..].chars().next().is_some_and(is_identifier);
+        result.push_str(&rest[..start]);
+        result.push_str(if before || after { name } else { with });
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Translates GLSL ES 3.00 to Vulkan GLSL 4.50 naga understands, one line to one line:
+/// uniform blocks get bindings, other uniforms blocks of their own, samplers are split to textures and samplers,
+/// `mat4` attributes to columns and varyings get locations, matched by name between the stages as WebGL does
+fn desktop_dialect(stage: ShaderStage, source: &str, varyings: &[String]) -> Result<String, String> {
+    let layouts = rust_layouts();
+    let mut replacements = vec![];
+    let mut loose_uniforms = 0;
+    let mut fragment_outputs = 0;
+    let mut lines = vec![];
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("#version") {
+            lines.push("#version 450".to_string());
+            continue;
+        }
+        if trimmed.starts_with("layout") && trimmed.contains("uniform") && trimmed.ends_with('{') {
+            let name = trimmed.split_whitespace().rev().nth(1).unwrap_or_default();
+            let block = layouts.iter().find(|block| block.name == name)
+                .ok_or_else(|| format!("uniform block {} has no layout on the Rust side", name))?;
+            lines.push(line.replace("std140", &format!("std140, binding = {}", block.binding)));
+            continue;
+        }
+        let declaration = match declaration(line) {
+            Some(declaration) if !declaration.qualifiers.is_empty() => declaration,
+            _ => {
+                lines.push(line.to_string());
+                continue;
+            }
+        };
+        let name = &declaration.name;
+        let storage = declaration.qualifiers.iter().find(|q| ["in", "out", "uniform"].contains(q)).copied();
+        let translated = match (storage, declaration.ty) {
+            (Some("uniform"), sampler @ ("sampler2D" | "samplerCube")) => {
+                let binding = FIRST_SAMPLER_BINDING + 2 * replacements.len() as u32;
+                let texture = sampler.replace("sampler", "texture");
+                replacements.push((name.clone(), format!("{1}({0}_texture, {0}_sampler)", name, sampler)));
+                format!("layout (binding = {}) uniform {} {3}_texture; layout (binding = {}) uniform sampler {3}_sampler;", binding, texture, binding + 1, name)
+            }
+            (Some("uniform"), ty) if declaration.layout.is_none() => {
+                loose_uniforms += 1;
+                format!("layout (std140, binding = {}) uniform {}_block {{ {} {}; }};", FIRST_LOOSE_UNIFORM_BINDING + loose_uniforms - 1, name, ty, name)
+            }
+            (Some("in"), "mat4") if stage == ShaderStage::Vertex => {
+                let layout = declaration.layout.ok_or_else(|| format!("attribute {} has no location", name))?;
+                let location = layout.trim_end_matches(')').rsplit('=').next().and_then(|l| l.trim().parse::<u32>().ok())
+                    .ok_or_else(|| format!("attribute {} has no location", name))?;
+                replacements.push((name.clone(), format!("mat4({0}_0, {0}_1, {0}_2, {0}_3)", name)));
+                (0..4).map(|i| format!("layout (location = {}) in vec4 {}_{};", location + i, name, i)).collect::<Vec<_>>().join(" ")
+            }
+            (Some("out"), _) if stage == ShaderStage::Fragment && declaration.layout.is_none() => {
+                fragment_outputs += 1;
+                format!("layout (location = {}) {}", fragment_outputs - 1, trimmed)
+            }
+            (Some("in"), _) | (Some("out"), _) if declaration.layout.is_none() => {
+                let location = varyings.iter().position(|v| v == name)
+                    .ok_or_else(|| format!("{} is read by the fragment shader, but the vertex shader doesn't write it", name))?;
+                format!("layout (location = {}) {}", location, trimmed)
+            }
+            _ => line.to_string(),
+        };
+        lines.push(translated);
+    }
+
+    // Vulkan counts vertices with its own built-in, it's the same without base vertices
+    replacements.push(("gl_VertexID".to_string(), "gl_VertexIndex".to_string()));
+
+    // declarations stay as they are, `name_0` or `name_texture` are different identifiers
+    let mut translated = String::new();
+    for line in lines {
+        translated.push_str(&replacements.iter().fold(line, |line, (name, with)| replace_identifier(&line, name, with)));
+        translated.push('\n');
+    }
+    Ok(translated)
+}
+
+/// Uniform blocks of the module flattened to member paths, the same way `UniformBlock` describes them
+fn uniform_blocks(module: &Module) -> Vec<UniformBlock> {
+    module.global_variables.iter()
+        .filter(|(_, variable)| variable.space == AddressSpace::Uniform)
+        .filter_map(|(_, variable)| {
+            let ty = &module.types[variable.ty];
+            let span = match ty.inner {
+                TypeInner::Struct { span, .. } => span,
+                _ => return None,
+            };
+            let name = ty.name.clone().or_else(|| variable.name.clone()).unwrap_or_default();
+            let mut members = vec![];
+            flatten(module, variable.ty, "", 0, &mut members);
+            Some(UniformBlock {
+                name,
+                binding: variable.binding.as_ref().map_or(u32::MAX, |binding| binding.binding),
+                size: span,
+                members,
+            })
+        })
+        .collect()
+}
+
+fn flatten(module: &Module, ty: Handle<Type>, path: &str, offset: u32, members: &mut Vec<(String, u32)>) {
+    match &module.types[ty].inner {
+        TypeInner::Struct { members: fields, .. } => {
+            for field in fields {
+                let name = field.name.clone().unwrap_or_default();
+                let path = if path.is_empty() { name } else { format!("{}.{}", path, name) };
+                flatten(module, field.ty, &path, offset + field.offset, members);
+            }
+        }
+        TypeInner::Array { base, size: ArraySize::Constant(length), stride } => {
+            for i in 0..length.get() {
+                flatten(module, *base, &format!("{}[{}]", path, i), offset + i * stride, members);
+            }
+        }
+        _ => members.push((path.to_string(), offset)),
+    }
+}
+
+/// Differences between the block in the shader and the Rust layout, empty if they match
+fn layout_mismatches(shader: &UniformBlock, rust: &UniformBlock) -> Vec<String> {
+    let mut mismatches = vec![];
+    if shader.size != rust.size {
+        mismatches.push(format!("block takes {} bytes in the shader, {} are allocated", shader.size, rust.size));
+    }
+    let written = rust.members.iter().cloned().collect::<HashMap<_, _>>();
+    for (path, offset) in &shader.members {
+        match written.get(path) {
+            Some(written) if written != offset => mismatches.push(format!("{} is at {} in the shader, but written at {}", path, offset, written)),
+            None => mismatches.push(format!("{} is never written", path)),
+            _ => (),
+        }
+    }
+    let declared = shader.members.iter().map(|(path, _)| path).collect::<Vec<_>>();
+    for (path, _) in &rust.members {
+        if !declared.contains(&path) {
+            mismatches.push(format!("{} is written, but the shader doesn't declare it", path));
+        }
+    }
+    mismatches
+}
+
+/// Locations of varyings with their types, for the vertex outputs or the fragment inputs
+fn interface(module: &Module, stage: ShaderStage) -> Vec<(u32, String)> {
+    let entry = module.entry_points.iter().find(|entry| entry.stage == stage).expect("Module has no entry point");
+    let location = |binding: &Option<Binding>, ty: Handle<Type>| match binding {
+        Some(Binding::Location { location, interpolation, .. }) => Some((*location, format!("{:?} {:?}", module.types[ty].inner, interpolation))),
+        _ => None,
+    };
+    let mut varyings = match stage {
+        ShaderStage::Vertex => {
+            let result = entry.function.result.as_ref().expect("Vertex shader has no outputs");
+            match &module.types[result.ty].inner {
+                TypeInner::Struct { members, .. } => members.iter().filter_map(|m| location(&m.binding, m.ty)).collect(),
+                _ => vec![],
+            }
+        }
+        _ => entry.function.arguments.iter().filter_map(|a| location(&a.binding, a.ty)).collect::<Vec<_>>(),
+    };
+    varyings.sort();
+    varyings
+}
+
+/// Runs the check on every variant of every program and fails with all problems at once
+fn check_variants(check: impl Fn(&ProgramSource, &Defines) -> Result<(), String>) {
+    let failures = variants().into_iter()
+        .filter_map(|(program, defines)| check(&program, &defines).err().map(|e| format!("{} [{}]\n{}", program.name, defines, e)))
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use naga::ShaderStage;
+
+    use crate::shader::{DOUBLE_SIDED, FEATURES, ProgramSource, STANDARD_PROGRAM, UNLIT_PROGRAM};
+    use crate::shader::preprocessor::Defines;
+    use crate::shader::validation::{check_variants, compile, feature_sets, interface, layout_mismatches, replace_identifier, rust_layouts, uniform_blocks, UniformBlock, variants};
+
+    #[test]
+    fn every_variant_of_every_program_validates() {
+        check_variants(|program, defines| compile(program, defines).map(|_| ()));
+    }
+
+    #[test]
+    fn variants_cover_all_feature_combinations_of_all_pipelines() {
+        let variants = variants();
+
+        assert_eq!(feature_sets(FEATURES).len(), 1 << FEATURES.len());
+        for features in feature_sets(FEATURES) {
+            assert!(variants.contains(&(STANDARD_PROGRAM, features.clone())));
+            assert!(variants.contains(&(STANDARD_PROGRAM, features.clone().flag(DOUBLE_SIDED))));
+            // the fallback for lit pipelines
+            assert!(variants.contains(&(UNLIT_PROGRAM, features)));
+        }
+    }
+
+    #[test]
+    fn uniform_blocks_match_rust_layouts() {
+        check_variants(|program, defines| {
+            let compiled = compile(program, defines)?;
+            let mut mismatches = vec![];
+            for (stage, module) in [("vert", &compiled.vertex), ("frag", &compiled.fragment)] {
+                for block in uniform_blocks(module) {
+                    // blocks made up for loose uniforms have no Rust layout, unknown named blocks don't translate at all
+                    let rust = match rust_layouts().into_iter().find(|rust| rust.name == block.name) {
+                        Some(rust) => rust,
+                        None => continue,
+                    };
+                    mismatches.extend(layout_mismatches(&block, &rust).into_iter()
+                        .map(|m| format!("{}.{}, {}: {}", program.name, stage, block.name, m)));
+                }
+            }
+            if mismatches.is_empty() { Ok(()) } else { Err(mismatches.join("\n")) }
+        });
+    }
+
+    #[test]
+    fn fragment_inputs_match_vertex_outputs() {
+        check_variants(|program, defines| {
+            let compiled = compile(program, defines)?;
+            let outputs = interface(&compiled.vertex, ShaderStage::Vertex);
+            let inputs = interface(&compiled.fragment, ShaderStage::Fragment);
+            match inputs.iter().find(|input| !outputs.contains(input)) {
+                Some(input) => Err(format!("fragment input {:?} doesn't match the vertex output", input)),
+                None => Ok(()),
+            }
+        });
+    }
+
+    #[test]
+    fn reports_errors_with_the_offending_line() {
+        let broken = ProgramSource {
+            name: "broken",
+            vertex: "#version 300 es\nvoid main() {\n    gl_Position = vec4(0.0);\n}\n",
+            fragment: "#version 300 es\nprecision highp float;\nout vec4 FragColor;\nvoid main() {\n    FragColor = colour;\n}\n",
+        };
+
+        let error = compile(&broken, &Defines::new()).err().unwrap();
+
+        assert!(error.starts_with("broken.frag does not compile (0: broken.frag)"), "{}", error);
+        assert!(error.contains("FragColor = colour;"), "{}", error);
+    }
+
+    #[test]
+    fn reports_varyings_missing_in_the_vertex_shader() {
+        let broken = ProgramSource {
+            name: "broken",
+            vertex: "#version 300 es\nout vec3 Normal;\nvoid main() {\n    gl_Position = vec4(0.0);\n    Normal = vec3(0.0);\n}\n",
+            fragment: "#version 300 es\nprecision highp float;\nin vec3 Normal;\nin vec2 TexCoords;\nout vec4 FragColor;\nvoid main() {\n    FragColor = vec4(Normal, TexCoords.x);\n}\n",
+        };
+
+        let error = compile(&broken, &Defines::new()).err().unwrap();
+
+        assert_eq!(error, "broken.frag: TexCoords is read by the fragment shader, but the vertex shader doesn't write it");
+    }
+
+    #[test]
+    fn finds_members_at_wrong_offsets() {
+        let shader = UniformBlock::new("Camera", 0, 32).member("position", 0).member("view", 16);
+        let rust = UniformBlock::new("Camera", 0, 28).member("position", 0).member("view", 12).member("zoom", 24);
+
+        let mismatches = layout_mismatches(&shader, &rust);
+
+        assert_eq!(mismatches, vec![
+            "block takes 32 bytes in the shader, 28 are allocated",
+            "view is at 16 in the shader, but written at 12",
+            "zoom is written, but the shader doesn't declare it",
+        ]);
+    }
+
+    #[test]
+    fn replaces_whole_identifiers_only() {
+        assert_eq!(replace_identifier("view * viewPos + preview(view)", "view", "v"), "v * viewPos + preview(v)");
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
+use web_sys::{HtmlImageElement, WebGl2RenderingContext as GL};
+
+use crate::color::ColorSpace;
+use crate::gpu::Texture;
+use crate::material::MaterialTextures;
+use crate::shader::{DIFFUSE_TEXTURE_UNIT, EMISSIVE_TEXTURE_UNIT, NORMAL_TEXTURE_UNIT};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+const JPEG_SIGNATURE: [u8; 3] = [0xff, 0xd8, 0xff];
+
+/// Textures referenced by name that are not embedded are fetched relative to this path
+const TEXTURES_PATH: &str = "models/";
+
+/// Textures shipped inside the binary, they are available right from the first frame
+static EMBEDDED_TEXTURES: &[(&str, &[u8])] = &[
+    ("snow.png", include_bytes!("../models/snow.png")),
+];
+
+pub type TextureId = usize;
+
+#[derive(Debug)]
+pub enum ImageError {
+    UnknownFormat,
+    Png(png::DecodingError),
+    Jpeg(jpeg_decoder::Error),
+    UnsupportedPixelFormat(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::UnknownFormat => write!(f, "unknown image format, only PNG and JPEG are supported"),
+            ImageError::Png(e) => write!(f, "cannot decode PNG image: {}", e),
+            ImageError::Jpeg(e) => write!(f, "cannot decode JPEG image: {}", e),
+            ImageError::UnsupportedPixelFormat(format) => write!(f, "unsupported pixel format: {}", format),
+        }
+    }
+}
+
+/// Decoded image, always 8-bit RGBA with rows stored top to bottom.
+#[derive(Debug)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+        if bytes.starts_with(&PNG_SIGNATURE) {
+            Self::decode_png(bytes)
+        } else if bytes.starts_with(&JPEG_SIGNATURE) {
+            Self::decode_jpeg(bytes)
+        } else {
+            Err(ImageError::UnknownFormat)
+        }
+    }
+
+    fn decode_png(bytes: &[u8]) -> Result<Image, ImageError> {
+        let mut decoder = png::Decoder::new(bytes);
+        // palettes and low bit depths get expanded, 16-bit channels get stripped down to 8 bits
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let (info, mut reader) = decoder.read_info().map_err(ImageError::Png)?;
+        let mut buffer = vec![0; info.buffer_size()];
+        reader.next_frame(&mut buffer).map_err(ImageError::Png)?;
+        let pixels = match info.color_type {
+            png::ColorType::RGBA => buffer,
+            png::ColorType::RGB => Self::rgb_to_rgba(&buffer),
+            png::ColorType::GrayscaleAlpha => buffer.chunks(2).flat_map(|p| vec![p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&l| vec![l, l, l, 255]).collect(),
+            other => return Err(ImageError::UnsupportedPixelFormat(format!("{:?}", other))),
+        };
+        Ok(Image { width: info.width, height: info.height, pixels })
+    }
+
+    fn decode_jpeg(bytes: &[u8]) -> Result<Image, ImageError> {
+        let mut decoder = jpeg_decoder::Decoder::new(bytes);
+        let buffer = decoder.decode().map_err(ImageError::Jpeg)?;
+        let info = decoder.info().expect("JPEG metadata should be available after decoding");
+        let pixels = match info.pixel_format {
+            jpeg_decoder::PixelFormat::RGB24 => Self::rgb_to_rgba(&buffer),
+            jpeg_decoder::PixelFormat::L8 => buffer.iter().flat_map(|&l| vec![l, l, l, 255]).collect(),
+            other => return Err(ImageError::UnsupportedPixelFormat(format!("{:?}", other))),
+        };
+        Ok(Image { width: info.width as u32, height: info.height as u32, pixels })
+    }
+
+    fn rgb_to_rgba(rgb: &[u8]) -> Vec<u8> {
+        rgb.chunks(3).flat_map(|p| vec![p[0], p[1], p[2], 255]).collect()
+    }
+}
+
+/// GL textures bound to the texture units used by a single material, they stay alive as long as any set uses them.
+#[derive(Clone, Default)]
+pub struct TextureSet {
+    diffuse: Option<Rc<Texture>>,
+    normal: Option<Rc<Texture>>,
+    emissive: Option<Rc<Texture>>,
+}
+
+impl TextureSet {
+    /// Sets binding the same textures are equal, no matter which meshes they belong to
+    pub fn key(&self) -> [usize; 3] {
+        let address = |texture: &Option<Rc<Texture>>| texture.as_ref().map_or(0, |t| Rc::as_ptr(t) as usize);
+        [address(&self.diffuse), address(&self.normal), address(&self.emissive)]
+    }
+
+    pub fn bind(&self, gl: &GL) {
+        Self::bind_unit(gl, DIFFUSE_TEXTURE_UNIT, &self.diffuse);
+        Self::bind_unit(gl, NORMAL_TEXTURE_UNIT, &self.normal);
+        Self::bind_unit(gl, EMISSIVE_TEXTURE_UNIT, &self.emissive);
+    }
+
+    fn bind_unit(gl: &GL, unit: u32, texture: &Option<Rc<Texture>>) {
+        gl.active_texture(GL::TEXTURE0 + unit);
+        gl.bind_texture(GL::TEXTURE_2D, texture.as_deref().map(|t| &**t));
+    }
+}
+
+/// Texture cache, every image is uploaded only once, no matter how many materials use it.
+/// An image used both for colors and for normals is uploaded once for each color space.
+pub struct Textures {
+    textures: Vec<Rc<Texture>>,
+    by_name: HashMap<(String, ColorSpace), TextureId>,
+    /// Files of textures decoded from bytes, exported scenes carry them along instead of referencing them
+    encoded: HashMap<String, Vec<u8>>,
+}
+
+impl Textures {
+    pub fn setup() -> Self {
+        Textures { textures: vec![], by_name: HashMap::new(), encoded: HashMap::new() }
+    }
+
+    /// Finds a texture by the name used in material files. Already loaded textures, like images embedded in models,
+    /// and textures embedded in the binary take precedence over fetching.
+    /// Colors of `ColorSpace::Srgb` textures are converted to linear ones whenever shaders sample them.
+    pub fn load(&mut self, gl: &GL, name: &str, space: ColorSpace) -> TextureId {
+        if let Some(&texture_id) = self.by_name.get(&(name.to_string(), space)) {
+            return texture_id;
+        }
+        let embedded = EMBEDDED_TEXTURES.iter().find(|(embedded_name, _)| *embedded_name == name).map(|(_, bytes)| bytes.to_vec());
+        match self.encoded.get(name).cloned().or(embedded) {
+            Some(bytes) => self.load_embedded(gl, name, &bytes, space).expect("Cannot decode embedded texture"),
+            None => self.load_url(gl, name, space),
+        }
+    }
+
+    /// Decodes and uploads an image embedded in the binary, usually with `include_bytes!`
+    pub fn load_embedded(&mut self, gl: &GL, name: &str, bytes: &[u8], space: ColorSpace) -> Result<TextureId, ImageError> {
+        if let Some(&texture_id) = self.by_name.get(&(name.to_string(), space)) {
+            return Ok(texture_id);
+        }
+        let image = Image::decode(bytes)?;
+        let texture = Self::create_texture(gl);
+        gl.pixel_storei(GL::UNPACK_FLIP_Y_WEBGL, 1); // images are stored top to bottom, texture coordinates go bottom to top
+        gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            GL::TEXTURE_2D, 0, internal_format(space), image.width as i32, image.height as i32, 0, GL::RGBA, GL::UNSIGNED_BYTE, Some(&image.pixels))
+            .expect("Cannot upload texture");
+        // the flag applies to every later upload, cubemaps and data textures must not be flipped
+        gl.pixel_storei(GL::UNPACK_FLIP_Y_WEBGL, 0);
+        gl.generate_mipmap(GL::TEXTURE_2D);
+        gl.bind_texture(GL::TEXTURE_2D, None);
+        self.encoded.insert(name.to_string(), bytes.to_vec());
+        Ok(self.register(name, space, texture))
+    }
+
+    /// Starts fetching an image from the directory of models, the texture stays plain white until the browser loads
+    /// and decodes it. It's known by `name` like any other texture, so exported scenes reference it the same way.
+    fn load_url(&mut self, gl: &GL, name: &str, space: ColorSpace) -> TextureId {
+        let texture = Self::create_texture(gl);
+        gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+            GL::TEXTURE_2D, 0, internal_format(space), 1, 1, 0, GL::RGBA, GL::UNSIGNED_BYTE, Some(&[255, 255, 255, 255]))
+            .expect("Cannot upload placeholder texture");
+        gl.bind_texture(GL::TEXTURE_2D, None);
+
+        let image = HtmlImageElement::new().expect("Cannot create image element");
+        image.set_cross_origin(Some("anonymous"));
+        let texture = Rc::new(texture);
+        {
+            let gl = gl.clone();
+            // the image may arrive after nothing uses the texture anymore, it shouldn't keep the texture alive
+            let texture = Rc::downgrade(&texture);
+            let image2 = image.clone();
+            let on_load = Closure::wrap(Box::new(move || {
+                let texture = match texture.upgrade() {
+                    Some(texture) => texture,
+                    None => return,
+                };
+                gl.bind_texture(GL::TEXTURE_2D, Some(&*texture));
+                gl.pixel_storei(GL::UNPACK_FLIP_Y_WEBGL, 1);
+                gl.tex_image_2d_with_u32_and_u32_and_html_image_element(GL::TEXTURE_2D, 0, internal_format(space), GL::RGBA, GL::UNSIGNED_BYTE, &image2)
+                    .expect("Cannot upload texture");
+                gl.pixel_storei(GL::UNPACK_FLIP_Y_WEBGL, 0);
+                gl.generate_mipmap(GL::TEXTURE_2D);
+                gl.bind_texture(GL::TEXTURE_2D, None);
+            }) as Box<dyn Fn()>);
+            image.set_onload(Some(on_load.as_ref().unchecked_ref()));
+            on_load.forget();
+        }
+        image.set_src(&url(name));
+        self.register_shared(name, space, texture)
+    }
+
+    /// Name the texture was loaded by, as used in material files
+    pub fn name(&self, texture_id: TextureId) -> Option<&str> {
+        self.by_name.iter().find(|(_, &id)| id == texture_id).map(|((name, _), _)| name.as_str())
+    }
+
+    /// Image file the texture was decoded from, not known for fetched textures
+    pub fn encoded(&self, name: &str) -> Option<&[u8]> {
+        self.encoded.get(name).map(|bytes| &bytes[..])
+    }
+
+    pub fn texture_set(&self, textures: &MaterialTextures) -> TextureSet {
+        let get = |id: Option<TextureId>| id.map(|id| self.textures[id].clone());
+        TextureSet { diffuse: get(textures.diffuse), normal: get(textures.normal), emissive: get(textures.emissive) }
+    }
+
+    fn create_texture(gl: &GL) -> Texture {
+        let texture = Texture::new(gl);
+        gl.bind_texture(GL::TEXTURE_2D, Some(&*texture));
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::REPEAT as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::REPEAT as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR_MIPMAP_LINEAR as i32);
+        gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+        texture
+    }
+
+    fn register(&mut self, name: &str, space: ColorSpace, texture: Texture) -> TextureId {
+        self.register_shared(name, space, Rc::new(texture))
+    }
+
+    fn register_shared(&mut self, name: &str, space: ColorSpace, texture: Rc<Texture>) -> TextureId {
+        self.textures.push(texture);
+        let texture_id = self.textures.len() - 1;
+        self.by_name.insert((name.to_string(), space), texture_id);
+        texture_id
+    }
+}
+
+/// Where a texture that isn't loaded yet is fetched from, `name` is the name used in material files
+pub fn url(name: &str) -> String {
+    format!("{}{}", TEXTURES_PATH, name)
+}
+
+/// sRGB textures are decoded by the GPU before filtering, which blends colors correctly
+fn internal_format(space: ColorSpace) -> i32 {
+    match space {
+        ColorSpace::Srgb => GL::SRGB8_ALPHA8 as i32,
+        ColorSpace::Linear => GL::RGBA8 as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::texture::{Image, ImageError};
+
+    #[test]
+    fn decodes_embedded_png() {
+        let image = Image::decode(include_bytes!("../models/snow.png")).unwrap();
+
+        assert_eq!(image.width, 64);
+        assert_eq!(image.height, 64);
+        assert_eq!(image.pixels.len(), 64 * 64 * 4);
+        assert!(image.pixels.chunks(4).all(|p| p[3] == 255), "snow texture should be opaque");
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        let result = Image::decode(b"GIF89a");
+
+        assert!(matches!(result, Err(ImageError::UnknownFormat)));
+    }
+
+    #[test]
+    fn reports_broken_jpeg() {
+        let result = Image::decode(&[0xff, 0xd8, 0xff, 0x00, 0x01]);
+
+        assert!(matches!(result, Err(ImageError::Jpeg(_))));
+    }
+}
@@ -0,0 +1,153 @@
+use std::f32::consts::PI;
+
+use cgmath::{Matrix4, Point3, Rad, vec2, vec3};
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::camera::View;
+use crate::color::LinearRgb;
+use crate::lights::Light;
+use crate::mesh::{Mesh, Vertex};
+use crate::model::{Instance, Model};
+use crate::render::{FRAMES_PER_SECOND, Pipeline};
+use crate::render::queue::RenderQueue;
+use crate::shader::Shader;
+use crate::xmas_tree::sky::CULMINATION_AZIMUTH;
+
+/// Curtains hang around the tree at this distance, inside the sky dome
+const DISTANCE: f32 = 60.;
+const BOTTOM: f32 = 8.;
+const HEIGHT: f32 = 18.;
+/// Part of the horizon a curtain spans, in radians
+const SPAN: f32 = 1.6;
+const SEGMENTS: u32 = 64;
+/// How far the curtains sway towards and away from the tree, as in aurora.vert
+#[cfg(test)]
+const SWAY: f32 = 2.;
+/// (azimuth relative to the moon's, scale, seed of the animation) of every curtain
+const CURTAINS: [(f32, f32, f32); 3] = [(0., 1., 0.), (-0.5, 0.85, 2.3), (0.6, 1.1, 4.7)];
+/// Faint green the aurora adds to the ambient light at its brightest
+const AMBIENT_TINT: LinearRgb = LinearRgb::new(0.005, 0.04, 0.02);
+
+/// A curtain along an arc around the tree, centered on the positive part of Z axis.
+/// Texture coordinates go along the curtain and from its bottom edge up.
+fn curtain() -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(2 * (SEGMENTS + 1) as usize);
+    for segment in 0..=SEGMENTS {
+        let along = segment as f32 / SEGMENTS as f32;
+        let angle = SPAN * (along - 0.5);
+        // folds, so the curtain isn't a plain arc
+        let distance = DISTANCE + 4. * (3. * 2. * PI * along).sin();
+        let (x, z) = (distance * angle.sin(), distance * angle.cos());
+        let normal = vec3(-angle.sin(), 0., -angle.cos());
+        vertices.push(Vertex { position: Point3::new(x, BOTTOM, z), normal, uv: vec2(along, 0.) });
+        vertices.push(Vertex { position: Point3::new(x, BOTTOM + HEIGHT, z), normal, uv: vec2(along, 1.) });
+    }
+    let indices = (0..SEGMENTS)
+        .flat_map(|segment| {
+            let (bottom, top) = (2 * segment, 2 * segment + 1);
+            vec![bottom, top, bottom + 2, top, top + 2, bottom + 2]
+        })
+        .collect();
+    (vertices, indices)
+}
+
+/// Ambient light the aurora adds to the scene, it pulses slowly
+fn ambient_tint(seconds: f32, visibility: f32) -> LinearRgb {
+    AMBIENT_TINT * ((0.75 + 0.25 * (seconds * 0.5).sin()) * visibility)
+}
+
+/// Glowing curtains high in the sky, seen only at night
+pub struct Aurora {
+    mesh: Mesh,
+    /// Frames since the aurora appeared, the animation follows them
+    frame: u32,
+}
+
+impl Aurora {
+    pub fn new(gl: &GL) -> Self {
+        let (vertices, indices) = curtain();
+        let mut mesh = Mesh::new(gl, vertices, indices, CURTAINS.len());
+        mesh.set_pipeline(Pipeline::Aurora);
+        let instances = CURTAINS.iter()
+            .map(|&(azimuth, scale, seed)| {
+                let transform = Matrix4::from_angle_y(Rad(CULMINATION_AZIMUTH + azimuth)) * Matrix4::from_scale(scale);
+                // the aurora program doesn't use materials
+                Instance { user: seed, ..Instance::new(transform, 0) }
+            })
+            .collect();
+        mesh.set_instances(instances);
+        Aurora { mesh, frame: 0 }
+    }
+
+    fn seconds(&self) -> f32 {
+        self.frame as f32 / FRAMES_PER_SECOND
+    }
+
+    /// Sets uniforms of the aurora program, the program has to be in use. `visibility` is 1 at night, 0 during the day.
+    pub fn set_uniforms(&self, gl: &GL, shader: &Shader, visibility: f32) {
+        shader.set_float(gl, "time", self.seconds());
+        shader.set_float(gl, "visibility", visibility);
+    }
+
+    /// Light tinting the ambient term of everything lit, it comes from all around
+    pub fn light(&self, visibility: f32) -> Light {
+        Light::directional(vec3(0., 1., 0.), ambient_tint(self.seconds(), visibility), LinearRgb::BLACK, LinearRgb::BLACK)
+    }
+}
+
+impl Model for Aurora {
+    fn next_frame(&mut self, _gl: &GL) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        self.mesh.draw(gl, queue, view);
+    }
+
+    fn meshes(&self) -> Vec<&Mesh> {
+        // like falling snow, it's weather, not a part of the decorated scene
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{EuclideanSpace, InnerSpace};
+
+    use crate::camera::{FAR_PLANE, MAX_DISTANCE};
+    use crate::color::LinearRgb;
+    use crate::xmas_tree::aurora::{ambient_tint, AMBIENT_TINT, BOTTOM, CURTAINS, curtain, SEGMENTS, SWAY};
+
+    #[test]
+    fn curtain_is_a_strip_from_its_bottom_edge_up() {
+        let (vertices, indices) = curtain();
+
+        assert_eq!(vertices.len(), 2 * (SEGMENTS as usize + 1));
+        assert_eq!(indices.len(), 6 * SEGMENTS as usize);
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+        for v in &vertices {
+            assert_eq!(v.position.y == BOTTOM, v.uv.y == 0.);
+        }
+        assert_eq!(vertices.first().unwrap().uv.x, 0.);
+        assert_eq!(vertices.last().unwrap().uv.x, 1.);
+    }
+
+    #[test]
+    fn curtains_are_in_front_of_the_far_plane_from_any_camera_position() {
+        let largest_scale = CURTAINS.iter().map(|&(_, scale, _)| scale).fold(0., f32::max);
+        let reach = curtain().0.iter()
+            .map(|v| (v.position.to_vec() * largest_scale).magnitude() + SWAY)
+            .fold(0., f32::max);
+
+        assert!(MAX_DISTANCE + reach < FAR_PLANE, "{}", MAX_DISTANCE + reach);
+    }
+
+    #[test]
+    fn ambient_tint_is_faint_and_gone_during_the_day() {
+        for seconds in [0., 1., 4., 100.] {
+            let tint = ambient_tint(seconds, 1.).0;
+            assert!(tint.y > 0. && tint.y <= AMBIENT_TINT.0.y, "{:?}", tint);
+        }
+        assert_eq!(ambient_tint(5., 0.), LinearRgb::BLACK);
+    }
+}
@@ -1,15 +1,17 @@
-use core::f32::consts::PI;
 use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_6, FRAC_PI_8};
 use std::iter::FromIterator;
 
-use cgmath::{Matrix4, Point3, vec3, Vector3};
+use cgmath::{Matrix4, Point3, Vector3};
 use web_sys::WebGl2RenderingContext as GL;
 
+use crate::camera::View;
 use crate::coords::CylindricalPoint3;
-use crate::material::{Material, MaterialId, Materials};
-use crate::mesh::{Mesh, Vertex};
+use crate::material::MaterialId;
+use crate::material_library::MaterialLibrary;
+use crate::mesh::{Mesh, primitives};
 use crate::model::{Instance, Model};
-use crate::shader::Shader;
+use crate::render::Pipeline;
+use crate::render::queue::RenderQueue;
 
 struct Bauble {
     center: CylindricalPoint3<f32>,
@@ -18,48 +20,21 @@ struct Bauble {
 
 pub struct Baubles {
     mesh: Mesh,
-    baubles: Vec<Bauble>,
+    /// Translucent baubles need their own pipeline, so they can't share the draw call with the others
+    glass: Mesh,
 }
 
 impl Baubles {
-    pub fn new(gl: &GL, materials: &mut Materials) -> Self {
-        let precision = 8 as u32;
-        let radius = 0.2 as f32;
+    pub fn new(gl: &GL, materials: &MaterialLibrary) -> Self {
+        let radius: f32 = 0.2;
 
-        let ambient: Vector3<f32> = vec3(0.1745, 0.01175, 0.01175);
-        let diffuse: Vector3<f32> = vec3(0.61424, 0.04136, 0.04136);
-        let specular: Vector3<f32> = vec3(0.727811, 0.626959, 0.626959);
-        let shininess: f32 = 76.8;
-        let red = Material { ambient, diffuse, specular, shininess };
-        let red_id = materials.add(gl, red);
-
-        let ambient: Vector3<f32> = vec3(0.01175, 0.01175, 0.1745);
-        let diffuse: Vector3<f32> = vec3(0.04136, 0.04136, 0.61424);
-        let specular: Vector3<f32> = vec3(0.626959, 0.626959, 0.61424);
-        let shininess: f32 = 76.8;
-        let blue = Material { ambient, diffuse, specular, shininess };
-        let blue_id = materials.add(gl, blue);
-
-        let ambient: Vector3<f32> = vec3(0.1745, 0.1745, 0.01175);
-        let diffuse: Vector3<f32> = vec3(0.61424, 0.61424, 0.04136);
-        let specular: Vector3<f32> = vec3(0.727811, 0.727811, 0.626959);
-        let shininess: f32 = 76.8;
-        let yellow = Material { ambient, diffuse, specular, shininess };
-        let yellow_id = materials.add(gl, yellow);
-
-        let ambient: Vector3<f32> = vec3(0.01175, 0.1745, 0.1745);
-        let diffuse: Vector3<f32> = vec3(0.04136, 0.61424, 0.61424);
-        let specular: Vector3<f32> = vec3(0.626959, 0.727811, 0.727811);
-        let shininess: f32 = 76.8;
-        let light_blue = Material { ambient, diffuse, specular, shininess };
-        let light_blue_id = materials.add(gl, light_blue);
-
-        let ambient: Vector3<f32> = vec3(0.1745, 0.01175, 0.1745);
-        let diffuse: Vector3<f32> = vec3(0.61424, 0.04136, 0.61424);
-        let specular: Vector3<f32> = vec3(0.727811, 0.626959, 0.727811);
-        let shininess: f32 = 76.8;
-        let violet = Material { ambient, diffuse, specular, shininess };
-        let violet_id = materials.add(gl, violet);
+        let material = |name: &str| materials.get_by_name(name).expect("Bauble material missing in the library");
+        let red_id = material("red_bauble");
+        let blue_id = material("blue_bauble");
+        let yellow_id = material("yellow_bauble");
+        let light_blue_id = material("light_blue_bauble");
+        let violet_id = material("violet_bauble");
+        let glass_id = material("glass_bauble");
 
         let baubles: Vec<Bauble> = vec![
             Bauble { center: CylindricalPoint3::new(0., 0., 2.7), material_id: red_id },
@@ -92,83 +67,41 @@ impl Baubles {
             Bauble { center: CylindricalPoint3::new(4., 13. * FRAC_PI_8, -4.1), material_id: yellow_id },
             Bauble { center: CylindricalPoint3::new(4., 17. * FRAC_PI_8, -4.1), material_id: red_id },
             Bauble { center: CylindricalPoint3::new(4., 21. * FRAC_PI_8, -4.1), material_id: blue_id },
+            Bauble { center: CylindricalPoint3::new(1.5, 2.8, 0.25), material_id: glass_id },
+            Bauble { center: CylindricalPoint3::new(2.2, -2.2, -0.85), material_id: glass_id },
+            Bauble { center: CylindricalPoint3::new(3., 2.3, -1.8), material_id: glass_id },
+            Bauble { center: CylindricalPoint3::new(3.6, 3. * FRAC_PI_6, -3.), material_id: glass_id },
+            Bauble { center: CylindricalPoint3::new(3.6, 7. * FRAC_PI_6, -3.), material_id: glass_id },
+            Bauble { center: CylindricalPoint3::new(4., 9. * FRAC_PI_8, -4.1), material_id: glass_id },
+            Bauble { center: CylindricalPoint3::new(4., 15. * FRAC_PI_8, -4.1), material_id: glass_id },
         ];
 
-        let mut vertices: Vec<Vertex> = Vec::with_capacity(2 * precision.pow(2) as usize);
-        let mut indices: Vec<u32> = Vec::with_capacity(3 * 4 * precision.pow(2) as usize);
-
-        Self::gen_sphere(&mut vertices, &mut indices, Point3::new(0., 0., 0.), radius, precision);
+        let (glass, solid): (Vec<Bauble>, Vec<Bauble>) = baubles.into_iter().partition(|b| b.material_id == glass_id);
+        let mut glass = Self::mesh(gl, radius, &glass);
+        glass.set_pipeline(Pipeline::for_material(materials.get(glass_id).unwrap()));
+        Self { mesh: Self::mesh(gl, radius, &solid), glass }
+    }
 
-        let mesh = Mesh::new(gl, vertices, indices, baubles.len());
+    fn mesh(gl: &GL, radius: f32, baubles: &[Bauble]) -> Mesh {
+        // (precision, max distance from the camera), baubles further away are just a few pixels wide
+        let lods = [(12, 14.), (8, 20.), (4, f32::INFINITY)].iter()
+            .map(|&(precision, max_distance)| {
+                let (vertices, indices) = primitives::uv_sphere(radius, 2 * precision, precision);
+                (vertices, indices, max_distance)
+            })
+            .collect();
+        let mut mesh = Mesh::with_lods(gl, lods, baubles.len());
 
         let instances = Vec::from_iter(
             baubles.iter()
                 .map(|b| {
                     let center_cartesian: Point3<f32> = b.center.into();
                     let center_arr: [f32; 3] = center_cartesian.into();
-                    Instance { model: Matrix4::from_translation(Vector3::from(center_arr)), material_id : b.material_id }
+                    Instance::new(Matrix4::from_translation(Vector3::from(center_arr)), b.material_id)
                 })
         );
-        mesh.fill_instances_vbo(gl, &instances);
-        Self { mesh, baubles }
-    }
-
-    fn gen_sphere(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, center: Point3<f32>, radius: f32, precision: u32) {
-        Self::gen_vertices(vertices, center, radius, precision);
-        Self::gen_indices(indices, precision)
-    }
-
-    fn gen_vertices(vertices: &mut Vec<Vertex>, center: Point3<f32>, radius: f32, precision: u32) {
-        let angle_diff = PI / precision as f32;
-
-        vertices.push(Vertex { position: Point3::new(center.x, center.y + radius, center.z), normal: vec3(0., 1., 0.) });
-
-        for layer in 1..precision {
-            let v_angle = angle_diff * layer as f32;   // vertically I'm doing only half rotation
-            for slice in 0..(2 * precision) {
-                let h_angle = angle_diff * slice as f32;   // horizontally I'm doing full circle
-                let layer_radius = radius * v_angle.sin();
-                let vertex = Point3::new(center.x + layer_radius * h_angle.sin(), center.y + radius * v_angle.cos(), center.z + layer_radius * h_angle.cos());
-
-                vertices.push(Vertex { position: vertex, normal: vec3(h_angle.sin(), v_angle.cos(), h_angle.cos()) });
-            }
-        }
-
-        vertices.push(Vertex { position: Point3::new(center.x, center.y - radius, center.z), normal: vec3(0., -1., 0.) });
-    }
-
-    fn gen_indices(indices: &mut Vec<u32>, precision: u32) {
-        let find_index = |layer: u32, slice: u32| {
-            // layers 0 and precision have only 1 vertex
-            if layer == 0 {
-                0
-            } else if layer == precision {
-                (layer - 1) * 2 * precision + 1
-            } else {
-                (layer - 1) * 2 * precision + 1 + slice % (2 * precision)
-            }
-        };
-
-        // I'm generating indices for triangles drawn between this and previous layers of vertices
-        let mut layer = 1;
-        for slice in 0..2 * precision {
-            // first layer has only triangles
-            indices.extend([find_index(layer - 1, slice), find_index(layer, slice), find_index(layer, slice + 1)].iter());
-        }
-
-        for layer in 2..precision {
-            for slice in 0..2 * precision {
-                // midddle layers are actually traapezoids, I need two triangles per slice
-                indices.extend([find_index(layer - 1, slice), find_index(layer, slice), find_index(layer, slice + 1)].iter());
-                indices.extend([find_index(layer - 1, slice + 1), find_index(layer - 1, slice), find_index(layer, slice + 1)].iter());
-            }
-        }
-
-        layer = precision;
-        for slice in 0..2 * precision {
-            // last layer has only triangles
-            indices.extend([find_index(layer - 1, slice + 1), find_index(layer - 1, slice), find_index(layer, slice)].iter());
-        }
+        mesh.set_instances(instances);
+        mesh
     }
 }
 
@@ -177,7 +110,12 @@ impl Model for Baubles {
         // nothing changes
     }
 
-    fn draw(&self, gl: &GL, shader: &Shader) {
-        self.mesh.draw_instances(gl, shader, self.baubles.len());
+    fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        self.mesh.draw(gl, queue, view);
+        self.glass.draw(gl, queue, view);
+    }
+
+    fn meshes(&self) -> Vec<&Mesh> {
+        vec![&self.mesh, &self.glass]
     }
 }
@@ -1,38 +1,30 @@
-use cgmath::{Matrix4, Point3, SquareMatrix, vec3, Vector3};
+use cgmath::{Matrix4, vec3};
 use web_sys::WebGl2RenderingContext as GL;
 
-use crate::material::{Material, Materials};
-use crate::mesh::{Mesh, Vertex};
+use crate::camera::View;
+use crate::material_library::MaterialLibrary;
+use crate::mesh::{Mesh, primitives};
 use crate::model::{Instance, Model};
-use crate::shader::Shader;
+use crate::render::queue::RenderQueue;
+use crate::texture::Textures;
 
 pub struct Ground {
     mesh: Mesh,
 }
 
 impl Ground {
-    pub fn new(gl: &GL, materials: &mut Materials) -> Self {
-        let vertices: Vec<Vertex> = vec![
-            Vertex { position: Point3::new(-10., -5., -10.), normal: vec3(0., 1., 0.) },   // far
-            Vertex { position: Point3::new(-10., -5., 10.), normal: vec3(0., 1., 0.) }, // left
-            Vertex { position: Point3::new(10., -5., -10.), normal: vec3(0., 1., 0.) }, // right
-            Vertex { position: Point3::new(10., -5., 10.), normal: vec3(0., 1., 0.) }, // near
-        ];
-
-        let indices: Vec<u32> = vec![
-            0, 1, 2,
-            1, 3, 2,
-        ];
-
-        let ambient: Vector3<f32> = vec3(1., 1., 1.);
-        let diffuse: Vector3<f32> = vec3(0.623960, 0.686685, 0.693872);
-        let specular: Vector3<f32> = vec3(0.5, 0.5, 0.5);
-        let shininess: f32 = 225.;
-        let material = Material { ambient, diffuse, specular, shininess };
-        let material_id = materials.add(gl, material);
-
-        let mesh = Mesh::new(gl, vertices, indices, 1);
-        mesh.fill_instances_vbo(gl, &vec![Instance { model: Matrix4::identity(), material_id }]);
+    pub fn new(gl: &GL, materials: &MaterialLibrary, textures: &Textures) -> Self {
+        let (mut vertices, indices) = primitives::plane(20., 20., 1);
+        for vertex in &mut vertices {
+            vertex.uv *= 5.;   // snow texture repeats 5 times in each direction
+        }
+
+        let material_id = materials.get_by_name("ground").expect("Ground material missing in the library");
+        let material = materials.get(material_id).unwrap();
+
+        let mut mesh = Mesh::new(gl, vertices, indices, 1);
+        mesh.set_textures(textures.texture_set(&material.textures));
+        mesh.set_instances(vec![Instance::new(Matrix4::from_translation(vec3(0., -5., 0.)), material_id)]);
         Self { mesh }
     }
 }
@@ -42,7 +34,11 @@ impl Model for Ground {
         // nothing changes
     }
 
-    fn draw(&self, gl: &GL, shader: &Shader) {
-        self.mesh.draw_single(gl, shader);
+    fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        self.mesh.draw(gl, queue, view);
+    }
+
+    fn meshes(&self) -> Vec<&Mesh> {
+        vec![&self.mesh]
     }
 }
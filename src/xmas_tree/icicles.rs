@@ -0,0 +1,83 @@
+use std::f32::consts::PI;
+
+use cgmath::{Deg, Matrix4, Point3, vec3};
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::camera::View;
+use crate::coords::CylindricalPoint3;
+use crate::material_library::MaterialLibrary;
+use crate::mesh::{Mesh, primitives};
+use crate::model::{Instance, Model};
+use crate::render::Pipeline;
+use crate::render::queue::RenderQueue;
+
+/// Tips of the branch tiers, the same ones baubles hang from: (distance from the trunk, height, number of icicles)
+const TIERS: [(f32, f32, u32); 6] = [(1.1, 1.4, 5), (1.5, 0.35, 7), (2.2, -0.75, 9), (3., -1.7, 11), (3.6, -2.9, 13), (4., -4., 15)];
+const RADIUS: f32 = 0.04;
+
+pub struct Icicles {
+    mesh: Mesh,
+}
+
+impl Icicles {
+    pub fn new(gl: &GL, materials: &MaterialLibrary) -> Self {
+        let material_id = materials.get_by_name("icicle").expect("Icicle material missing in the library");
+
+        let instances: Vec<Instance> = TIERS.iter()
+            .flat_map(|&(distance, height, count)| (0..count).map(move |i| {
+                // shifted by a quarter so that icicles and baubles of the tier don't line up
+                let angle = 2. * PI * (i as f32 + 0.25) / count as f32;
+                // a fixed pattern of lengths, so that the row doesn't look cut to size
+                let length = 0.2 + 0.08 * ((i * 7 + count) % 5) as f32;
+                (CylindricalPoint3::new(distance, angle, height).into(), length)
+            }))
+            .map(|(top, length)| Instance::new(hanging(top, length), material_id))
+            .collect();
+
+        // a unit cone, every icicle scales it to its own length
+        let (vertices, indices) = primitives::cone(RADIUS, 1., 8);
+        let mut mesh = Mesh::new(gl, vertices, indices, instances.len());
+        mesh.set_pipeline(Pipeline::for_material(materials.get(material_id).unwrap()));
+        mesh.set_instances(instances);
+        Self { mesh }
+    }
+}
+
+/// Turns the unit cone upside down, with its base at `top` and the tip `length` below
+fn hanging(top: Point3<f32>, length: f32) -> Matrix4<f32> {
+    Matrix4::from_translation(vec3(top.x, top.y - length / 2., top.z))
+        * Matrix4::from_nonuniform_scale(1., length, 1.)
+        * Matrix4::from_angle_x(Deg(180.))
+}
+
+impl Model for Icicles {
+    fn next_frame(&mut self, _gl: &GL) {
+        // nothing changes
+    }
+
+    fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        self.mesh.draw(gl, queue, view);
+    }
+
+    fn meshes(&self) -> Vec<&Mesh> {
+        vec![&self.mesh]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Point3, Transform};
+
+    use crate::xmas_tree::icicles::hanging;
+
+    #[test]
+    fn icicles_hang_from_their_top() {
+        let transform = hanging(Point3::new(1., 2., 3.), 0.5);
+
+        let tip = transform.transform_point(Point3::new(0., 0.5, 0.));
+        let base = transform.transform_point(Point3::new(0., -0.5, 0.));
+
+        assert!((tip - Point3::new(1., 1.5, 3.)).magnitude() < 1e-6, "{:?}", tip);
+        assert!((base - Point3::new(1., 2., 3.)).magnitude() < 1e-6, "{:?}", base);
+    }
+}
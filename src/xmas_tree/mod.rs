@@ -1,5 +1,11 @@
+mod aurora;
 mod baubles;
 mod ground;
+mod icicles;
 pub mod scene;
 mod snow;
+mod sky;
 mod tree;
+
+/// Azimuth (as in `SphericalPoint3`) the camera starts at, the moon and the aurora are on the opposite side of the tree
+const CAMERA_AZIMUTH: f32 = 0.9;
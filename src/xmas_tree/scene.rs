@@ -1,61 +1,316 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
 use cgmath::{Point3, vec3};
 use web_sys::WebGl2RenderingContext as GL;
 
 use crate::camera::Camera;
+use crate::color::LinearRgb;
 use crate::coords::SphericalPoint3;
-use crate::lights::Lights;
-use crate::material::Materials;
+use crate::lights::{Light, LightId, Lights};
+use crate::culling::Aabb;
+use crate::environment::Environment;
+use crate::exporter;
+use crate::fog::{Atmosphere, Fog};
+use crate::loader::{gltf, ImportedModel, LoadError, ModelData, NormalMode, obj};
+use crate::material_library::MaterialLibrary;
 use crate::model::Model;
-use crate::shader::Shader;
+use crate::render::Pipeline;
+use crate::render::post::PostProcessing;
+use crate::render::queue::RenderQueue;
+use crate::render::state::StateCache;
+use crate::shader::{ProgramSource, Shader, STANDARD_PROGRAM};
+use crate::shader::cache::ShaderCache;
+use crate::shader::error::ShaderError;
+use crate::shader::preprocessor::Defines;
+use crate::texture::Textures;
+use crate::xmas_tree::CAMERA_AZIMUTH;
+use crate::xmas_tree::aurora::Aurora;
 use crate::xmas_tree::baubles::Baubles;
 use crate::xmas_tree::ground::Ground;
+use crate::xmas_tree::icicles::Icicles;
+use crate::xmas_tree::sky::SkyDome;
 use crate::xmas_tree::snow::Snow;
 use crate::xmas_tree::tree::Tree;
 
+/// Imported models stand on the ground next to the tree, scaled to this size
+const IMPORTED_MODEL_POSITION: [f32; 3] = [6., -5., 0.];
+const IMPORTED_MODEL_SIZE: f32 = 3.;
+/// Regenerating the environment takes a while, it follows the sky in steps of this fraction of the day
+const ENVIRONMENT_STEP: f32 = 1. / 256.;
+
+/// Optional parts of the scene, chosen by the page
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct SceneConfig {
+    /// Aurora curtains in the night sky, they tint the ambient light a little
+    pub aurora: bool,
+    /// Fog and how much it snows
+    pub atmosphere: Atmosphere,
+    /// Calmer animation, for people who prefer reduced motion
+    pub reduced_motion: bool,
+}
+
 pub struct Scene {
     pub camera: Camera,
     lights: Lights,
-    shader: Shader,
+    /// The moon, or the sun during the day
+    celestial_light: LightId,
+    /// Ambient tint of the aurora, dark without it
+    aurora_light: LightId,
+    shaders: ShaderCache,
+    /// Program lit surfaces are drawn with and the features it's compiled with
+    program: ProgramSource,
+    features: Defines,
+    /// Compiled program of every pipeline
+    pipelines: HashMap<Pipeline, Rc<Shader>>,
+    state: StateCache,
+    post: PostProcessing,
+    sky: SkyDome,
+    aurora: Option<Aurora>,
+    fog: Fog,
+    atmosphere: Atmosphere,
+    reduced_motion: bool,
+    /// Surroundings reflective materials mirror, generated from the sky
+    environment: Environment,
+    /// Time of day the environment was generated for
+    environment_time: f32,
+    pub materials: MaterialLibrary,
+    textures: Textures,
     models: Vec<Box<dyn Model>>,
+    /// Kept out of `models`, the atmosphere sets how hard it snows
+    snow: Snow,
+    /// Where in `models` the tree is, so it can be replaced
+    tree_index: usize,
+    /// Models replacing the tree are fitted to the same space
+    tree_bounds: Aabb,
 }
 
 impl Scene {
-    pub fn setup(gl: &GL) -> Self {
-        let camera = Camera::new(gl, SphericalPoint3::new(18., 1.7, 0.9), Point3::new(0., -1., 0.));
+    /// Fails if the program doesn't compile on this device, a simpler one can be tried then
+    pub fn setup(gl: &GL, program: ProgramSource, features: Defines, config: SceneConfig) -> Result<Self, ShaderError> {
+        let camera = Camera::new(gl, SphericalPoint3::new(18., 1.7, CAMERA_AZIMUTH), Point3::new(0., -1., 0.));
+        let sky = SkyDome::new(gl);
         let mut lights = Lights::setup(gl);
-        lights.add(gl, Point3::new(10., 100., 10.), vec3(0.3, 0.3, 0.3), vec3(0.2, 0.2, 0.2), vec3(0., 0., 0.));
-        lights.add(gl, Point3::new(5., 6., 2.), vec3(0.2, 0.2, 0.2), vec3(2., 2., 2.), vec3(0.5, 0.5, 0.5));
+        let celestial_light = lights.add(gl, sky.light());
+        lights.add(gl, Light::point(Point3::new(5., 6., 2.), LinearRgb::new(0.2, 0.2, 0.2), LinearRgb::new(2., 2., 2.), LinearRgb::new(0.5, 0.5, 0.5)));
+        let aurora_light = lights.add(gl, Scene::no_light());
+        let aurora = if config.aurora { Some(Aurora::new(gl)) } else { None };
+
+        let mut shaders = ShaderCache::new();
+        let pipelines = Pipeline::ALL.iter()
+            .map(|&pipeline| Ok((pipeline, Scene::pipeline_shader(gl, &mut shaders, pipeline, &program, &features)?)))
+            .collect::<Result<_, ShaderError>>()?;
+        let post = PostProcessing::new(gl, &mut shaders, gl.drawing_buffer_width(), gl.drawing_buffer_height())?;
+
+        let environment = Environment::new(gl, &sky.sky());
+        let environment_time = sky.time_of_day();
+        let mut textures = Textures::setup();
+        let mut materials = MaterialLibrary::setup(gl, &mut textures);
+        let tree = Tree::new(gl, &mut materials, &mut textures);
+        let tree_bounds = tree.bounds();
+        let models = Scene::add_models(gl, tree, &mut materials, &mut textures);
+        let mut snow = Snow::new(gl, &materials);
+        snow.set_intensity(config.atmosphere.snow_intensity);
+        snow.set_reduced_motion(config.reduced_motion);
+        let mut scene = Scene {
+            camera, lights, celestial_light, aurora_light, shaders, program, features, pipelines, state: StateCache::new(), post,
+            sky, aurora, fog: Fog::setup(gl), atmosphere: config.atmosphere, reduced_motion: config.reduced_motion,
+            environment, environment_time, materials, textures, models, snow, tree_index: 1, tree_bounds,
+        };
+        scene.update_sky(gl);
+        Ok(scene)
+    }
+
+    /// Placeholder for optional lights, it doesn't add anything
+    fn no_light() -> Light {
+        Light::directional(vec3(0., 1., 0.), LinearRgb::BLACK, LinearRgb::BLACK, LinearRgb::BLACK)
+    }
 
-        let shader = Shader::new(gl);
+    /// Lit pipelines use the program of the scene, it's the unlit one on devices that cannot compile the standard one
+    fn pipeline_shader(gl: &GL, shaders: &mut ShaderCache, pipeline: Pipeline, program: &ProgramSource, features: &Defines) -> Result<Rc<Shader>, ShaderError> {
+        let pipeline_program = pipeline.program();
+        let program = if pipeline_program == STANDARD_PROGRAM { program } else { &pipeline_program };
+        shaders.get(gl, program, &pipeline.defines(features))
+    }
+
+    fn add_models(gl: &GL, tree: Tree, materials: &mut MaterialLibrary, textures: &mut Textures) -> Vec<Box<dyn Model>> {
+        vec![
+            Box::new(Ground::new(gl, materials, textures)),
+            Box::new(tree),
+            Box::new(Baubles::new(gl, materials)),
+            Box::new(Icicles::new(gl, materials)),
+        ]
+    }
 
-        let mut materials = Materials::setup(gl);
-        let models = Scene::add_models(gl, &mut materials);
-        Scene { camera, lights, shader, models }
+    /// Adds a model parsed from OBJ and optional MTL files, `images` are texture files materials may use
+    pub fn import_obj(&mut self, gl: &GL, obj: &[u8], mtl: Option<&[u8]>, images: &HashMap<String, Vec<u8>>, normals: NormalMode) -> Result<(), LoadError> {
+        let mut data = obj::parse(obj, mtl, normals)?;
+        data.add_referenced_images(images);
+        self.add_imported(gl, data)
     }
 
-    fn add_models(gl: &GL, materials: &mut Materials) -> Vec<Box<dyn Model>> {
-        let mut models: Vec<Box<dyn Model>> = Vec::new();
-        models.push(Box::new(Ground::new(gl, materials)));
-        models.push(Box::new(Tree::new(gl, materials)));
-        models.push(Box::new(Baubles::new(gl, materials)));
-        models.push(Box::new(Snow::new(gl, materials)));
-        models
+    /// Adds a glTF model, `resources` are files it references, like `.bin` buffers
+    pub fn import_gltf(&mut self, gl: &GL, name: &str, bytes: &[u8], resources: &HashMap<String, Vec<u8>>) -> Result<(), LoadError> {
+        let data = gltf::parse(name, bytes, resources)?;
+        self.add_imported(gl, data)
+    }
+
+    /// Replaces the tree with a glTF model, scaled to take the same space
+    pub fn replace_tree_with_gltf(&mut self, gl: &GL, name: &str, bytes: &[u8], resources: &HashMap<String, Vec<u8>>) -> Result<(), LoadError> {
+        let data = gltf::parse(name, bytes, resources)?;
+        let extent = self.tree_bounds.max - self.tree_bounds.min;
+        let bottom = Point3::new(self.tree_bounds.center().x, self.tree_bounds.min.y, self.tree_bounds.center().z);
+        let transform = ImportedModel::fit(&data, bottom, extent.x.max(extent.y).max(extent.z));
+        let model = ImportedModel::new(gl, data, transform, &mut self.materials, &mut self.textures)?;
+        self.tree_bounds = model.bounds();
+        self.models[self.tree_index] = Box::new(model);
+        Ok(())
+    }
+
+    fn add_imported(&mut self, gl: &GL, data: ModelData) -> Result<(), LoadError> {
+        let transform = ImportedModel::fit(&data, Point3::from(IMPORTED_MODEL_POSITION), IMPORTED_MODEL_SIZE);
+        let model = ImportedModel::new(gl, data, transform, &mut self.materials, &mut self.textures)?;
+        self.models.push(Box::new(model));
+        Ok(())
+    }
+
+    /// Turns an optional feature of the program on or off, each combination is compiled only once.
+    /// If the new variant doesn't compile, the scene keeps the current one.
+    pub fn set_shader_feature(&mut self, gl: &GL, feature: &str, enabled: bool) -> Result<(), ShaderError> {
+        let features = if enabled { self.features.clone().flag(feature) } else { self.features.clone().without(feature) };
+        let pipelines = Pipeline::ALL.iter()
+            .map(|&pipeline| Ok((pipeline, Scene::pipeline_shader(gl, &mut self.shaders, pipeline, &self.program, &features)?)))
+            .collect::<Result<_, ShaderError>>()?;
+        self.pipelines = pipelines;
+        self.features = features;
+        Ok(())
+    }
+
+    /// Turns one of `post::EFFECTS` on or off
+    pub fn set_post_effect(&mut self, gl: &GL, effect: &str, enabled: bool) -> Result<(), ShaderError> {
+        self.post.set_effect(gl, &mut self.shaders, effect, enabled)
+    }
+
+    /// Draws with MSAA of about `samples` samples per pixel, 0 or 1 turns it off, returns the count actually used
+    pub fn set_msaa_samples(&mut self, gl: &GL, samples: i32) -> i32 {
+        self.post.set_samples(gl, samples)
+    }
+
+    /// Seconds a whole day takes, 0 stops the time of day
+    pub fn set_day_length(&mut self, seconds: f32) {
+        self.sky.set_day_length(seconds);
+    }
+
+    /// Moves to the time of day given as a fraction of the day since midnight
+    pub fn set_time_of_day(&mut self, gl: &GL, time: f32) {
+        self.sky.set_time_of_day(time);
+        self.update_sky(gl);
+    }
+
+    pub fn config(&self) -> SceneConfig {
+        SceneConfig { aurora: self.aurora.is_some(), atmosphere: self.atmosphere, reduced_motion: self.reduced_motion }
+    }
+
+    /// Adds or removes optional parts of the scene
+    pub fn configure(&mut self, gl: &GL, config: SceneConfig) {
+        if config.aurora != self.aurora.is_some() {
+            self.aurora = if config.aurora { Some(Aurora::new(gl)) } else { None };
+        }
+        self.atmosphere = config.atmosphere;
+        self.snow.set_intensity(config.atmosphere.snow_intensity);
+        self.reduced_motion = config.reduced_motion;
+        self.snow.set_reduced_motion(config.reduced_motion);
+        self.update_sky(gl);
+    }
+
+    /// Stops falling snow, or lets it fall again
+    pub fn toggle_snow(&mut self) {
+        self.snow.set_paused(!self.snow.is_paused());
+    }
+
+    /// Lights, the fog and the environment follow the sky
+    fn update_sky(&mut self, gl: &GL) {
+        self.lights.set(gl, self.celestial_light, self.sky.light());
+        let aurora_light = self.aurora.as_ref().map_or_else(Scene::no_light, |aurora| aurora.light(self.sky.darkness()));
+        self.lights.set(gl, self.aurora_light, aurora_light);
+        self.fog.update(gl, self.sky.sky().horizon, self.atmosphere.fog_density());
+        let time = self.sky.time_of_day();
+        // the time wraps around at midnight, a step back is as far as a step forward
+        let elapsed = (time - self.environment_time).rem_euclid(1.);
+        if elapsed.min(1. - elapsed) >= ENVIRONMENT_STEP {
+            self.environment.update(gl, &self.sky.sky());
+            self.environment_time = time;
+        }
+    }
+
+    pub fn on_window_resize(&mut self, gl: &GL) {
+        self.camera.on_window_resize(gl);
+        self.post.resize(gl, gl.drawing_buffer_width(), gl.drawing_buffer_height());
+    }
+
+    /// Everything in the scene as it looks right now, with images of textures that can't be fetched by their names
+    pub fn export(&self) -> ModelData {
+        let meshes = self.models.iter().flat_map(|model| model.meshes()).collect::<Vec<_>>();
+        let mut data = exporter::model_data(
+            meshes.iter().map(|mesh| {
+                let (vertices, indices) = mesh.geometry();
+                (vertices, indices, mesh.instances())
+            }),
+            |material_id| {
+                let material = self.materials.get(material_id).expect("Instance uses unknown material");
+                let name = self.materials.name(material_id).map_or_else(|| format!("material{}", material_id), String::from);
+                exporter::material_data(&name, material, &self.textures)
+            });
+        exporter::add_images(&mut data, &self.textures);
+        data
     }
 
     pub fn next_frame(&mut self, gl: &GL) {
+        self.sky.next_frame();
+        if let Some(aurora) = &mut self.aurora {
+            aurora.next_frame(gl);
+        }
+        self.update_sky(gl);
         for d in &mut self.models {
             d.next_frame(gl);
         }
+        self.snow.next_frame(gl);
     }
 
-    pub fn draw(&self, gl: &GL) {
-        gl.clear_color(0.0157, 0., 0.3607, 1.0);
+    pub fn draw(&mut self, gl: &GL) {
+        self.post.begin(gl);
+        // models could have been added or textures loaded since the last frame, both change bindings,
+        // and the last frame could have ended with depth writes off
+        self.state.reset(gl);
+        // the sky dome covers the background, the color only matters for its seams
+        let [r, g, b]: [f32; 3] = self.sky.sky().horizon.into();
+        gl.clear_color(r, g, b, 1.0);
         gl.clear(GL::COLOR_BUFFER_BIT | GL::DEPTH_BUFFER_BIT);
 
-        gl.use_program(Some(&self.shader.program));
-
+        self.materials.bind(gl);
+        self.environment.bind(gl);
+        let sky_shader = &self.pipelines[&Pipeline::Sky];
+        gl.use_program(Some(&*sky_shader.program));
+        self.sky.set_uniforms(gl, sky_shader);
+        if let Some(aurora) = &self.aurora {
+            let aurora_shader = &self.pipelines[&Pipeline::Aurora];
+            gl.use_program(Some(&*aurora_shader.program));
+            aurora.set_uniforms(gl, aurora_shader, self.sky.darkness());
+        }
+        let view = self.camera.view(gl);
+        let mut queue = RenderQueue::new();
         for d in &self.models {
-            d.draw(gl, &self.shader);
+            d.draw(gl, &mut queue, &view);
         }
+        self.snow.draw(gl, &mut queue, &view);
+        self.sky.draw(gl, &mut queue, &view);
+        if let Some(aurora) = &self.aurora {
+            aurora.draw(gl, &mut queue, &view);
+        }
+        let pipelines = &self.pipelines;
+        queue.execute(gl, &mut self.state, |pipeline| &pipelines[&pipeline]);
+        gl.bind_vertex_array(None);
+
+        self.post.finish(gl);
     }
 }
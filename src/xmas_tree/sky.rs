@@ -0,0 +1,247 @@
+//! Sky around the scene: a gradient dome with twinkling stars and the moon, which also lights the scene.
+//! An optional day cycle moves the sun and the moon across the sky and shifts the colors of the sky and the light.
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, vec3, Vector3};
+use web_sys::WebGl2RenderingContext as GL;
+
+use crate::camera::View;
+use crate::color::{LinearRgb, Srgb};
+use crate::environment::Sky;
+use crate::lights::Light;
+use crate::mesh::{Mesh, primitives};
+use crate::model::Instance;
+use crate::render::{FRAMES_PER_SECOND, Pipeline};
+use crate::render::queue::RenderQueue;
+use crate::shader::Shader;
+use crate::xmas_tree::CAMERA_AZIMUTH;
+
+/// Inside the far plane of the camera, but further than anything in the scene
+const DOME_RADIUS: f32 = 90.;
+/// Azimuth (as in `SphericalPoint3`) the sun and the moon are highest at, opposite to where the camera starts,
+/// so that the moon is behind the tree
+pub const CULMINATION_AZIMUTH: f32 = CAMERA_AZIMUTH + PI;
+/// A low winter sun, and a moon in view of the camera
+const CULMINATION_ELEVATION: f32 = 0.35;
+/// Time of day the scene starts at, as a fraction of the day since midnight
+const MIDNIGHT: f32 = 0.;
+
+const NIGHT_AMBIENT: LinearRgb = LinearRgb::new(0.3, 0.3, 0.3);
+const MOONLIGHT: LinearRgb = LinearRgb::new(0.18, 0.2, 0.26);
+const DAY_AMBIENT: LinearRgb = LinearRgb::new(0.7, 0.7, 0.7);
+const SUNLIGHT: LinearRgb = LinearRgb::new(1.4, 1.3, 1.15);
+
+/// Night sky behind the tree, the scene used to be cleared with this color
+const BACKGROUND: Srgb = Srgb::new(0.0157, 0., 0.3607);
+
+fn night_sky() -> Sky {
+    Sky {
+        zenith: Srgb::new(0.005, 0., 0.12).to_linear(),
+        horizon: BACKGROUND.to_linear(),
+        ground: Srgb::new(0.45, 0.5, 0.6).to_linear(),
+    }
+}
+
+fn day_sky() -> Sky {
+    Sky {
+        zenith: Srgb::new(0.25, 0.5, 0.85).to_linear(),
+        horizon: Srgb::new(0.75, 0.85, 0.95).to_linear(),
+        ground: Srgb::new(0.9, 0.92, 0.95).to_linear(),
+    }
+}
+
+/// Horizon around sunrise and sunset
+fn dusk_horizon() -> LinearRgb {
+    Srgb::new(0.95, 0.5, 0.3).to_linear()
+}
+
+/// Direction towards the sun, `time` is the fraction of the day since midnight.
+/// It rises at 6:00, culminates at noon and the moon is always on the opposite side.
+fn sun_direction(time: f32) -> Vector3<f32> {
+    let south = vec3(CULMINATION_AZIMUTH.sin(), 0., CULMINATION_AZIMUTH.cos());
+    let east = vec3(south.z, 0., -south.x);
+    let highest = south * CULMINATION_ELEVATION.cos() + vec3(0., CULMINATION_ELEVATION.sin(), 0.);
+    let angle = 2. * PI * (time - 0.25);
+    east * angle.cos() + highest * angle.sin()
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+/// 0 at night, 1 during the day, in between while the sun is near the horizon
+fn daylight(sun: Vector3<f32>) -> f32 {
+    smoothstep(-0.1, 0.1, sun.y)
+}
+
+/// 1 with the sun right at the horizon, fading as it gets further from it
+fn twilight(sun: Vector3<f32>) -> f32 {
+    1. - (sun.y.abs() / 0.15).min(1.)
+}
+
+fn sky(time: f32) -> Sky {
+    let sun = sun_direction(time);
+    let (night, day) = (night_sky(), day_sky());
+    let amount = daylight(sun);
+    let horizon = night.horizon.mix(day.horizon, amount);
+    Sky {
+        zenith: night.zenith.mix(day.zenith, amount),
+        horizon: horizon.mix(dusk_horizon(), 0.8 * twilight(sun)),
+        ground: night.ground.mix(day.ground, amount),
+    }
+}
+
+/// The sun during the day and the moon at night, whichever is above the horizon.
+/// Both fade out before they set, so the light doesn't jump when they take turns.
+fn celestial_light(time: f32) -> Light {
+    let sun = sun_direction(time);
+    let ambient = NIGHT_AMBIENT.mix(DAY_AMBIENT, daylight(sun));
+    if sun.y > 0. {
+        let sunlight = SUNLIGHT * smoothstep(0., 0.1, sun.y);
+        Light::directional(sun, ambient, sunlight, sunlight * 0.5)
+    } else {
+        let moon = -sun;
+        Light::directional(moon, ambient, MOONLIGHT * smoothstep(0., 0.1, moon.y), LinearRgb::BLACK)
+    }
+}
+
+/// Time of day, standing still unless the day has a length
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DayCycle {
+    /// Fraction of the day since midnight, 0.5 is noon
+    time: f32,
+    /// Seconds a whole day takes, `None` keeps the time still
+    day_length: Option<f32>,
+}
+
+impl DayCycle {
+    fn advance(&mut self, seconds: f32) {
+        if let Some(day_length) = self.day_length {
+            self.time = (self.time + seconds / day_length).rem_euclid(1.);
+        }
+    }
+}
+
+impl Default for DayCycle {
+    fn default() -> Self {
+        DayCycle { time: MIDNIGHT, day_length: None }
+    }
+}
+
+pub struct SkyDome {
+    mesh: Mesh,
+    /// Frames since the start, stars twinkle with them
+    frame: u32,
+    cycle: DayCycle,
+}
+
+impl SkyDome {
+    pub fn new(gl: &GL) -> Self {
+        let (vertices, indices) = primitives::uv_sphere(DOME_RADIUS, 32, 16);
+        let mut mesh = Mesh::new(gl, vertices, indices, 1);
+        mesh.set_pipeline(Pipeline::Sky);
+        // the sky program doesn't use materials, the dome follows the camera on its own
+        mesh.set_instances(vec![Instance::new(Matrix4::identity(), 0)]);
+        SkyDome { mesh, frame: 0, cycle: DayCycle::default() }
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+        self.cycle.advance(1. / FRAMES_PER_SECOND);
+    }
+
+    /// Fraction of the day since midnight
+    pub fn time_of_day(&self) -> f32 {
+        self.cycle.time
+    }
+
+    pub fn set_time_of_day(&mut self, time: f32) {
+        self.cycle.time = time.rem_euclid(1.);
+    }
+
+    /// Seconds a whole day takes, 0 stops the time
+    pub fn set_day_length(&mut self, seconds: f32) {
+        self.cycle.day_length = if seconds > 0. { Some(seconds) } else { None };
+    }
+
+    /// Colors of the sky right now
+    pub fn sky(&self) -> Sky {
+        sky(self.cycle.time)
+    }
+
+    /// Light of the moon, or of the sun during the day
+    pub fn light(&self) -> Light {
+        celestial_light(self.cycle.time)
+    }
+
+    /// 1 at night, 0 during the day, for whatever is seen only in the dark
+    pub fn darkness(&self) -> f32 {
+        1. - daylight(sun_direction(self.cycle.time))
+    }
+
+    /// Sets uniforms of the sky program, the program has to be in use
+    pub fn set_uniforms(&self, gl: &GL, shader: &Shader) {
+        let sky = self.sky();
+        let sun = sun_direction(self.cycle.time).normalize();
+        shader.set_vec3(gl, "zenith", sky.zenith.into());
+        shader.set_vec3(gl, "horizon", sky.horizon.into());
+        shader.set_vec3(gl, "ground", sky.ground.into());
+        shader.set_vec3(gl, "sunDirection", sun.into());
+        shader.set_vec3(gl, "moonDirection", (-sun).into());
+        shader.set_float(gl, "starVisibility", self.darkness());
+        shader.set_float(gl, "time", self.frame as f32 / FRAMES_PER_SECOND);
+    }
+
+    pub fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        self.mesh.draw(gl, queue, view);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use crate::xmas_tree::sky::{celestial_light, DayCycle, day_sky, MIDNIGHT, night_sky, sky, sun_direction};
+
+    #[test]
+    fn moon_is_up_at_night_and_sun_during_the_day() {
+        assert!(sun_direction(MIDNIGHT).y < 0.);
+        assert!(sun_direction(0.5).y > 0.);
+        assert!(sun_direction(0.25).y.abs() < 1e-6, "the sun rises at 6:00");
+    }
+
+    #[test]
+    fn sky_changes_from_night_to_day() {
+        assert_eq!(sky(MIDNIGHT), night_sky());
+        let noon = sky(0.5);
+        assert!((noon.zenith.0 - day_sky().zenith.0).magnitude() < 1e-6, "{:?}", noon);
+        assert!((noon.horizon.0 - day_sky().horizon.0).magnitude() < 1e-6, "{:?}", noon);
+        let dusk = sky(0.75);
+        assert!(dusk.horizon.0.x > dusk.horizon.0.z, "sunsets are red");
+    }
+
+    #[test]
+    fn light_fades_out_while_the_sun_and_the_moon_take_turns() {
+        let before_sunrise = celestial_light(0.249);
+        let after_sunrise = celestial_light(0.251);
+        let midnight = celestial_light(MIDNIGHT);
+        let noon = celestial_light(0.5);
+
+        assert!(before_sunrise.diffuse().0.x < 0.01 && after_sunrise.diffuse().0.x < 0.01);
+        assert!(midnight.diffuse().0.x > 0.1);
+        assert!(noon.diffuse().0.x > midnight.diffuse().0.x);
+        assert!(noon.ambient().0.x > midnight.ambient().0.x);
+    }
+
+    #[test]
+    fn day_cycle_wraps_around_midnight() {
+        let mut still = DayCycle::default();
+        still.advance(100.);
+        assert_eq!(still, DayCycle::default());
+
+        let mut cycle = DayCycle { time: 0.9, day_length: Some(10.) };
+        cycle.advance(2.);
+        assert!((cycle.time - 0.1).abs() < 1e-5, "{}", cycle.time);
+    }
+}
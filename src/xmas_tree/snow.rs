@@ -2,16 +2,19 @@ extern crate rand;
 
 use core::f32::consts::PI;
 
-use cgmath::{Euler, Matrix4, Point3, Rad, vec3, Vector3};
+use cgmath::{Euler, Matrix4, Point3, Rad, vec2, vec3, Vector3};
 use rand::{Rng, SeedableRng};
 use rand::distributions::Uniform;
 use rand::rngs::SmallRng;
 use web_sys::WebGl2RenderingContext as GL;
 
-use crate::material::{Material, MaterialId, Materials};
+use crate::camera::View;
+use crate::material::MaterialId;
+use crate::material_library::MaterialLibrary;
 use crate::mesh::{Mesh, Vertex};
 use crate::model::{Instance, Model};
-use crate::shader::Shader;
+use crate::render::Pipeline;
+use crate::render::queue::RenderQueue;
 
 const SNOW_X_MIN: f32 = -10.;
 const SNOW_X_MAX: f32 = 10.;
@@ -23,70 +26,90 @@ const SNOW_Z_MAX: f32 = 10.;
 const SNOWFLAKE_FALL_VELOCITY: f32 = 0.01;
 const SNOWFLAKE_MAX_RANDOM_OFFSET: f32 = 0.01;
 const SNOWFLAKE_MAX_RANDOM_ROTATION: f32 = PI / 180. * 10.;
+const SNOWFLAKE_MIN_BRIGHTNESS: f32 = 0.8;
 const MAX_SNOWFLAKES: usize = 5_000;
+/// Snowflakes drift and spin this much slower for people who prefer reduced motion
+const REDUCED_MOTION_SPEED: f32 = 0.25;
 
 struct Snowflake {
     position: Vector3<f32>,
     rotation: Vector3<Rad<f32>>,
+    brightness: f32,
 }
 
 pub struct Snow {
     mesh: Mesh,
     snowflakes: Vec<Snowflake>,
+    /// How many of the snowflakes fall, the rest wait until it snows harder
+    falling: usize,
+    /// 1 normally, slower with reduced motion
+    speed: f32,
+    paused: bool,
     material_id: MaterialId,
 }
 
 impl Snow {
-    pub fn new(gl: &GL, materials: &mut Materials) -> Self {
-        let ambient: Vector3<f32> = vec3(1., 1., 1.);
-        let diffuse: Vector3<f32> = vec3(0.623960, 0.686685, 0.693872);
-        let specular: Vector3<f32> = vec3(0.5, 0.5, 0.5);
-        let shininess: f32 = 225.;
-        let material = Material { ambient, diffuse, specular, shininess };
-        let material_id = materials.add(gl, material);
+    pub fn new(gl: &GL, materials: &MaterialLibrary) -> Self {
+        let material_id = materials.get_by_name("snow").expect("Snow material missing in the library");
 
         let (vertices, indices) = Snow::gen_snowflake_mesh();
-        let mesh = Mesh::new(gl, vertices, indices, MAX_SNOWFLAKES);
+        let mut mesh = Mesh::new(gl, vertices, indices, MAX_SNOWFLAKES);
+        mesh.set_pipeline(Pipeline::Snow);
 
         let snowflakes = Snow::gen_snowflakes();
-        let snow = Self { mesh, snowflakes, material_id };
+        let mut snow = Self { mesh, snowflakes, falling: MAX_SNOWFLAKES, speed: 1., paused: false, material_id };
         let instances = snow.gen_instances();
-        snow.mesh.fill_instances_vbo(gl, &instances);
+        snow.mesh.set_instances(instances);
         snow
     }
 
+    /// Fraction of snowflakes falling, 0 stops the snow
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.falling = falling_snowflakes(intensity);
+    }
+
+    /// Slows the snow down, for people who prefer reduced motion
+    pub fn set_reduced_motion(&mut self, reduced: bool) {
+        self.speed = if reduced { REDUCED_MOTION_SPEED } else { 1. };
+    }
+
+    /// Snowflakes stay where they are while paused
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
     fn gen_snowflake_mesh() -> (Vec<Vertex>, Vec<u32>) {
         let radius: f32 = 0.05;
         let normal: Vector3<f32> = vec3(1., 0., 0.);
         let mut vertices: Vec<Vertex> = vec![];
 
-        let angle_diff = PI / 3 as f32;
+        let angle_diff = PI / 3.;
 
         for i in 0..6 {
             let angle = i as f32 * angle_diff;
-            // upper side
-            vertices.push(Vertex { position: Point3::new(0., radius * angle.cos(), radius * angle.sin()), normal });
-            // bottom side
-            vertices.push(Vertex { position: Point3::new(-0., -radius * angle.cos(), -radius * angle.sin()), normal: -normal });
+            let uv = vec2(0.5 + 0.5 * angle.cos(), 0.5 + 0.5 * angle.sin());
+            vertices.push(Vertex { position: Point3::new(0., radius * angle.cos(), radius * angle.sin()), normal, uv });
         }
+        // a single side is enough, the snow pipeline doesn't cull back faces and lights them as front ones
         let indices: Vec<u32> = vec![
-            // upper side
-            8, 4, 0,
-            10, 6, 2,
-            // bottom side
-            1, 5, 9,
-            3, 7, 11,
+            4, 2, 0,
+            5, 3, 1,
         ];
 
         (vertices, indices)
     }
 
     fn gen_snowflakes() -> Vec<Snowflake> {
-        let mut snowflakes: Vec<Snowflake> = Vec::with_capacity(MAX_SNOWFLAKES as usize);
+        let mut snowflakes: Vec<Snowflake> = Vec::with_capacity(MAX_SNOWFLAKES);
         let x_range = Uniform::new(SNOW_X_MIN, SNOW_X_MAX);
         let y_range = Uniform::new(SNOW_Y_MIN, SNOW_Y_MAX);
         let z_range = Uniform::new(SNOW_Z_MIN, SNOW_Z_MAX);
         let angle_range = Uniform::new(0., 2. * PI);
+        let brightness_range = Uniform::new_inclusive(SNOWFLAKE_MIN_BRIGHTNESS, 1.);
         let mut rng = SmallRng::from_entropy();
         for _i in 0..MAX_SNOWFLAKES {
             let x_position = rng.sample(x_range);
@@ -97,19 +120,22 @@ impl Snow {
             let z_rotation = Rad(rng.sample(angle_range));
             let position = vec3(x_position, y_position, z_position);
             let rotation = vec3(x_rotation, y_rotation, z_rotation);
-            snowflakes.push(Snowflake { position, rotation });
+            let brightness = rng.sample(brightness_range);
+            snowflakes.push(Snowflake { position, rotation, brightness });
         }
         snowflakes
     }
 
     fn move_snowflakes(&mut self) {
         let mut rng = SmallRng::from_entropy();
-        let pos_offset_range = Uniform::new(-SNOWFLAKE_MAX_RANDOM_OFFSET as f32, SNOWFLAKE_MAX_RANDOM_OFFSET);
-        let rot_angle_range = Uniform::new(-SNOWFLAKE_MAX_RANDOM_ROTATION, SNOWFLAKE_MAX_RANDOM_ROTATION);
-        for i in 0..MAX_SNOWFLAKES as usize {
-            let mut snowflake = &mut self.snowflakes[i];
+        let max_offset = SNOWFLAKE_MAX_RANDOM_OFFSET * self.speed;
+        let max_rotation = SNOWFLAKE_MAX_RANDOM_ROTATION * self.speed;
+        let pos_offset_range = Uniform::new(-max_offset, max_offset);
+        let rot_angle_range = Uniform::new(-max_rotation, max_rotation);
+        for i in 0..MAX_SNOWFLAKES {
+            let snowflake = &mut self.snowflakes[i];
             let new_x_pos = snowflake.position.x + rng.sample(pos_offset_range);
-            let mut new_y_pos = snowflake.position.y + rng.sample(pos_offset_range) - SNOWFLAKE_FALL_VELOCITY;
+            let mut new_y_pos = snowflake.position.y + rng.sample(pos_offset_range) - SNOWFLAKE_FALL_VELOCITY * self.speed;
             if new_y_pos < SNOW_Y_MIN {
                 new_y_pos = SNOW_Y_MAX;
             }
@@ -124,26 +150,50 @@ impl Snow {
     }
 
     fn gen_instances(&self) -> Vec<Instance> {
-        let mut instances: Vec<Instance> = Vec::with_capacity(MAX_SNOWFLAKES);
-        for i in 0..MAX_SNOWFLAKES {
+        let mut instances: Vec<Instance> = Vec::with_capacity(self.falling);
+        for i in 0..self.falling {
             let snowflake = &self.snowflakes[i];
             let rotation = Matrix4::from(Euler { x: snowflake.rotation.x, y: snowflake.rotation.y, z: snowflake.rotation.z });
             let translation = Matrix4::from_translation(snowflake.position);
             let model = translation * rotation;
-            instances.push(Instance { model, material_id: self.material_id });
+            instances.push(Instance::new(model, self.material_id).with_tint(vec3(snowflake.brightness, snowflake.brightness, snowflake.brightness)));
         }
         instances
     }
 }
 
+fn falling_snowflakes(intensity: f32) -> usize {
+    (MAX_SNOWFLAKES as f32 * intensity.clamp(0., 1.)).round() as usize
+}
+
 impl Model for Snow {
-    fn next_frame(&mut self, gl: &GL) {
-        self.move_snowflakes();
+    fn next_frame(&mut self, _gl: &GL) {
+        if !self.paused {
+            self.move_snowflakes();
+        }
         let instances = self.gen_instances();
-        self.mesh.fill_instances_vbo(gl, &instances);
+        self.mesh.set_instances(instances);
+    }
+
+    fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        self.mesh.draw(gl, queue, view);
     }
 
-    fn draw(&self, gl: &GL, shader: &Shader) {
-        self.mesh.draw_instances(gl, shader, MAX_SNOWFLAKES);
+    fn meshes(&self) -> Vec<&Mesh> {
+        // falling snow is weather, not a part of the decorated scene
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xmas_tree::snow::{falling_snowflakes, MAX_SNOWFLAKES};
+
+    #[test]
+    fn intensity_is_the_fraction_of_snowflakes_falling() {
+        assert_eq!(falling_snowflakes(1.), MAX_SNOWFLAKES);
+        assert_eq!(falling_snowflakes(0.5), MAX_SNOWFLAKES / 2);
+        assert_eq!(falling_snowflakes(0.), 0);
+        assert_eq!(falling_snowflakes(3.), MAX_SNOWFLAKES);
     }
 }
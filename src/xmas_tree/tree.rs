@@ -1,62 +1,45 @@
-use cgmath::{Matrix4, Point3, vec3, Vector3};
-use tobj::{load_mtl_buf, load_obj_buf};
-use wasm_bindgen::__rt::std::io::BufReader;
+use cgmath::Matrix4;
 use web_sys::WebGl2RenderingContext as GL;
 
-use crate::material::{Material, Materials};
-use crate::mesh::{Mesh, Vertex};
-use crate::model::{Instance, Model};
-use crate::shader::Shader;
+use crate::camera::View;
+use crate::culling::Aabb;
+use crate::loader::{ImportedModel, NormalMode, obj};
+use crate::mesh::Mesh;
+use crate::material_library::MaterialLibrary;
+use crate::model::Model;
+use crate::render::queue::RenderQueue;
+use crate::texture::Textures;
 
-static TREE_MODEL: &'static str = include_str!("../../models/tree.obj");
-static TREE_MATERIALS: &'static str = include_str!("../../models/tree.mtl");
+static TREE_MODEL: &[u8] = include_bytes!("../../models/tree.obj");
+static TREE_MATERIALS: &[u8] = include_bytes!("../../models/tree.mtl");
 
 pub struct Tree {
-    meshes: Vec<Mesh>,
+    model: ImportedModel,
 }
 
 impl Tree {
-    pub fn new(gl: &GL, materials: &mut Materials) -> Self {
-        Self::from_model(gl, materials)
+    pub fn new(gl: &GL, materials: &mut MaterialLibrary, textures: &mut Textures) -> Self {
+        let data = obj::parse(TREE_MODEL, Some(TREE_MATERIALS), NormalMode::Smooth).expect("Cannot parse tree model");
+        let scaling = Matrix4::from_nonuniform_scale(1.8, 1., 1.8);
+        let model = ImportedModel::new(gl, data, scaling, materials, textures).expect("Cannot add tree model");
+        Self { model }
     }
 
-    fn from_model(gl: &GL, materials: &mut Materials) -> Self {
-        let mut model_reader = BufReader::new(TREE_MODEL.as_bytes());
-        let tree = load_obj_buf(&mut model_reader, false, |_p| load_mtl_buf(&mut BufReader::new(TREE_MATERIALS.as_bytes())));
-        let (models, model_materials) = tree.unwrap();
-        let mut meshes: Vec<Mesh> = vec![];
-        for mi in 0..models.len() {
-            let mut vertices: Vec<Vertex> = vec![];
-            let mut indices: Vec<u32> = vec![];
-            let mesh = models[mi].mesh.clone();
-            for vi in (0..mesh.positions.len()).step_by(3) {
-                let position = Point3::new(mesh.positions[vi], mesh.positions[vi+1], mesh.positions[vi+2]);
-                let normal = vec3(mesh.normals[vi], mesh.normals[vi+1], mesh.normals[vi+2]);
-                vertices.push(Vertex { position, normal });
-            }
-            indices.extend(mesh.indices.iter());
-
-            let material = &model_materials[models[mi].mesh.material_id.unwrap()];
-            let my_material = Material{ambient: Vector3::from(material.ambient), diffuse: Vector3::from(material.diffuse), specular: Vector3::from(material.specular), shininess: material.shininess};
-            let material_id = materials.add(gl, my_material);
-            let mesh = Mesh::new(gl, vertices, indices, 1);
-            let scaling = Matrix4::from_nonuniform_scale(1.8, 1., 1.8);
-            mesh.fill_instances_vbo(gl, &vec![Instance { model: scaling, material_id }]);
-            meshes.push(mesh);
-        }
-
-        Self { meshes }
+    pub fn bounds(&self) -> Aabb {
+        self.model.bounds()
     }
 }
 
 impl Model for Tree {
-    fn next_frame(&mut self, _gl: &GL) {
-        // nothing changes
+    fn next_frame(&mut self, gl: &GL) {
+        self.model.next_frame(gl);
     }
 
-    fn draw(&self, gl: &GL, shader: &Shader) {
-        for mesh in &self.meshes {
-            mesh.draw_single(gl, shader);
-        }
+    fn draw<'a>(&'a self, gl: &GL, queue: &mut RenderQueue<'a>, view: &View) {
+        self.model.draw(gl, queue, view);
+    }
+
+    fn meshes(&self) -> Vec<&Mesh> {
+        self.model.meshes()
     }
 }